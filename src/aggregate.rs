@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::call_index::CallIndex;
+use crate::graph::Graph;
+
+pub const UNKNOWN_FILE: &str = "<unknown>";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    File,
+    // directory prefix with at most this many path components
+    Directory(usize),
+}
+
+impl FromStr for Granularity {
+    type Err = String;
+
+    // file | dir[=DEPTH]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            None if s == "file" => Ok(Granularity::File),
+            None if s == "dir" => Ok(Granularity::Directory(1)),
+            Some(("dir", depth)) => match depth.parse() {
+                Ok(depth) if depth > 0 => Ok(Granularity::Directory(depth)),
+                _ => Err(format!("invalid directory depth '{}'", depth)),
+            },
+            _ => Err(format!("unknown aggregation '{}', expected file or dir=DEPTH", s)),
+        }
+    }
+}
+
+impl Granularity {
+    pub fn group(&self, file: &str) -> String {
+        match self {
+            Granularity::File => file.to_string(),
+            Granularity::Directory(depth) => {
+                let directories: Vec<&str> = file.split('/').filter(|part| !part.is_empty()).collect();
+                let directories = &directories[..directories.len().saturating_sub(1)];
+                if directories.is_empty() {
+                    ".".to_string()
+                } else {
+                    directories[..directories.len().min(*depth)].join("/")
+                }
+            }
+        }
+    }
+}
+
+// Collapses every function to the file or directory it is defined in. An edge
+// between two groups carries the call sites of all calls between their
+// functions, calls inside a group are dropped.
+pub fn aggregate(graph: &Graph, index: &dyn CallIndex, granularity: Granularity) -> Graph {
+    let mut groups: HashMap<&str, String> = HashMap::new();
+    for (file, function) in index.definitions() {
+        groups.entry(function).or_insert_with(|| granularity.group(file));
+    }
+    let group_of = |name: &str| groups.get(name).cloned().unwrap_or_else(|| UNKNOWN_FILE.to_string());
+
+    let mut aggregated = Graph::new();
+    for node in graph.nodes() {
+        aggregated.add_node(group_of(&node.name), 1);
+    }
+    for (caller, callee, edge) in graph.edges() {
+        let start = group_of(caller);
+        let end = group_of(callee);
+        if start == end {
+            continue;
+        }
+        let mut call_sites = edge.call_sites.clone();
+        if let Some(existing) = aggregated.edge_mut(&start, &end) {
+            // add_edge only merges distinct call sites
+            existing.call_sites.append(&mut call_sites);
+        } else if let Err(err) = aggregated.add_edge(start, end, call_sites) {
+            log::error!("{}", err);
+        }
+    }
+    aggregated
+}
+
+#[cfg(test)]
+mod aggregate_test;
@@ -0,0 +1,76 @@
+use super::*;
+use crate::call_index::MapIndex;
+use crate::searcher::CallSite;
+
+fn site(file: &str, line: u32) -> CallSite {
+    CallSite { file: file.to_string(), line, column: 1 }
+}
+
+fn strings(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn index() -> MapIndex {
+    let index_map = HashMap::from([
+        ("drivers/net/uart.c".to_string(), strings(&["uart_irq", "uart_read"])),
+        ("drivers/net/eth.c".to_string(), strings(&["eth_send"])),
+        ("ui/status.c".to_string(), strings(&["show_status"])),
+        ("main.c".to_string(), strings(&["main"])),
+    ]);
+    MapIndex::new(index_map, HashMap::new())
+}
+
+fn function_graph() -> Graph {
+    let mut graph = Graph::new();
+    for name in ["main", "uart_irq", "uart_read", "eth_send", "show_status", "printf"] {
+        graph.add_node(name.to_string(), 1);
+    }
+    let mut edge = |start: &str, end: &str, sites: Vec<CallSite>| {
+        graph.add_edge(start.to_string(), end.to_string(), sites).unwrap();
+    };
+    edge("main", "uart_irq", vec![site("main.c", 3)]);
+    edge("main", "eth_send", vec![site("main.c", 4), site("main.c", 5)]);
+    edge("uart_irq", "uart_read", vec![site("drivers/net/uart.c", 10)]);
+    edge("uart_irq", "show_status", vec![site("drivers/net/uart.c", 11)]);
+    edge("eth_send", "show_status", vec![site("drivers/net/eth.c", 7), site("drivers/net/eth.c", 9)]);
+    edge("show_status", "printf", vec![site("ui/status.c", 2)]);
+    graph
+}
+
+fn call_count(graph: &Graph, start: &str, end: &str) -> usize {
+    graph.edge(start, end).unwrap().call_count()
+}
+
+#[test]
+fn test_granularity() {
+    assert_eq!("file".parse::<Granularity>(), Ok(Granularity::File));
+    assert_eq!("dir".parse::<Granularity>(), Ok(Granularity::Directory(1)));
+    assert_eq!("dir=2".parse::<Granularity>(), Ok(Granularity::Directory(2)));
+    assert!("dir=0".parse::<Granularity>().is_err());
+    assert!("module".parse::<Granularity>().is_err());
+
+    assert_eq!(Granularity::Directory(1).group("drivers/net/uart.c"), "drivers");
+    assert_eq!(Granularity::Directory(2).group("drivers/net/uart.c"), "drivers/net");
+    assert_eq!(Granularity::Directory(5).group("drivers/net/uart.c"), "drivers/net");
+    assert_eq!(Granularity::Directory(1).group("main.c"), ".");
+}
+
+#[test]
+fn test_aggregate_by_file() {
+    let graph = aggregate(&function_graph(), &index(), Granularity::File);
+
+    assert_eq!(graph.nodes().count(), 5);
+    assert_eq!(call_count(&graph, "main.c", "drivers/net/eth.c"), 2);
+    assert_eq!(call_count(&graph, "ui/status.c", UNKNOWN_FILE), 1);
+    // uart_irq -> uart_read stays inside uart.c
+    assert!(!graph.graph_to_tuple().contains(&("drivers/net/uart.c".to_string(), "drivers/net/uart.c".to_string())));
+}
+
+#[test]
+fn test_aggregate_by_directory_sums_calls() {
+    let graph = aggregate(&function_graph(), &index(), Granularity::Directory(1));
+
+    assert_eq!(call_count(&graph, "drivers", "ui"), 3);
+    assert_eq!(call_count(&graph, ".", "drivers"), 3);
+    assert_eq!(graph.edge_count(), 3);
+}
@@ -4,11 +4,25 @@ use super::*;
 
 use regex::Regex;
 use crate::ast_generator::AstNode;
-use crate::searcher::{ParentChildNode, FunctionNode};
-
+use crate::dead_code::{DeadCodeReport, EntryPoints};
+use crate::impact::{FileChange, ImpactReport};
+use crate::rules::{RuleSet, Violation};
+use crate::stats::FunctionStats;
+use crate::validate::{self, Severity};
+use crate::prune::{PruneSpec, PrunedNode, PruningPolicy};
+use crate::query::Query;
+use crate::searcher::{CallSite, ParentChildNode, FunctionNode};
+
+/// Runs queries against an [`Index`](crate::Index) and collects the calls
+/// they select into a graph.
 pub struct Analyzer {
-    pub graph : graph::Graph,
+    graph : graph::Graph,
     lang_server : Box<dyn searcher::LSPServer>,
+    pruning: Box<dyn PruningPolicy>,
+    query_targets: HashSet<String>,
+    // values of the `$parameters` in queries
+    parameters: HashMap<String, String>,
+    pruned: Vec<PrunedNode>,
     //global_vars :HashSet<(String, HashSet<(String, String)>)>,
     //global_filter :HashSet<(String, String)>
 }
@@ -19,35 +33,120 @@ pub enum FilterName {
     FunctionNameFromIdent,
     File,
     Forced,
+    Cycles,
+    Path,
+    From,
+    To,
+    Count,
+    Depth,
 }
 
 
 impl Analyzer {
-    pub fn new(lsp_server: Box<dyn searcher::LSPServer>) -> Analyzer {
+    pub(crate) fn new(lsp_server: Box<dyn searcher::LSPServer>) -> Analyzer {
         let p = Analyzer {
-            graph: graph::Graph {
-                pet_graph: petgraph::Graph::new(),
-                nodes: HashSet::new(),
-            },
+            graph: graph::Graph::new(),
             lang_server: lsp_server,
+            pruning: PruneSpec::default().policy(),
+            query_targets: HashSet::new(),
+            parameters: HashMap::new(),
+            pruned: Vec::new(),
         };
         p
     }
 
-    pub fn parse(&mut self, input: &str){
-        let ast_result = ast_generator::parse_ast(input);
-        if ast_result.is_ok() {
-            for ast in ast_result.unwrap() {
-                match ast {
-                    AstNode::Statements(statements) => {
-                        self.interpret_statements(statements);
-                    }
-                    _ => {}
+    pub(crate) fn set_pruning_policy(&mut self, policy: Box<dyn PruningPolicy>) {
+        self.pruning = policy;
+    }
+
+    /// Name of the pruning policy applied to query results, like `fan-in=10`.
+    pub fn pruning_policy(&self) -> String {
+        self.pruning.name()
+    }
+
+    /// Binds `$parameters` for the queries run with [`Analyzer::try_parse`].
+    /// [`Analyzer::run`] binds the parameters of its query instead.
+    pub fn set_parameters(&mut self, parameters: HashMap<String, String>) {
+        self.parameters = parameters;
+    }
+
+    /// The bound `$parameters`, by name.
+    pub fn parameters(&self) -> &HashMap<String, String> {
+        &self.parameters
+    }
+
+    /// The graph built by [`Analyzer::try_parse`] since the last
+    /// [`Analyzer::clear`].
+    pub fn graph(&self) -> &graph::Graph {
+        &self.graph
+    }
+
+    /// The nodes the pruning policy removed from [`Analyzer::graph`].
+    pub fn pruned(&self) -> &[PrunedNode] {
+        &self.pruned
+    }
+
+    pub(crate) fn lang_server(&mut self) -> &mut dyn searcher::LSPServer {
+        &mut *self.lang_server
+    }
+
+    /// Forgets the graph and pruned nodes of the previous queries.
+    pub fn clear(&mut self) {
+        self.graph = graph::Graph::new();
+        self.query_targets.clear();
+        self.pruned.clear();
+    }
+
+    /// Runs `query` with its parameters on an empty graph and returns the
+    /// resulting call graph. The parameters and pruning policy of the query
+    /// only apply to this run.
+    pub fn run(&mut self, query: &Query) -> anyhow::Result<graph::Graph> {
+        self.clear();
+        let parameters = std::mem::replace(&mut self.parameters, query.parameters().clone());
+        let pruning = query.pruning().map(|spec| std::mem::replace(&mut self.pruning, spec.policy()));
+        let result = self.try_parse(query.text()).map_err(anyhow::Error::msg);
+        self.parameters = parameters;
+        if let Some(pruning) = pruning {
+            self.pruning = pruning;
+        }
+        result?;
+        Ok(std::mem::take(&mut self.graph))
+    }
+
+    // Panics on invalid queries, library users go through `run` or
+    // `try_parse`.
+    #[cfg(test)]
+    fn parse(&mut self, input: &str){
+        if let Err(err) = self.try_parse(input) {
+            panic!("unable to parse input: {}", err);
+        }
+    }
+
+    /// Adds the result of the query to [`Analyzer::graph`], with the bound
+    /// parameters. Fails with the diagnostics if the query is not valid.
+    pub fn try_parse(&mut self, input: &str) -> Result<(), String> {
+        ast_generator::parse_grammar(input).map_err(|err| format!("{}", err))?;
+        // building the ast panics on invalid regexes
+        let errors: Vec<String> = validate::validate_with(input, None, Some(&self.parameters))
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+        let ast_result = ast_generator::parse_ast(input).map_err(|err| format!("{}", err))?;
+        for ast in ast_result {
+            match ast {
+                AstNode::Statements(statements) => {
+                    self.interpret_statements(statements);
                 }
+                _ => {}
             }
-        } else {
-            panic!("unable to parse input: {:?}", ast_result.err());
         }
+        let pruned = self.pruning.prune_result(&mut self.graph, &self.query_targets);
+        self.pruned.extend(pruned);
+        Ok(())
     }
 
     fn interpret_statements(&mut self, ast_nodes: Vec<AstNode>) -> (HashSet<FunctionNode>, u32) {
@@ -79,6 +178,12 @@ impl Analyzer {
             AstNode::Statement { verb, scope } => {
                 if verb.len() > 0 {
                     let filter = self.interpret_verb(verb);
+                    if filter.contains_key(&FilterName::Cycles) {
+                        return (self.interpret_cycles(filter), 0);
+                    }
+                    if filter.contains_key(&FilterName::Path) {
+                        return (self.interpret_paths(filter), 0);
+                    }
                     parent_filter.push(filter.clone());
                 }
 
@@ -134,27 +239,19 @@ impl Analyzer {
                 let mut matched_parents: HashSet<String> = HashSet::new();
                 for child in child_names.to_owned() {
                     let connections = child.clone().match_strategy.do_match(parent.to_owned(), &mut self.lang_server);
-                    for is_match in connections.clone() {
+                    for (is_match, call_sites) in connections {
                         matched_parents.insert(is_match.0.clone());
                         self.graph.add_node(is_match.0.clone(), 1);
                         self.graph.add_node(is_match.1.clone(), 1);
-                        did_find_important_node = self.graph.add_edge(is_match.0.clone(), is_match.1.clone());
+                        did_find_important_node = self.add_edge(is_match.0.clone(), is_match.1.clone(), call_sites);
                         child_names_with_parents.insert(is_match.1.clone());
                     }
                 }
                 parents.insert(FunctionNode{ function_name: matched_parents, match_strategy:parent.match_strategy });
 
-                if did_find_important_node {
-                    //remove unimportant nodes from graph
-                    for child in child_names.clone() {
-                        for node in self.graph.nodes.clone() {
-                            if child.function_name.contains(&node.name.clone()) {
-                                if node.times_used < 2 {
-                                    self.graph.remove_node(node.clone());
-                                }
-                            }
-                        }
-                    }
+                for child in child_names.clone() {
+                    let pruned = self.pruning.prune_children(&mut self.graph, &child.function_name, did_find_important_node);
+                    self.pruned.extend(pruned);
                 }
             }
             let node = ParentChildNode {
@@ -178,12 +275,12 @@ impl Analyzer {
 
                 for mut grand_child in grand_children {
                     let connections = grand_child.match_strategy.do_match(parent.clone(), &mut self.lang_server);
-                    for connection in connections.clone() {
+                    for (connection, call_sites) in connections {
                         new_child_list.insert(connection.1.clone());
 
                         self.graph.add_node(connection.0.clone(), 1);
                         self.graph.add_node(connection.1.clone(), 1);
-                        self.graph.add_edge(connection.0.clone(), connection.1.clone());
+                        self.add_edge(connection.0.clone(), connection.1.clone(), call_sites);
                     }
                 }
 
@@ -198,37 +295,147 @@ impl Analyzer {
         if has_parent_filter {
             for parent in parent_names.clone() {
                 for name in parent.function_name.clone() {
+                    self.query_targets.insert(name.clone());
                     self.graph.add_node(name, 1);
                 }
             }
             parents = parent_names;
         } else {
             for parent in parents.clone() {
-                for node in self.graph.nodes.clone() {
-                    if parent.function_name.contains(&node.name.clone()) {
-                        for edge in self.graph.pet_graph.edge_indices() {
-                            if self.graph.pet_graph.edge_weight(edge).is_some() {
-                                let endpoints = self.graph.pet_graph.edge_endpoints(edge).unwrap();
-                                if endpoints.0 == endpoints.1 {
-                                    if self.graph.pet_graph.node_weight(endpoints.0).unwrap().to_owned() == node.name {
-                                        self.graph.pet_graph.remove_edge(edge);
-                                    }
-                                }
-                            }
-                        }
-                    }
+                for name in parent.function_name.iter() {
+                    self.graph.remove_self_loop(name);
                 }
             }
         }
         (parents, 0)
     }
 
+    // Adds every recursive function and the edges of its cycle to the graph.
+    // With a file filter only cycles with a function in a matching file are kept.
+    fn interpret_cycles(&mut self, filter: HashMap<FilterName, Regex>) -> HashSet<FunctionNode> {
+        let mut cycles = cycles::find_cycles(&self.lang_server.call_index());
+
+        if let Some(file_filter) = filter.get(&FilterName::File) {
+            let file_functions: HashSet<String> = self.lang_server
+                .find_func_name(vec![HashMap::from([(FilterName::File, file_filter.clone())])])
+                .into_iter()
+                .flat_map(|node| node.function_name)
+                .collect();
+            cycles.retain(|cycle| cycle.iter().any(|name| file_functions.contains(name)));
+        }
+
+        let mut members: HashSet<String> = HashSet::new();
+        for cycle in cycles {
+            let cycle_members: HashSet<String> = cycle.into_iter().collect();
+            for name in &cycle_members {
+                self.graph.add_node(name.clone(), 1);
+            }
+            let mut calls: Vec<(String, String)> = Vec::new();
+            let index = self.lang_server.call_index();
+            for caller in &cycle_members {
+                for callee in index.callees(caller).filter(|callee| cycle_members.contains(*callee)) {
+                    calls.push((caller.clone(), callee.to_string()));
+                }
+            }
+            drop(index);
+            let links = self.lang_server.find_link(cycle_members.clone(), cycle_members.clone());
+            for (caller, callee) in calls {
+                let call_sites = links.get(&(caller.clone(), callee.clone())).cloned().unwrap_or_default();
+                self.add_edge(caller.clone(), callee.clone(), call_sites);
+                self.graph.mark_cycle_edge(&caller, &callee);
+            }
+            members.extend(cycle_members);
+        }
+
+        if members.is_empty() {
+            return HashSet::new();
+        }
+        self.query_targets.extend(members.iter().cloned());
+        let node = ParentChildNode { function_name: members.clone() };
+        HashSet::from([FunctionNode { function_name: members, match_strategy: Box::new(node) }])
+    }
+
+    // Adds the shortest call chain, or the first `k` simple call chains, from
+    // a function matching `from` to a function matching `to`.
+    fn interpret_paths(&mut self, filter: HashMap<FilterName, Regex>) -> HashSet<FunctionNode> {
+        let (from_filter, to_filter) = match (filter.get(&FilterName::From), filter.get(&FilterName::To)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => {
+                log::error!("@path needs a from and a to parameter");
+                return HashSet::new();
+            }
+        };
+        let number = |name: FilterName, default: usize| -> usize {
+            match filter.get(&name) {
+                Some(value) => value.as_str().parse().unwrap_or_else(|_| {
+                    log::error!("@path: {:?} is not a number: {}", name, value);
+                    default
+                }),
+                None => default,
+            }
+        };
+        let count = number(FilterName::Count, 1);
+        let max_depth = number(FilterName::Depth, paths::DEFAULT_MAX_DEPTH);
+
+        let found = {
+            let index = self.lang_server.call_index();
+            let mut from: HashSet<String> = HashSet::new();
+            let mut to: HashSet<String> = HashSet::new();
+            for (_, function) in index.definitions() {
+                if from_filter.is_match(function) {
+                    from.insert(function.to_string());
+                }
+                if to_filter.is_match(function) {
+                    to.insert(function.to_string());
+                }
+            }
+            paths::find_paths(&index, &from, &to, count, max_depth)
+        };
+
+        let mut members: HashSet<String> = HashSet::new();
+        for path in found {
+            for name in &path {
+                self.graph.add_node(name.clone(), 1);
+            }
+            for (hop, pair) in path.windows(2).enumerate() {
+                let links = self.lang_server.find_link(HashSet::from([pair[0].clone()]), HashSet::from([pair[1].clone()]));
+                let call_sites = links.get(&(pair[0].clone(), pair[1].clone())).cloned().unwrap_or_default();
+                self.add_edge(pair[0].clone(), pair[1].clone(), call_sites);
+                self.graph.mark_hop(&pair[0], &pair[1], hop + 1);
+            }
+            members.extend(path);
+        }
+
+        if members.is_empty() {
+            return HashSet::new();
+        }
+        self.query_targets.extend(members.iter().cloned());
+        let node = ParentChildNode { function_name: members.clone() };
+        HashSet::from([FunctionNode { function_name: members, match_strategy: Box::new(node) }])
+    }
+
+    fn add_edge(&mut self, start: String, end: String, call_sites: Vec<CallSite>) -> bool {
+        match self.graph.add_edge(start, end, call_sites) {
+            Ok(has_children) => has_children,
+            Err(err) => {
+                log::error!("{}", err);
+                false
+            }
+        }
+    }
+
     fn interpret_verb(&mut self, ast_nodes: Vec<AstNode>) -> HashMap<FilterName, Regex>{
         let mut filter: HashMap<FilterName, Regex> = HashMap::new();
         for ast in ast_nodes {
             match ast {
                 AstNode::Verb { ident,named_parameter } =>{
                     match *ident {
+                        // a parameter always names a function
+                        AstNode::Parameter(name) => {
+                            if let Some(Ok(value)) = self.parameters.get(&name).map(|value| Regex::new(value)) {
+                                filter.insert(FilterName::FunctionNameFromIdent, value);
+                            }
+                        }
                         AstNode::Ident(ident) => {
                             match ident.as_str() {
                                 "filter" => {
@@ -243,6 +450,22 @@ impl Analyzer {
                                 "forced" => {
                                     filter.insert(FilterName::Forced, Regex::new("TRUE").unwrap());
                                 }
+                                "path" => {
+                                    filter.insert(FilterName::Path, Regex::new("TRUE").unwrap());
+                                    for parameter in named_parameter {
+                                        if let Some((name, value)) = self.interpret_define_options(parameter) {
+                                            filter.insert(name, value);
+                                        }
+                                    }
+                                }
+                                "recursive" | "cycles" => {
+                                    filter.insert(FilterName::Cycles, Regex::new("TRUE").unwrap());
+                                    for parameter in named_parameter {
+                                        if let Some((name, value)) = self.interpret_define_options(parameter) {
+                                            filter.insert(name, value);
+                                        }
+                                    }
+                                }
                                 _ => {
                                     filter.insert(FilterName::FunctionNameFromIdent, Regex::new(ident.as_str()).unwrap());
                                 }
@@ -274,6 +497,10 @@ impl Analyzer {
                     AstNode::Regex(regex) => {
                         value = regex;
                     }
+                    // checked by validate_with before the query runs
+                    AstNode::Parameter(name) => {
+                        value = Regex::new(self.parameters.get(&name)?).ok()?;
+                    }
                     _ =>{}
                 }
             }
@@ -293,12 +520,101 @@ impl Analyzer {
                      value)
                 )
             }
+            "from" => Some((FilterName::From, value)),
+            "to" => Some((FilterName::To, value)),
+            "k" => Some((FilterName::Count, value)),
+            "depth" => Some((FilterName::Depth, value)),
             _ => {
                 None
             }
         }
     }
 
+    pub(crate) fn dead_code(&mut self, entry_points: &EntryPoints) -> DeadCodeReport {
+        let mut roots: HashSet<String> = self.lang_server.call_index()
+            .definitions()
+            .filter(|(_, function)| entry_points.is_entry_point(function))
+            .map(|(_, function)| function.to_string())
+            .collect();
+        if entry_points.address_taken {
+            roots.extend(self.lang_server.address_taken_functions());
+        }
+        dead_code::find_dead_code(&self.lang_server.call_index(), &roots)
+    }
+
+    pub(crate) fn stats(&mut self) -> Vec<FunctionStats> {
+        stats::compute_stats(&self.lang_server.call_index())
+    }
+
+    pub(crate) fn check(&mut self, rules: &RuleSet) -> Vec<Violation> {
+        let external = match rules.external_callees() {
+            Some(pattern) => self.lang_server.external_calls(&pattern),
+            None => HashMap::new(),
+        };
+        let mut violations = rules.check(&self.lang_server.call_index(), &external);
+        for violation in violations.iter_mut().filter(|violation| violation.call_sites.is_empty()) {
+            let links = self.lang_server.find_link(HashSet::from([violation.caller.clone()]), HashSet::from([violation.callee.clone()]));
+            violation.call_sites = links.get(&(violation.caller.clone(), violation.callee.clone())).cloned().unwrap_or_default();
+        }
+        violations
+    }
+
+    // Maps the changed lines onto the indexed functions and adds every
+    // transitive caller of them to the graph.
+    pub(crate) fn impact(&mut self, changes: &[FileChange], max_depth: Option<usize>) -> ImpactReport {
+        let mut changed: HashSet<String> = HashSet::new();
+        for change in changes {
+            for (function, start, end) in self.lang_server.function_ranges(&change.file) {
+                if change.touches(start, end) {
+                    changed.insert(function);
+                }
+            }
+        }
+
+        let report = impact::transitive_callers(&changed, &self.lang_server.call_index(), max_depth);
+        for name in report.affected.keys() {
+            self.graph.add_node(name.clone(), 1);
+        }
+        let affected: HashSet<String> = report.affected.keys().cloned().collect();
+        let links = self.lang_server.find_link(affected.clone(), affected);
+        for (caller, callee) in &report.edges {
+            let call_sites = links.get(&(caller.clone(), callee.clone())).cloned().unwrap_or_default();
+            self.add_edge(caller.clone(), callee.clone(), call_sites);
+        }
+        self.query_targets.extend(report.changed.iter().cloned());
+        report
+    }
+
+    /// Every call in the index as a graph, with call sites on the edges.
+    pub fn index_graph(&mut self) -> graph::Graph {
+        let functions: HashSet<String> = self.lang_server.call_index()
+            .definitions()
+            .map(|(_, function)| function.to_string())
+            .collect();
+        let mut graph = graph::Graph::new();
+        for function in &functions {
+            graph.add_node(function.clone(), 1);
+        }
+        for ((caller, callee), call_sites) in self.lang_server.find_link(functions.clone(), functions) {
+            if let Err(err) = graph.add_edge(caller, callee, call_sites) {
+                log::error!("{}", err);
+            }
+        }
+        graph
+    }
+
+    pub(crate) fn aggregate(&mut self, graph: &graph::Graph, granularity: aggregate::Granularity) -> graph::Graph {
+        aggregate::aggregate(graph, &self.lang_server.call_index(), granularity)
+    }
+
+    /// Updates the index for the files that changed, the current graph is
+    /// kept. `changed` lists files whose modification time may not have
+    /// moved.
+    pub fn reindex(&mut self, changed: &[String]) {
+        self.lang_server.reindex(changed);
+    }
+
+    /// Stops clangd, if the index was built with it.
     pub fn close_lsp(&mut self) {
         self.lang_server.close();
     }
@@ -1,6 +1,7 @@
 use super::*;
+use crate::call_index::{CallIndex, MapIndex};
 use std::collections::HashSet;
-use crate::searcher::{ForcedNode, LSPServer};
+use crate::searcher::{ForcedNode, LSPServer, Links};
 
 #[cfg(test)]
 struct MockLSPServer;
@@ -12,10 +13,6 @@ impl MockLSPServer {
 }
 
 impl LSPServer for MockLSPServer {
-    fn restart(&mut self) {
-        unimplemented!();
-    }
-
     fn find_func_name(
         &mut self,
         filter: Vec<HashMap<FilterName, Regex>>,
@@ -53,16 +50,42 @@ impl LSPServer for MockLSPServer {
         result
     }
 
-    fn find_link(&mut self, parent_name: HashSet<String>, child_name: HashSet<String>) -> HashSet<(String, String)> {
-        let mut result: HashSet<(String, String)> = HashSet::new();
+    fn find_link(&mut self, parent_name: HashSet<String>, child_name: HashSet<String>) -> Links {
+        let mut result: Links = HashMap::new();
         for parent in parent_name.clone() {
             for child in child_name.clone() {
-                result.insert((parent.clone(), child.clone()));
+                result.insert((parent.clone(), child.clone()), Vec::new());
             }
         }
         result
     }
 
+    fn call_index(&mut self) -> Box<dyn CallIndex + '_> {
+        Box::new(MapIndex::new(
+            HashMap::from([
+                ("main.c".to_string(), vec!["main".to_string(), "ping".to_string(), "pong".to_string()]),
+                ("math.c".to_string(), vec!["fact".to_string(), "unused".to_string(), "callback".to_string()]),
+            ]),
+            HashMap::from([
+                ("main".to_string(), vec!["ping".to_string(), "fact".to_string()]),
+                ("ping".to_string(), vec!["pong".to_string()]),
+                ("pong".to_string(), vec!["ping".to_string()]),
+                ("fact".to_string(), vec!["fact".to_string()]),
+            ]),
+        ))
+    }
+
+    fn address_taken_functions(&mut self) -> HashSet<String> {
+        HashSet::from(["callback".to_string()])
+    }
+
+    fn function_ranges(&mut self, file: &str) -> Vec<(String, u32, u32)> {
+        match file {
+            "main.c" => vec![("main".to_string(), 1, 10), ("ping".to_string(), 12, 20), ("pong".to_string(), 22, 30)],
+            _ => Vec::new(),
+        }
+    }
+
     fn close(&mut self) {
         unimplemented!()
     }
@@ -109,9 +132,84 @@ fn test_graph_only_node() {
     parser.parse(input);
 
     assert_eq!(parser.graph.graph_to_tuple(), HashSet::new());
-    for node in parser.graph.pet_graph.raw_nodes().to_owned(){
-        assert_eq!(node.weight, "foo".to_string());
+    for node in parser.graph.nodes() {
+        assert_eq!(node.name, "foo".to_string());
     }
     //let g : tabbycat::Graph = analyzer.graph.try_into().unwrap();
     //assert_eq!(g.to_string(), "This test is unusable")
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_recursive_verb() {
+    let mut parser = Analyzer::new(MockLSPServer::new());
+    parser.parse(r#"@recursive"#);
+
+    let graph_output = HashSet::from([
+        ("ping".to_string(), "pong".to_string()),
+        ("pong".to_string(), "ping".to_string()),
+        ("fact".to_string(), "fact".to_string()),
+    ]);
+    assert_eq!(parser.graph.graph_to_tuple(), graph_output);
+    assert!(parser.graph.edges().all(|(_, _, edge)| edge.in_cycle));
+}
+
+#[test]
+fn test_dead_code() {
+    let mut parser = Analyzer::new(MockLSPServer::new());
+    let report = parser.dead_code(&EntryPoints::default());
+    assert_eq!(report.unreachable.len(), 1);
+    assert_eq!(report.unreachable["math.c"], vec!["unused".to_string()]);
+
+    let entry_points = EntryPoints { address_taken: false, ..EntryPoints::default() };
+    let report = parser.dead_code(&entry_points);
+    assert_eq!(report.unreachable["math.c"], vec!["callback".to_string(), "unused".to_string()]);
+}
+
+#[test]
+fn test_path_verb() {
+    let mut parser = Analyzer::new(MockLSPServer::new());
+    parser.parse(r#"@path(from="main",to="pong")"#);
+
+    let graph_output = HashSet::from([
+        ("main".to_string(), "ping".to_string()),
+        ("ping".to_string(), "pong".to_string()),
+    ]);
+    assert_eq!(parser.graph.graph_to_tuple(), graph_output);
+
+    assert_eq!(parser.graph.edge("main", "ping").unwrap().hops, vec![1]);
+    assert!(parser.graph.graph_to_dot().contains("hop 2"));
+}
+
+#[test]
+fn test_impact() {
+    let mut parser = Analyzer::new(MockLSPServer::new());
+    let changes = vec![FileChange { file: "main.c".to_string(), lines: vec![(25, 25)] }];
+    let report = parser.impact(&changes, None);
+
+    assert_eq!(report.changed, vec!["pong".to_string()]);
+    assert_eq!(report.affected.keys().collect::<Vec<_>>(), vec!["main", "ping", "pong"]);
+    let graph_output = HashSet::from([
+        ("main".to_string(), "ping".to_string()),
+        ("ping".to_string(), "pong".to_string()),
+        ("pong".to_string(), "ping".to_string()),
+    ]);
+    assert_eq!(parser.graph.graph_to_tuple(), graph_output);
+}
+
+#[test]
+fn test_try_parse_rejects_invalid_queries() {
+    let mut parser = Analyzer::new(MockLSPServer::new());
+    assert!(parser.try_parse("@filter(file=\"(\")\n{@func}").unwrap_err().contains("invalid regex"));
+    assert!(parser.try_parse("@path(from=\"main\")").unwrap_err().contains("@path needs to"));
+    assert!(parser.graph.graph_to_tuple().is_empty());
+}
+
+#[test]
+fn test_run_with_query_pruning() {
+    let mut parser = Analyzer::new(MockLSPServer::new());
+    parser.set_pruning_policy(PruneSpec::FanIn(3).policy());
+    let graph = parser.run(&Query::parse("{{@func}}").unwrap().with_pruning(PruneSpec::None)).unwrap();
+    assert_eq!(graph.graph_to_tuple().len(), 4);
+    assert!(parser.pruned.is_empty());
+    assert_eq!(parser.pruning_policy(), "fan-in=3");
+}
@@ -11,6 +11,8 @@ struct MyParser;
 pub enum AstNode {
     Ident(String),
     Regex(Regex),
+    // `$name`, bound to a value when the query runs
+    Parameter(String),
     NamedParameter {
         ident: Box<AstNode>,
         regex: Box<AstNode>,
@@ -87,12 +89,15 @@ fn build_ast_from_statement(pairs: Pairs<Rule>) -> AstNode {
 
 fn build_ast_from_verb(pairs: Pairs<Rule>) -> AstNode {
     let mut named_parameter:Vec<AstNode> = vec![];
-    let mut ident_str = String::new();
+    let mut ident = AstNode::Ident(String::new());
 
     for pair in pairs {
         match pair.as_rule() {
             Rule::ident => {
-                ident_str = pair.as_str().to_string();
+                ident = AstNode::Ident(pair.as_str().to_string());
+            },
+            Rule::parameter => {
+                ident = build_ast_from_parameter(pair);
             },
             Rule::named_parameter => {
                 let parameter = build_ast_from_named_parameter(pair.into_inner());
@@ -107,32 +112,51 @@ fn build_ast_from_verb(pairs: Pairs<Rule>) -> AstNode {
         }
     }
     AstNode::Verb {
-        ident: Box::new(AstNode::Ident(ident_str)),
+        ident: Box::new(ident),
         named_parameter,
     }
 }
 
 fn build_ast_from_named_parameter(pairs: Pairs<Rule>) -> (AstNode, AstNode) {
     let mut ident_str = String::new();
-    let mut regex_expr = Regex::new(".").unwrap();
+    let mut value = AstNode::Regex(Regex::new(".").unwrap());
     for pair in pairs {
         match pair.as_rule() {
             Rule::ident => {
                 ident_str = pair.as_str().clone().to_string();
             },
             Rule::regex => {
-                regex_expr = Regex::new(pair.as_str()).unwrap();
+                value = AstNode::Regex(Regex::new(pair.as_str()).unwrap());
+            },
+            Rule::parameter => {
+                value = build_ast_from_parameter(pair);
             },
             _ => {},
         }
     }
     (
         AstNode::Ident(ident_str),
-        AstNode::Regex(regex_expr)
+        value
     )
 
 }
 
+fn build_ast_from_parameter(pair: Pair<Rule>) -> AstNode {
+    AstNode::Parameter(pair.as_str().trim_start_matches('$').to_string())
+}
+
+// Names of the parameters used in `source`, sorted and without duplicates.
+pub fn parameter_names(source: &str) -> Result<Vec<String>, Box<pest::error::Error<Rule>>> {
+    let mut names: Vec<String> = parse_grammar(source).map_err(Box::new)?
+        .flatten()
+        .filter(|pair| pair.as_rule() == Rule::parameter)
+        .map(|pair| pair.as_str().trim_start_matches('$').to_string())
+        .collect();
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
 fn build_ast_from_scope(pair: Pair<Rule>) -> AstNode {
     match pair.as_rule() {
         Rule::statements => {
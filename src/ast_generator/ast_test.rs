@@ -62,4 +62,14 @@ fn test_ast_parser_rebuild() {
     let statements = AstNode::Statements(vec![statement]);
     assert_eq!(format!("{:?}",ast.unwrap().last().unwrap().to_owned()),
                format!("{:?}",statements));
-}
\ No newline at end of file
+}
+#[test]
+fn test_ast_parser_parameters() {
+    let input = r#"@$caller(k="2"){@filter(function="$target")}"#;
+    let ast = super::parse_ast(input).unwrap();
+    let debug = format!("{:?}", ast);
+    assert!(debug.contains(r#"Verb { ident: Parameter("caller"), named_parameter: [NamedParameter { ident: Ident("k"), regex: Regex(2) }] }"#), "{}", debug);
+    assert!(debug.contains(r#"NamedParameter { ident: Ident("function"), regex: Parameter("target") }"#), "{}", debug);
+    assert_eq!(super::parameter_names(input).unwrap(), vec!["caller".to_string(), "target".to_string()]);
+    assert!(super::parameter_names("{@main}").unwrap().is_empty());
+}
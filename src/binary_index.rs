@@ -0,0 +1,529 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{anyhow, bail, Context};
+use memmap2::Mmap;
+
+use crate::call_index::CallIndex;
+use crate::indexer::FunctionCalls;
+use crate::searcher::CallSite;
+
+// On-disk layout (all integers little endian):
+//   header:   magic "CAIX", version u32, section count u32, reserved u32
+//   sections: (tag u32, reserved u32, offset u64, length u64) per section
+//   data:     each section starts on an 8 byte boundary
+// Symbols and files are stored sorted, so a name is resolved to its id with a
+// binary search directly on the mapped bytes. Call edges are kept as CSR
+// arrays: `offsets[id]..offsets[id + 1]` is the slice of neighbour ids. The
+// call sites of the n-th callee edge are `SITES[SITE_OFFSETS[n]..SITE_OFFSETS[n + 1]]`,
+// each stored as (file id, line, column). FUNCTION_RANGES holds the first and
+// last line (starting at 1) of every entry of FILE_FUNCTIONS. FILE_STAMPS holds
+// the modification time of every file when it was indexed, as u64 nanoseconds.
+const MAGIC: &[u8; 4] = b"CAIX";
+const VERSION: u32 = 4;
+const HEADER_LEN: usize = 16;
+const SECTION_ENTRY_LEN: usize = 24;
+
+const SYMBOL_OFFSETS: u32 = 1;
+const SYMBOL_DATA: u32 = 2;
+const FILE_OFFSETS: u32 = 3;
+const FILE_DATA: u32 = 4;
+const FILE_FUNCTION_OFFSETS: u32 = 5;
+const FILE_FUNCTIONS: u32 = 6;
+const CALLEE_OFFSETS: u32 = 7;
+const CALLEES: u32 = 8;
+const CALLER_OFFSETS: u32 = 9;
+const CALLERS: u32 = 10;
+const SITE_OFFSETS: u32 = 11;
+const SITES: u32 = 12;
+const FUNCTION_RANGES: u32 = 13;
+const FILE_STAMPS: u32 = 14;
+
+pub struct BinaryIndex {
+    mmap: Mmap,
+    sections: HashMap<u32, (usize, usize)>,
+    symbol_count: usize,
+    file_count: usize,
+}
+
+#[derive(Clone, Copy)]
+struct U32Array<'a>(&'a [u8]);
+
+impl<'a> U32Array<'a> {
+    fn len(&self) -> usize {
+        self.0.len() / 4
+    }
+
+    fn get(&self, i: usize) -> u32 {
+        let bytes = &self.0[i * 4..i * 4 + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn range(&self, i: usize) -> std::ops::Range<usize> {
+        self.get(i) as usize..self.get(i + 1) as usize
+    }
+}
+
+#[derive(Clone, Copy)]
+struct StringTable<'a> {
+    offsets: U32Array<'a>,
+    data: &'a [u8],
+}
+
+impl<'a> StringTable<'a> {
+    fn get(&self, i: usize) -> &'a str {
+        str::from_utf8(&self.data[self.offsets.range(i)]).unwrap_or("")
+    }
+
+    fn find(&self, name: &str) -> Option<u32> {
+        let mut low = 0;
+        let mut high = self.offsets.len().saturating_sub(1);
+        while low < high {
+            let mid = (low + high) / 2;
+            match self.get(mid).cmp(name) {
+                std::cmp::Ordering::Equal => return Some(mid as u32),
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+            }
+        }
+        None
+    }
+}
+
+impl BinaryIndex {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<BinaryIndex> {
+        let file = File::open(path.as_ref())
+            .with_context(|| format!("unable to open index {}", path.as_ref().display()))?;
+        // SAFETY: the index is only ever replaced by writing a new file and renaming it,
+        // so the mapped file is never truncated while we hold the mapping.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        if mmap.len() < HEADER_LEN || &mmap[0..4] != MAGIC {
+            bail!("{} is not a code-analysis index", path.as_ref().display());
+        }
+        let header = U32Array(&mmap[4..HEADER_LEN]);
+        if header.get(0) != VERSION {
+            bail!("unsupported index version {}", header.get(0));
+        }
+
+        let section_count = header.get(1) as usize;
+        let mut sections = HashMap::new();
+        for i in 0..section_count {
+            let start = HEADER_LEN + i * SECTION_ENTRY_LEN;
+            let entry = mmap
+                .get(start..start + SECTION_ENTRY_LEN)
+                .ok_or_else(|| anyhow!("truncated section table"))?;
+            let tag = U32Array(&entry[0..4]).get(0);
+            let offset = u64::from_le_bytes(entry[8..16].try_into()?) as usize;
+            let len = u64::from_le_bytes(entry[16..24].try_into()?) as usize;
+            if offset + len > mmap.len() {
+                bail!("section {} exceeds the index file", tag);
+            }
+            sections.insert(tag, (offset, len));
+        }
+
+        let mut index = BinaryIndex {
+            mmap,
+            sections,
+            symbol_count: 0,
+            file_count: 0,
+        };
+        for tag in SYMBOL_OFFSETS..=FILE_STAMPS {
+            if !index.sections.contains_key(&tag) {
+                bail!("index is missing section {}", tag);
+            }
+        }
+        index.symbol_count = index.array(SYMBOL_OFFSETS).len().saturating_sub(1);
+        index.file_count = index.array(FILE_OFFSETS).len().saturating_sub(1);
+        Ok(index)
+    }
+
+    pub fn write<P: AsRef<Path>>(
+        path: P,
+        index_map: &HashMap<String, Vec<String>>,
+        function_ranges: &HashMap<String, Vec<(u32, u32)>>,
+        function_index: &HashMap<String, FunctionCalls>,
+        stamps: &HashMap<String, u64>,
+    ) -> anyhow::Result<()> {
+        let mut names: BTreeSet<&str> = BTreeSet::new();
+        for functions in index_map.values() {
+            names.extend(functions.iter().map(|f| f.as_str()));
+        }
+        for (caller, callees) in function_index {
+            names.insert(caller.as_str());
+            names.extend(callees.iter().map(|(f, _)| f.as_str()));
+        }
+        let names: Vec<&str> = names.into_iter().collect();
+        let ids: HashMap<&str, u32> = names.iter().enumerate().map(|(i, n)| (*n, i as u32)).collect();
+
+        let mut files: Vec<&String> = index_map.keys().collect();
+        files.sort();
+        let file_ids: HashMap<&str, u32> = files.iter().enumerate().map(|(i, f)| (f.as_str(), i as u32)).collect();
+
+        let mut file_functions: Vec<Vec<u32>> = Vec::with_capacity(files.len());
+        let mut ranges: Vec<u32> = Vec::new();
+        for file in &files {
+            file_functions.push(index_map[*file].iter().map(|f| ids[f.as_str()]).collect());
+            let file_ranges = function_ranges.get(*file);
+            for i in 0..index_map[*file].len() {
+                let (start, end) = file_ranges.and_then(|r| r.get(i)).copied().unwrap_or((0, 0));
+                ranges.extend_from_slice(&[start, end]);
+            }
+        }
+
+        let mut edges: Vec<BTreeMap<u32, Vec<u32>>> = vec![BTreeMap::new(); names.len()];
+        for (caller, called) in function_index {
+            let caller_id = ids[caller.as_str()];
+            for (callee, sites) in called {
+                let edge_sites = edges[caller_id as usize].entry(ids[callee.as_str()]).or_default();
+                for site in sites {
+                    if let Some(file_id) = file_ids.get(site.file.as_str()) {
+                        edge_sites.extend_from_slice(&[*file_id, site.line, site.column]);
+                    }
+                }
+            }
+        }
+
+        let mut callees: Vec<Vec<u32>> = Vec::with_capacity(names.len());
+        let mut callers: Vec<Vec<u32>> = vec![Vec::new(); names.len()];
+        let mut sites: Vec<Vec<u32>> = Vec::new();
+        for (caller_id, called) in edges.into_iter().enumerate() {
+            let mut list = Vec::with_capacity(called.len());
+            for (callee_id, edge_sites) in called {
+                list.push(callee_id);
+                callers[callee_id as usize].push(caller_id as u32);
+                sites.push(edge_sites);
+            }
+            callees.push(list);
+        }
+
+        let (symbol_offsets, symbol_data) = encode_strings(&names);
+        let (file_offsets, file_data) = encode_strings(&files);
+        let (file_function_offsets, file_function_ids) = encode_csr(&file_functions);
+        let (callee_offsets, callee_ids) = encode_csr(&callees);
+        let (caller_offsets, caller_ids) = encode_csr(&callers);
+        let (site_offsets, site_values) = encode_csr(&sites);
+        let range_values: Vec<u8> = ranges.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let stamp_values: Vec<u8> = files
+            .iter()
+            .flat_map(|file| stamps.get(file.as_str()).copied().unwrap_or(0).to_le_bytes())
+            .collect();
+
+        let sections: Vec<(u32, Vec<u8>)> = vec![
+            (SYMBOL_OFFSETS, symbol_offsets),
+            (SYMBOL_DATA, symbol_data),
+            (FILE_OFFSETS, file_offsets),
+            (FILE_DATA, file_data),
+            (FILE_FUNCTION_OFFSETS, file_function_offsets),
+            (FILE_FUNCTIONS, file_function_ids),
+            (CALLEE_OFFSETS, callee_offsets),
+            (CALLEES, callee_ids),
+            (CALLER_OFFSETS, caller_offsets),
+            (CALLERS, caller_ids),
+            (SITE_OFFSETS, site_offsets),
+            (SITES, site_values),
+            (FUNCTION_RANGES, range_values),
+            (FILE_STAMPS, stamp_values),
+        ];
+
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("bin.tmp");
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(sections.len() as u32).to_le_bytes())?;
+        out.write_all(&0u32.to_le_bytes())?;
+
+        let mut offset = align(HEADER_LEN + sections.len() * SECTION_ENTRY_LEN);
+        for (tag, data) in &sections {
+            out.write_all(&tag.to_le_bytes())?;
+            out.write_all(&0u32.to_le_bytes())?;
+            out.write_all(&(offset as u64).to_le_bytes())?;
+            out.write_all(&(data.len() as u64).to_le_bytes())?;
+            offset = align(offset + data.len());
+        }
+
+        let mut written = HEADER_LEN + sections.len() * SECTION_ENTRY_LEN;
+        for (_, data) in &sections {
+            let padding = align(written) - written;
+            out.write_all(&vec![0u8; padding])?;
+            out.write_all(data)?;
+            written += padding + data.len();
+        }
+        out.flush()?;
+        drop(out);
+
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    fn bytes(&self, tag: u32) -> &[u8] {
+        let (offset, len) = self.sections[&tag];
+        &self.mmap[offset..offset + len]
+    }
+
+    fn array(&self, tag: u32) -> U32Array<'_> {
+        U32Array(self.bytes(tag))
+    }
+
+    fn symbols(&self) -> StringTable<'_> {
+        StringTable { offsets: self.array(SYMBOL_OFFSETS), data: self.bytes(SYMBOL_DATA) }
+    }
+
+    fn file_table(&self) -> StringTable<'_> {
+        StringTable { offsets: self.array(FILE_OFFSETS), data: self.bytes(FILE_DATA) }
+    }
+
+    fn neighbours(&self, offsets: u32, values: u32, id: u32) -> impl Iterator<Item = u32> + '_ {
+        let offsets = self.array(offsets);
+        let values = self.array(values);
+        offsets.range(id as usize).map(move |i| values.get(i))
+    }
+
+    pub fn symbol_count(&self) -> usize {
+        self.symbol_count
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.file_count
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.array(CALLEES).len()
+    }
+
+    pub fn symbol_id(&self, name: &str) -> Option<u32> {
+        self.symbols().find(name)
+    }
+
+    pub fn symbol_name(&self, id: u32) -> &str {
+        self.symbols().get(id as usize)
+    }
+
+    pub fn file_id(&self, file: &str) -> Option<u32> {
+        self.file_table().find(file)
+    }
+
+    pub fn file_name(&self, id: u32) -> &str {
+        self.file_table().get(id as usize)
+    }
+
+    pub fn file_function_ids(&self, file_id: u32) -> impl Iterator<Item = u32> + '_ {
+        self.neighbours(FILE_FUNCTION_OFFSETS, FILE_FUNCTIONS, file_id)
+    }
+
+    // (function id, first line, last line) of every function defined in the file
+    pub fn function_ranges(&self, file_id: u32) -> Vec<(u32, u32, u32)> {
+        let functions = self.array(FILE_FUNCTIONS);
+        let ranges = self.array(FUNCTION_RANGES);
+        self.array(FILE_FUNCTION_OFFSETS)
+            .range(file_id as usize)
+            .map(|i| (functions.get(i), ranges.get(2 * i), ranges.get(2 * i + 1)))
+            .collect()
+    }
+
+    pub fn function_range_names(&self, file: &str) -> Vec<(String, u32, u32)> {
+        match self.file_id(file) {
+            Some(file_id) => self
+                .function_ranges(file_id)
+                .into_iter()
+                .map(|(id, start, end)| (self.symbol_name(id).to_string(), start, end))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn callee_ids(&self, id: u32) -> impl Iterator<Item = u32> + '_ {
+        self.neighbours(CALLEE_OFFSETS, CALLEES, id)
+    }
+
+    pub fn caller_ids(&self, id: u32) -> impl Iterator<Item = u32> + '_ {
+        self.neighbours(CALLER_OFFSETS, CALLERS, id)
+    }
+
+    // Call sites of the edge caller -> callee, the SITE_OFFSETS are counted in
+    // u32 values, three per site.
+    pub fn call_sites(&self, caller: u32, callee: u32) -> Vec<CallSite> {
+        let offsets = self.array(CALLEE_OFFSETS);
+        let callees = self.array(CALLEES);
+        let edges = offsets.range(caller as usize);
+
+        let (mut low, mut high) = (edges.start, edges.end);
+        while low < high {
+            let mid = (low + high) / 2;
+            match callees.get(mid).cmp(&callee) {
+                std::cmp::Ordering::Equal => {
+                    low = mid;
+                    break;
+                }
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+            }
+        }
+        if low >= edges.end || callees.get(low) != callee {
+            return Vec::new();
+        }
+
+        let values = self.array(SITES);
+        let range = self.array(SITE_OFFSETS).range(low);
+        range
+            .step_by(3)
+            .map(|i| CallSite {
+                file: self.file_name(values.get(i)).to_string(),
+                line: values.get(i + 1),
+                column: values.get(i + 2),
+            })
+            .collect()
+    }
+
+    pub fn call_site_names(&self, caller: &str, callee: &str) -> Vec<CallSite> {
+        match (self.symbol_id(caller), self.symbol_id(callee)) {
+            (Some(caller), Some(callee)) => self.call_sites(caller, callee),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn callee_names(&self, name: &str) -> Vec<String> {
+        match self.symbol_id(name) {
+            Some(id) => self.callee_ids(id).map(|c| self.symbol_name(c).to_string()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn caller_names(&self, name: &str) -> Vec<String> {
+        match self.symbol_id(name) {
+            Some(id) => self.caller_ids(id).map(|c| self.symbol_name(c).to_string()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // (file, first line, last line) of the definition of a function
+    pub fn function_location(&self, name: &str) -> Option<(String, u32, u32)> {
+        let id = self.symbol_id(name)?;
+        let functions = self.array(FILE_FUNCTIONS);
+        let ranges = self.array(FUNCTION_RANGES);
+        let offsets = self.array(FILE_FUNCTION_OFFSETS);
+        (0..self.file_count).find_map(|file_id| {
+            offsets
+                .range(file_id)
+                .find(|i| functions.get(*i) == id)
+                .map(|i| (self.file_name(file_id as u32).to_string(), ranges.get(2 * i), ranges.get(2 * i + 1)))
+        })
+    }
+
+    // Modification time of a file when it was indexed, see `modified_stamp`.
+    pub fn file_stamp(&self, file_id: u32) -> u64 {
+        let start = file_id as usize * 8;
+        self.bytes(FILE_STAMPS)
+            .get(start..start + 8)
+            .map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap_or([0; 8])))
+    }
+
+    // The files of `files` below `project_path` that are not in the index or
+    // were modified since they were indexed.
+    pub fn stale_files(&self, project_path: &str, files: &[String]) -> Vec<String> {
+        files
+            .iter()
+            .filter(|file| match self.file_id(file) {
+                Some(id) => self.file_stamp(id) != modified_stamp(&Path::new(project_path).join(file)),
+                None => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    // The index is up to date if it was built from exactly this list of files
+    // and none of them changed since.
+    pub fn covers_files(&self, project_path: &str, files: &[String]) -> bool {
+        self.file_count == files.len() && self.stale_files(project_path, files).is_empty()
+    }
+}
+
+impl CallIndex for BinaryIndex {
+    fn functions(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new((0..self.symbol_count as u32).map(move |id| self.symbol_name(id)))
+    }
+
+    fn has_function(&self, function: &str) -> bool {
+        self.symbol_id(function).is_some()
+    }
+
+    fn callees(&self, function: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self.symbol_id(function) {
+            Some(id) => Box::new(self.callee_ids(id).map(move |callee| self.symbol_name(callee))),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn callers(&self, function: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self.symbol_id(function) {
+            Some(id) => Box::new(self.caller_ids(id).map(move |caller| self.symbol_name(caller))),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn files(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new((0..self.file_count as u32).map(move |id| self.file_name(id)))
+    }
+
+    fn has_file(&self, file: &str) -> bool {
+        self.file_id(file).is_some()
+    }
+
+    fn file_functions(&self, file: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self.file_id(file) {
+            Some(id) => Box::new(self.file_function_ids(id).map(move |function| self.symbol_name(function))),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+}
+
+// Modification time of a file in nanoseconds, 0 when it cannot be read.
+pub fn modified_stamp(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos() as u64)
+}
+
+fn align(offset: usize) -> usize {
+    (offset + 7) & !7
+}
+
+fn encode_strings<S: AsRef<str>>(strings: &[S]) -> (Vec<u8>, Vec<u8>) {
+    let mut offsets = Vec::with_capacity((strings.len() + 1) * 4);
+    let mut data = Vec::new();
+    offsets.extend_from_slice(&0u32.to_le_bytes());
+    for s in strings {
+        data.extend_from_slice(s.as_ref().as_bytes());
+        offsets.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    }
+    (offsets, data)
+}
+
+fn encode_csr(lists: &[Vec<u32>]) -> (Vec<u8>, Vec<u8>) {
+    let mut offsets = Vec::with_capacity((lists.len() + 1) * 4);
+    let mut values = Vec::new();
+    let mut count: u32 = 0;
+    offsets.extend_from_slice(&count.to_le_bytes());
+    for list in lists {
+        for value in list {
+            values.extend_from_slice(&value.to_le_bytes());
+        }
+        count += list.len() as u32;
+        offsets.extend_from_slice(&count.to_le_bytes());
+    }
+    (offsets, values)
+}
+
+#[cfg(test)]
+mod binary_index_test;
@@ -0,0 +1,120 @@
+use super::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn index_path(name: &str) -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("code_analysis_{}_{}", name, std::process::id()))
+        .join("index.bin")
+}
+
+fn write_test_index(name: &str) -> PathBuf {
+    let index_map = HashMap::from([
+        ("src/main.c".to_string(), vec!["main".to_string(), "helper".to_string()]),
+        ("src/util.c".to_string(), vec!["util".to_string()]),
+    ]);
+    let site = |file: &str, line: u32, column: u32| CallSite { file: file.to_string(), line, column };
+    let function_index = HashMap::from([
+        ("main".to_string(), vec![
+            ("helper".to_string(), vec![site("src/main.c", 3, 5)]),
+            ("util".to_string(), vec![site("src/main.c", 4, 5), site("src/main.c", 7, 9)]),
+            ("util".to_string(), vec![site("src/main.c", 9, 1)]),
+        ]),
+        ("helper".to_string(), vec![("util".to_string(), vec![site("src/main.c", 12, 5)])]),
+        ("util".to_string(), vec![]),
+    ]);
+    let function_ranges = HashMap::from([
+        ("src/main.c".to_string(), vec![(1, 10), (11, 14)]),
+        ("src/util.c".to_string(), vec![(3, 8)]),
+    ]);
+    let path = index_path(name);
+    BinaryIndex::write(&path, &index_map, &function_ranges, &function_index, &HashMap::new()).unwrap();
+    path
+}
+
+#[test]
+fn test_binary_index_roundtrip() {
+    let path = write_test_index("roundtrip");
+    let index = BinaryIndex::open(&path).unwrap();
+
+    assert_eq!(index.symbol_count(), 3);
+    assert_eq!(index.file_count(), 2);
+    assert_eq!(index.edge_count(), 3);
+
+    let mut callees = index.callee_names("main");
+    callees.sort();
+    assert_eq!(callees, vec!["helper".to_string(), "util".to_string()]);
+
+    let mut callers = index.caller_names("util");
+    callers.sort();
+    assert_eq!(callers, vec!["helper".to_string(), "main".to_string()]);
+
+    assert!(index.caller_names("main").is_empty());
+    assert!(index.callee_names("unknown").is_empty());
+
+    assert_eq!(index.file_functions("src/main.c").collect::<Vec<_>>(), vec!["main", "helper"]);
+    assert_eq!(index.file_functions("src/util.c").collect::<Vec<_>>(), vec!["util"]);
+    assert_eq!(index.files().collect::<Vec<_>>(), vec!["src/main.c", "src/util.c"]);
+    assert_eq!(index.callees("main").collect::<Vec<_>>(), vec!["helper", "util"]);
+    assert!(index.has_function("helper") && !index.has_function("unknown"));
+}
+
+#[test]
+fn test_binary_index_covers_files() {
+    let path = index_path("covers");
+    let project = path.parent().unwrap().join("project");
+    std::fs::create_dir_all(project.join("src")).unwrap();
+    std::fs::write(project.join("src/main.c"), "int main() {}\n").unwrap();
+    std::fs::write(project.join("src/util.c"), "int util() {}\n").unwrap();
+    let project_path = project.to_str().unwrap();
+    let files = ["src/main.c".to_string(), "src/util.c".to_string()];
+    let index_map: HashMap<String, Vec<String>> = files.iter().map(|file| (file.clone(), Vec::new())).collect();
+    let stamps = files.iter().map(|file| (file.clone(), modified_stamp(&project.join(file)))).collect();
+    BinaryIndex::write(&path, &index_map, &HashMap::new(), &HashMap::new(), &stamps).unwrap();
+    let index = BinaryIndex::open(&path).unwrap();
+
+    assert!(index.covers_files(project_path, &["src/util.c".to_string(), "src/main.c".to_string()]));
+    assert!(!index.covers_files(project_path, &["src/main.c".to_string()]));
+    assert!(!index.covers_files(project_path, &["src/main.c".to_string(), "src/other.c".to_string()]));
+
+    let util = std::fs::File::options().write(true).open(project.join("src/util.c")).unwrap();
+    util.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(10)).unwrap();
+    assert_eq!(index.stale_files(project_path, &files), vec!["src/util.c".to_string()]);
+    assert!(!index.covers_files(project_path, &files));
+}
+
+#[test]
+fn test_binary_index_rejects_garbage() {
+    let path = index_path("garbage");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, b"{\"not\": \"an index\"}").unwrap();
+    assert!(BinaryIndex::open(&path).is_err());
+}
+
+#[test]
+fn test_binary_index_call_sites() {
+    let path = write_test_index("call_sites");
+    let index = BinaryIndex::open(&path).unwrap();
+
+    let sites = index.call_site_names("main", "util");
+    assert_eq!(sites.len(), 3);
+    assert_eq!(sites[0], CallSite { file: "src/main.c".to_string(), line: 4, column: 5 });
+    assert_eq!(sites[2].line, 9);
+
+    assert_eq!(index.call_site_names("helper", "util").len(), 1);
+    assert!(index.call_site_names("util", "main").is_empty());
+    assert!(index.call_site_names("main", "unknown").is_empty());
+}
+
+#[test]
+fn test_binary_index_function_ranges() {
+    let path = write_test_index("ranges");
+    let index = BinaryIndex::open(&path).unwrap();
+
+    assert_eq!(index.function_range_names("src/main.c"), vec![
+        ("main".to_string(), 1, 10),
+        ("helper".to_string(), 11, 14),
+    ]);
+    assert_eq!(index.function_range_names("src/util.c"), vec![("util".to_string(), 3, 8)]);
+    assert!(index.function_range_names("src/other.c").is_empty());
+}
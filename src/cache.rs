@@ -0,0 +1,97 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::SourceFilter;
+
+// A named build configuration of a project. Every profile gets its own cache
+// directory, so e.g. a debug and a release compile_commands.json of the same
+// tree can be indexed side by side without touching the project directory.
+// Within a profile the index is keyed by the files it covers and the clangd
+// arguments it was built with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexProfile {
+    pub name: String,
+    pub compile_commands_dir: String,
+    pub cache_dir: PathBuf,
+    pub index_key: String,
+}
+
+impl IndexProfile {
+    pub fn new(
+        project_path: &str,
+        name: &str,
+        compile_commands_dir: Option<String>,
+        cache_dir: Option<String>,
+        sources: &SourceFilter,
+        clangd_args: &[String],
+    ) -> IndexProfile {
+        let compile_commands_dir = compile_commands_dir.unwrap_or_else(|| project_path.to_string());
+        let profile_dir = format!("{}-{}", name, short_hash(&canonical(&compile_commands_dir)));
+
+        let cache_dir = match cache_dir {
+            Some(dir) => PathBuf::from(dir).join(profile_dir),
+            None => {
+                let project = canonical(project_path);
+                let project_name = Path::new(&project)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "project".to_string());
+                default_cache_root()
+                    .join("code-analysis")
+                    .join(format!("{}-{}", project_name, short_hash(&project)))
+                    .join(profile_dir)
+            }
+        };
+
+        IndexProfile {
+            name: name.to_string(),
+            compile_commands_dir,
+            cache_dir,
+            index_key: short_hash(&format!("{}\n{}", sources.key(), clangd_args.join("\0"))),
+        }
+    }
+
+    pub fn index_file(&self) -> PathBuf {
+        self.cache_dir.join(format!("index-{}.bin", self.index_key))
+    }
+
+    // where a daemon for this profile listens
+    pub fn socket_file(&self) -> PathBuf {
+        self.cache_dir.join("daemon.sock")
+    }
+
+    // clangd keeps its background index next to the compilation database
+    pub fn clangd_index_dir(&self) -> String {
+        self.compile_commands_dir.clone() + "/.cache/clangd/index"
+    }
+}
+
+fn default_cache_root() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    if let Some(home) = env::var_os("HOME").filter(|d| !d.is_empty()) {
+        return PathBuf::from(home).join(".cache");
+    }
+    env::temp_dir()
+}
+
+fn canonical(path: &str) -> String {
+    fs::canonicalize(path)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.trim_end_matches('/').to_string())
+}
+
+// FNV-1a, so cache directories stay the same across builds and platforms
+pub fn short_hash(value: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in value.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod cache_test;
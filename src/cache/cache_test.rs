@@ -0,0 +1,52 @@
+use super::*;
+use crate::config::SourcesConfig;
+
+fn profile(project_path: &str, name: &str, compile_commands_dir: Option<&str>, cache_dir: Option<&str>) -> IndexProfile {
+    IndexProfile::new(project_path, name, compile_commands_dir.map(str::to_string), cache_dir.map(str::to_string), &SourceFilter::default(), &[])
+}
+
+#[test]
+fn test_short_hash_is_stable() {
+    assert_eq!(short_hash(""), "cbf29ce484222325");
+    assert_eq!(short_hash("/src/project"), short_hash("/src/project"));
+    assert_ne!(short_hash("/src/project"), short_hash("/src/project2"));
+}
+
+#[test]
+fn test_explicit_cache_dir() {
+    let profile = profile("/nonexistent/project", "debug", None, Some("/tmp/ca-cache"));
+    assert!(profile.cache_dir.starts_with("/tmp/ca-cache"));
+    assert_eq!(profile.compile_commands_dir, "/nonexistent/project");
+    assert!(profile.index_file().starts_with(&profile.cache_dir));
+}
+
+#[test]
+fn test_profiles_do_not_share_a_cache() {
+    let cache_dir = Some("/tmp/ca-cache");
+    let debug = profile("/nonexistent/project", "debug", Some("/nonexistent/project/build-debug"), cache_dir);
+    let release = profile("/nonexistent/project", "release", Some("/nonexistent/project/build-release"), cache_dir);
+    let debug_other_build = profile("/nonexistent/project", "debug", Some("/nonexistent/project/build"), cache_dir);
+
+    assert_ne!(debug.cache_dir, release.cache_dir);
+    assert_ne!(debug.cache_dir, debug_other_build.cache_dir);
+}
+
+#[test]
+fn test_default_cache_dir_is_outside_the_project() {
+    let profile = profile("/nonexistent/project", "default", None, None);
+    assert!(!profile.cache_dir.starts_with("/nonexistent/project"));
+    assert!(profile.cache_dir.to_string_lossy().contains("code-analysis"));
+}
+
+#[test]
+fn test_sources_and_clangd_args_key_the_index() {
+    let headers = SourceFilter::new(&SourcesConfig { extensions: vec!["c".to_string(), "h".to_string()], ..SourcesConfig::default() }).unwrap();
+    let default = profile("/nonexistent/project", "debug", None, Some("/tmp/ca-cache"));
+    let with_headers = IndexProfile::new("/nonexistent/project", "debug", None, Some("/tmp/ca-cache".to_string()), &headers, &[]);
+    let with_args = IndexProfile::new("/nonexistent/project", "debug", None, Some("/tmp/ca-cache".to_string()), &SourceFilter::default(), &["--log=verbose".to_string()]);
+
+    assert_ne!(default.index_file(), with_headers.index_file());
+    assert_ne!(default.index_file(), with_args.index_file());
+    assert_eq!(default.index_file(), profile("/nonexistent/project", "debug", None, Some("/tmp/ca-cache")).index_file());
+    assert_eq!(default.socket_file(), with_args.socket_file());
+}
@@ -0,0 +1,153 @@
+use std::collections::{BTreeSet, HashMap};
+
+// Read access to the indexed files, the functions defined in them and the
+// calls between functions. The mapped index answers every lookup from the
+// file, so analyses of the whole call graph walk it without copying it.
+pub trait CallIndex {
+    // every function that is defined in a file or calls or is called by one
+    fn functions(&self) -> Box<dyn Iterator<Item = &str> + '_>;
+    fn has_function(&self, function: &str) -> bool;
+    fn callees(&self, function: &str) -> Box<dyn Iterator<Item = &str> + '_>;
+    fn callers(&self, function: &str) -> Box<dyn Iterator<Item = &str> + '_>;
+    fn files(&self) -> Box<dyn Iterator<Item = &str> + '_>;
+    fn has_file(&self, file: &str) -> bool;
+    fn file_functions(&self, file: &str) -> Box<dyn Iterator<Item = &str> + '_>;
+
+    // (file, function) of every definition
+    fn definitions(&self) -> Box<dyn Iterator<Item = (&str, &str)> + '_> {
+        Box::new(self.files().flat_map(move |file| self.file_functions(file).map(move |function| (file, function))))
+    }
+}
+
+impl<T: CallIndex + ?Sized> CallIndex for &T {
+    fn functions(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        (**self).functions()
+    }
+
+    fn has_function(&self, function: &str) -> bool {
+        (**self).has_function(function)
+    }
+
+    fn callees(&self, function: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        (**self).callees(function)
+    }
+
+    fn callers(&self, function: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        (**self).callers(function)
+    }
+
+    fn files(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        (**self).files()
+    }
+
+    fn has_file(&self, file: &str) -> bool {
+        (**self).has_file(file)
+    }
+
+    fn file_functions(&self, file: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        (**self).file_functions(file)
+    }
+}
+
+impl<T: CallIndex + ?Sized> CallIndex for Box<T> {
+    fn functions(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        (**self).functions()
+    }
+
+    fn has_function(&self, function: &str) -> bool {
+        (**self).has_function(function)
+    }
+
+    fn callees(&self, function: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        (**self).callees(function)
+    }
+
+    fn callers(&self, function: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        (**self).callers(function)
+    }
+
+    fn files(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        (**self).files()
+    }
+
+    fn has_file(&self, file: &str) -> bool {
+        (**self).has_file(file)
+    }
+
+    fn file_functions(&self, file: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        (**self).file_functions(file)
+    }
+}
+
+// An index held in maps, for servers without a mapped index and for tests.
+#[derive(Debug, Clone, Default)]
+pub struct MapIndex {
+    // file -> functions defined in it
+    index_map: HashMap<String, Vec<String>>,
+    // function -> functions it calls
+    function_calls: HashMap<String, Vec<String>>,
+    callers: HashMap<String, Vec<String>>,
+    functions: BTreeSet<String>,
+}
+
+// Built by the test servers, a server without an index uses the empty default.
+#[cfg(test)]
+impl MapIndex {
+    pub fn new(index_map: HashMap<String, Vec<String>>, function_calls: HashMap<String, Vec<String>>) -> MapIndex {
+        let mut functions: BTreeSet<String> = index_map.values().flatten().cloned().collect();
+        let mut callers: HashMap<String, Vec<String>> = HashMap::new();
+        for (caller, callees) in &function_calls {
+            functions.insert(caller.clone());
+            for callee in callees {
+                functions.insert(callee.clone());
+                let list = callers.entry(callee.clone()).or_default();
+                if !list.contains(caller) {
+                    list.push(caller.clone());
+                }
+            }
+        }
+        for list in callers.values_mut() {
+            list.sort();
+        }
+        MapIndex { index_map, function_calls, callers, functions }
+    }
+
+    // Only calls, no function is defined in a file.
+    pub fn from_calls(function_calls: HashMap<String, Vec<String>>) -> MapIndex {
+        MapIndex::new(HashMap::new(), function_calls)
+    }
+}
+
+fn names<'a>(list: Option<&'a Vec<String>>) -> Box<dyn Iterator<Item = &'a str> + 'a> {
+    Box::new(list.into_iter().flatten().map(String::as_str))
+}
+
+impl CallIndex for MapIndex {
+    fn functions(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(self.functions.iter().map(String::as_str))
+    }
+
+    fn has_function(&self, function: &str) -> bool {
+        self.functions.contains(function)
+    }
+
+    fn callees(&self, function: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        names(self.function_calls.get(function))
+    }
+
+    fn callers(&self, function: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        names(self.callers.get(function))
+    }
+
+    fn files(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(self.index_map.keys().map(String::as_str))
+    }
+
+    fn has_file(&self, file: &str) -> bool {
+        self.index_map.contains_key(file)
+    }
+
+    fn file_functions(&self, file: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        names(self.index_map.get(file))
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+// Finds calls to known functions in a function body with a single pass over
+// the text. Every identifier token is looked up in a hash map of all indexed
+// function names, so the cost no longer depends on the size of the project.
+pub struct CallScanner {
+    names: HashMap<String, usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannedCall {
+    pub function: usize,
+    // zero based, relative to the start of the scanned text
+    pub line: usize,
+    pub column: usize,
+}
+
+impl CallScanner {
+    pub fn new<S: AsRef<str>>(names: &[S]) -> CallScanner {
+        let mut map = HashMap::with_capacity(names.len());
+        for (i, name) in names.iter().enumerate() {
+            map.entry(name.as_ref().to_string()).or_insert(i);
+        }
+        CallScanner { names: map }
+    }
+
+    // Returns the id (position in the list given to `new`) of a known function.
+    pub fn lookup(&self, identifier: &str) -> Option<usize> {
+        // the index stores names without leading underscores
+        self.names
+            .get(identifier)
+            .or_else(|| self.names.get(identifier.trim_start_matches('_')))
+            .copied()
+    }
+
+    // Every `identifier (` whose identifier is a known function, skipping
+    // comments, string and character literals and preprocessor lines.
+    pub fn scan(&self, body: &str) -> Vec<ScannedCall> {
+        self.scan_known(body, true)
+    }
+
+    // Every known function name that is used without being called, e.g. when
+    // its address is stored in a function pointer or a table.
+    pub fn scan_references(&self, body: &str) -> Vec<ScannedCall> {
+        self.scan_known(body, false)
+    }
+
+    fn scan_known(&self, body: &str, calls_only: bool) -> Vec<ScannedCall> {
+        let mut calls = Vec::new();
+        scan_identifiers(body, calls_only, |identifier, line, column| {
+            if let Some(function) = self.lookup(identifier) {
+                calls.push(ScannedCall { function, line, column });
+            }
+        });
+        calls
+    }
+}
+
+// Keywords that can be followed by `(` without being a call.
+const KEYWORDS: &[&str] = &["if", "for", "while", "switch", "return", "sizeof", "alignof", "_Alignof", "catch"];
+
+// Calls `found` with the identifier, line and column of every call in `body`,
+// whether the called function is known or not.
+pub fn scan_calls<F: FnMut(&str, usize, usize)>(body: &str, mut found: F) {
+    scan_identifiers(body, true, |identifier, line, column| {
+        if !KEYWORDS.contains(&identifier) {
+            found(identifier, line, column);
+        }
+    })
+}
+
+fn scan_identifiers<F: FnMut(&str, usize, usize)>(body: &str, calls_only: bool, mut found: F) {
+    let bytes = body.as_bytes();
+    let mut i = 0;
+    let mut line = 0;
+    let mut line_start = 0;
+    let mut at_line_start = true;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        match c {
+            b'\n' => {
+                i += 1;
+                line += 1;
+                line_start = i;
+                at_line_start = true;
+                continue;
+            }
+            b' ' | b'\t' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            b'#' if at_line_start => {
+                // preprocessor directive, including `\` continuations
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    if bytes[i] == b'\\' && i + 1 < bytes.len() && bytes[i + 1] == b'\n' {
+                        i += 1;
+                        line += 1;
+                        line_start = i + 1;
+                    }
+                    i += 1;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        at_line_start = false;
+
+        if c == b'/' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+        } else if c == b'/' && i + 1 < bytes.len() && bytes[i + 1] == b'*' {
+            i += 2;
+            while i < bytes.len() && !(bytes[i] == b'*' && i + 1 < bytes.len() && bytes[i + 1] == b'/') {
+                if bytes[i] == b'\n' {
+                    line += 1;
+                    line_start = i + 1;
+                }
+                i += 1;
+            }
+            i = (i + 2).min(bytes.len());
+        } else if c == b'"' || c == b'\'' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != c && bytes[i] != b'\n' {
+                if bytes[i] == b'\\' && i + 1 < bytes.len() && bytes[i + 1] != b'\n' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            if i < bytes.len() && bytes[i] == c {
+                i += 1;
+            }
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            let mut next = i;
+            while next < bytes.len() && matches!(bytes[next], b' ' | b'\t' | b'\r' | b'\n') {
+                next += 1;
+            }
+            let is_call = next < bytes.len() && bytes[next] == b'(';
+            if is_call == calls_only {
+                found(&body[start..i], line, start - line_start);
+            }
+        } else if c.is_ascii_digit() {
+            // keeps suffixes like `10ul` from being read as identifiers
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] == b'.') {
+                i += 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod call_scanner_test;
@@ -0,0 +1,135 @@
+use super::*;
+use chrono::Utc;
+
+fn called(scanner: &CallScanner, names: &[&str], body: &str) -> Vec<String> {
+    scanner.scan(body).into_iter().map(|call| names[call.function].to_string()).collect()
+}
+
+#[test]
+fn test_scan_finds_calls() {
+    let names = ["foo", "bar", "baz"];
+    let scanner = CallScanner::new(&names);
+    let body = "    int x = foo(1);\n    if (bar (x)) {\n        x = baz;\n    }\n";
+
+    assert_eq!(called(&scanner, &names, body), vec!["foo", "bar"]);
+}
+
+#[test]
+fn test_scan_reports_positions() {
+    let names = ["foo", "bar"];
+    let scanner = CallScanner::new(&names);
+    let calls = scanner.scan("a();\n  foo(bar(1));");
+
+    assert_eq!(calls, vec![
+        ScannedCall { function: 0, line: 1, column: 2 },
+        ScannedCall { function: 1, line: 1, column: 6 },
+    ]);
+}
+
+#[test]
+fn test_scan_calls_to_unknown_functions() {
+    let mut calls = Vec::new();
+    scan_calls("// sqlite3_close(db);\nif (sqlite3_open(path, &db))\n  log(\"sqlite3_exec(\");", |name, line, column| {
+        calls.push((name.to_string(), line, column));
+    });
+
+    assert_eq!(calls, vec![("sqlite3_open".to_string(), 1, 4), ("log".to_string(), 2, 2)]);
+}
+
+#[test]
+fn test_scan_ignores_partial_identifiers() {
+    let names = ["name"];
+    let scanner = CallScanner::new(&names);
+
+    assert!(called(&scanner, &names, "my_name(); name_2(); rename();").is_empty());
+}
+
+#[test]
+fn test_scan_matches_underscore_prefixed_calls() {
+    let names = ["foo"];
+    let scanner = CallScanner::new(&names);
+
+    assert_eq!(called(&scanner, &names, "__foo(1);"), vec!["foo"]);
+}
+
+#[test]
+fn test_scan_skips_comments_strings_and_preprocessor() {
+    let names = ["foo", "bar"];
+    let scanner = CallScanner::new(&names);
+    let body = concat!(
+        "// foo(1);\n",
+        "/* foo(2);\n bar(3); */\n",
+        "printf(\"foo(%d)\\\"\", 'b');\n",
+        "#define CALL foo(4)\n",
+        "#if defined(foo)\n",
+        "#endif\n",
+        "bar(5);\n",
+    );
+    let calls = scanner.scan(body);
+
+    assert_eq!(calls, vec![ScannedCall { function: 1, line: 7, column: 0 }]);
+}
+
+#[test]
+fn test_scan_references_finds_address_taken_functions() {
+    let names = ["on_read", "on_write", "helper"];
+    let scanner = CallScanner::new(&names);
+    let body = "static const struct ops table = {\n    .read = on_read,\n    .write = &on_write,\n};\nint x = helper(1); // on_read\n";
+
+    let referenced: Vec<&str> = scanner.scan_references(body).into_iter().map(|r| names[r.function]).collect();
+    assert_eq!(referenced, vec!["on_read", "on_write"]);
+}
+
+// Synthetic project with 50k functions, every body calls a few other
+// functions. Run with `cargo test --release -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_call_scan_50k_functions() {
+    const FUNCTIONS: usize = 50_000;
+    const SAMPLE: usize = 200;
+
+    let names: Vec<String> = (0..FUNCTIONS).map(|i| format!("function_{}", i)).collect();
+    let bodies: Vec<String> = (0..FUNCTIONS)
+        .map(|i| {
+            format!(
+                "    int x = {}(a, b);\n    if (x > 0) {{\n        {}(x);\n    }}\n    return {}(x) + 1;\n",
+                names[(i * 7 + 1) % FUNCTIONS],
+                names[(i * 13 + 2) % FUNCTIONS],
+                names[(i * 31 + 3) % FUNCTIONS]
+            )
+        })
+        .collect();
+
+    // the previous implementation: one `contains` per known function and body
+    let naive = |body: &str| -> Vec<String> {
+        names.iter().filter(|name| body.contains(&(name.to_string() + "("))).cloned().collect()
+    };
+    let start = Utc::now().time();
+    let mut naive_calls = 0;
+    for body in bodies.iter().take(SAMPLE) {
+        naive_calls += naive(body).len();
+    }
+    let naive_sample = Utc::now().time() - start;
+    let naive_total = naive_sample.num_microseconds().unwrap() as f64 * (FUNCTIONS / SAMPLE) as f64;
+
+    let start = Utc::now().time();
+    let scanner = CallScanner::new(&names);
+    let mut scanned_calls = 0;
+    let mut sample_calls = 0;
+    for (i, body) in bodies.iter().enumerate() {
+        let calls = scanner.scan(body).len();
+        scanned_calls += calls;
+        if i < SAMPLE {
+            sample_calls += calls;
+        }
+    }
+    let scanned_total = (Utc::now().time() - start).num_microseconds().unwrap() as f64;
+
+    eprintln!("naive scan:   {:.0} ms (extrapolated from {} bodies)", naive_total / 1000.0, SAMPLE);
+    eprintln!("call scanner: {:.0} ms ({} calls)", scanned_total / 1000.0, scanned_calls);
+    eprintln!("speedup:      {:.0}x", naive_total / scanned_total.max(1.0));
+
+    // `function_1(` also matches `function_1X(` in the naive scan
+    assert!(naive_calls >= sample_calls);
+    assert!(scanned_total < naive_total);
+}
@@ -0,0 +1,835 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use chrono::Utc;
+
+use structopt::StructOpt;
+
+use crate::{aggregate, cache, config, daemon, dead_code, graphql, impact, prune, query_lsp, repl, rules, server, snapshot, stats};
+use crate::config::Config;
+use crate::{Analyzer, ExportFormat, Index, IndexOptions, Query};
+
+const EXIT_ERROR: i32 = 1;
+// a query, rule file or snapshot that could not be read
+const EXIT_INVALID_INPUT: i32 = 2;
+const EXIT_VIOLATIONS: i32 = 3;
+const EXIT_NO_INDEX: i32 = 4;
+
+// The one-shot subcommands read the index built by `index` and fail with
+// this when there is none yet.
+#[derive(Debug)]
+struct MissingIndex(PathBuf);
+
+impl fmt::Display for MissingIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no index at {}, run `index` first", self.0.display())
+    }
+}
+
+impl std::error::Error for MissingIndex {}
+
+#[derive(StructOpt, Debug)]
+#[structopt(about = "Call graphs of C and C++ projects")]
+enum Opt {
+    /// Build the index of a project or refresh it when files changed. The one-shot subcommands read this index
+    /// and exit with 4 when there is none
+    Index(IndexOpt),
+    /// Run a query and write the resulting graph. Exit codes: 1 error, 2 invalid query or unbound parameter
+    Query(QueryOpt),
+    /// Show the size of the index or metrics of every function
+    Stats(StatsOpt),
+    /// Check the calls against layering rules. Exit codes: 1 error, 2 invalid rules, 3 violations
+    Check(CheckOpt),
+    /// Write the graph of every call in the index
+    Export(ExportOpt),
+    /// Keep the index loaded and answer queries over HTTP
+    Serve(ServeOpt),
+    /// List functions that are unreachable from the entry points
+    DeadCode(DeadCodeOpt),
+    /// Show the callers of the functions changed by a diff
+    Impact(ImpactOpt),
+    /// Compare two snapshots, writes the diff graph and prints a summary. Exit codes: 1 error, 2 invalid snapshot
+    Diff(DiffOpt),
+    /// Run a GraphQL request against the index and print the JSON response
+    Graphql(GraphqlOpt),
+    /// Read queries and commands interactively, :help lists the commands
+    Repl(ReplOpt),
+    /// Run a language server for query files on stdin and stdout
+    QueryLsp(QueryLspOpt),
+    /// Keep the index loaded and answer requests on a Unix socket
+    Daemon(DaemonOpt),
+    /// Forward a request to a running daemon
+    Client(ClientOpt),
+    /// Inspect the settings of a project
+    Config(ConfigOpt),
+}
+
+// Where the index of a project is cached and how clangd builds it. Options
+// that are not given are taken from the config file of the project.
+#[derive(StructOpt, Debug)]
+struct IndexArgs {
+    /// defaults to the nearest directory with a code-analysis.toml
+    #[structopt(short = "p", long = "project-path")]
+    project_path: Option<String>,
+    /// read the settings from this file instead of code-analysis.toml in the project
+    #[structopt(long = "config")]
+    config: Option<String>,
+    #[structopt(short = "l", long = "lsp-path")]
+    lsp_path: Option<String>,
+    /// passed to clangd, can be repeated
+    #[structopt(long = "lsp-arg", allow_hyphen_values = true, number_of_values = 1)]
+    lsp_args: Vec<String>,
+    /// only index files matching this regex, can be repeated
+    #[structopt(long = "include", number_of_values = 1)]
+    include: Vec<String>,
+    /// do not index files matching this regex, can be repeated
+    #[structopt(long = "exclude", number_of_values = 1)]
+    exclude: Vec<String>,
+    /// extension of the files that are indexed, can be repeated
+    #[structopt(long = "extension", number_of_values = 1)]
+    extensions: Vec<String>,
+    #[structopt(short = "b", long = "benchmark")]
+    benchmark: bool,
+    #[structopt(long = "cache-dir")]
+    cache_dir: Option<String>,
+    #[structopt(long = "profile", default_value = "default")]
+    profile: String,
+    #[structopt(long = "compile-commands-dir")]
+    compile_commands_dir: Option<String>,
+    #[structopt(short = "j", long = "jobs")]
+    jobs: Option<usize>,
+}
+
+// A project with its config, command line options already applied.
+struct Project {
+    path: String,
+    config: Config,
+    options: IndexOptions,
+}
+
+impl IndexArgs {
+    fn load(&self) -> anyhow::Result<Project> {
+        let (path, mut config) = locate(self.project_path.as_deref(), self.config.as_deref())?;
+        if let Some(lsp_path) = &self.lsp_path {
+            config.language_server.path = lsp_path.clone();
+        }
+        if !self.lsp_args.is_empty() {
+            config.language_server.args = self.lsp_args.clone();
+        }
+        if !self.include.is_empty() {
+            config.sources.include = self.include.clone();
+        }
+        if !self.exclude.is_empty() {
+            config.sources.exclude = self.exclude.clone();
+        }
+        if !self.extensions.is_empty() {
+            config.sources.extensions = self.extensions.clone();
+        }
+        if let Some(cache_dir) = &self.cache_dir {
+            config.cache_dir = Some(cache_dir.clone());
+        }
+
+        let mut options = IndexOptions {
+            clangd_path: config.language_server.path.clone(),
+            clangd_args: config.language_server.args.clone(),
+            sources: config.source_filter()?,
+            profile: self.profile.clone(),
+            compile_commands_dir: self.compile_commands_dir.clone(),
+            cache_dir: config.cache_dir(Path::new(&path)),
+            benchmark: self.benchmark,
+            ..IndexOptions::default()
+        };
+        if let Some(jobs) = self.jobs {
+            options.jobs = jobs;
+        }
+        Ok(Project { path, config, options })
+    }
+}
+
+// The project directory and its config. Without a project path the nearest
+// directory with a config file is used.
+fn locate(project_path: Option<&str>, config_file: Option<&str>) -> anyhow::Result<(String, Config)> {
+    let path = match (project_path, config_file) {
+        (Some(path), _) => PathBuf::from(path),
+        (None, Some(file)) => Path::new(file).parent().map(Path::to_path_buf).unwrap_or_default(),
+        (None, None) => config::find_project_root(&std::env::current_dir()?)
+            .with_context(|| format!("no project path given and no {} found in this or a parent directory", config::CONFIG_FILE))?,
+    };
+    let path = if path.as_os_str().is_empty() { PathBuf::from(".") } else { path };
+    let config = match config_file {
+        Some(file) => Config::load(file)?,
+        None => Config::for_project(&path)?,
+    };
+    Ok((path.to_string_lossy().to_string(), config))
+}
+
+impl Project {
+    fn index_file(&self) -> PathBuf {
+        self.options.profile(&self.path).index_file()
+    }
+
+    fn build(&self) -> anyhow::Result<Index> {
+        Index::build(&self.path, &self.options)
+    }
+
+    // The index written by `index`, without starting clangd.
+    fn load_index(&self) -> anyhow::Result<Index> {
+        let index_file = self.index_file();
+        if !index_file.is_file() {
+            return Err(MissingIndex(index_file).into());
+        }
+        Index::load(&index_file, &self.path)
+    }
+
+    fn with_pruning(&self, mut analyzer: Analyzer) -> Analyzer {
+        analyzer.set_pruning_policy(self.config.prune.policy());
+        analyzer
+    }
+
+    fn analyzer(&self) -> anyhow::Result<Analyzer> {
+        Ok(self.with_pruning(self.load_index()?.into_analyzer()))
+    }
+
+    // Keeps clangd running, for the subcommands that reindex changed files.
+    fn clangd_analyzer(&self) -> anyhow::Result<Analyzer> {
+        Ok(self.with_pruning(self.build()?.into_analyzer()))
+    }
+}
+
+#[derive(StructOpt, Debug)]
+struct IndexOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    /// discard the cached index and index every file again
+    #[structopt(long = "force")]
+    force: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct QueryOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    #[structopt(short = "q", long = "query", required_unless = "saved")]
+    query: Option<String>,
+    /// run the query saved under this name in the config
+    #[structopt(short = "n", long = "saved", conflicts_with = "query")]
+    saved: Option<String>,
+    /// value of a $parameter in the query as NAME=VALUE, can be repeated
+    #[structopt(long = "param", number_of_values = 1, parse(try_from_str = parse_param))]
+    params: Vec<(String, String)>,
+    /// dot or json
+    #[structopt(long = "format")]
+    format: Option<ExportFormat>,
+    #[structopt(short = "o", long = "output-file")]
+    output: Option<String>,
+    /// none, times-used[=N], fan-in=N or query-paths
+    #[structopt(long = "prune")]
+    prune: Option<prune::PruneSpec>,
+    /// collapse the graph to files (file) or directories (dir=DEPTH)
+    #[structopt(long = "aggregate")]
+    aggregate: Option<aggregate::Granularity>,
+    /// save the resulting graph as a JSON snapshot
+    #[structopt(long = "save-snapshot")]
+    save_snapshot: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct StatsOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    /// report fan-in, fan-out and centrality of every function instead
+    #[structopt(long = "functions")]
+    functions: bool,
+    /// csv or json
+    #[structopt(long = "format", default_value = "csv")]
+    format: stats::StatsFormat,
+    /// name, fan-in, fan-out, transitive-callers, transitive-callees, betweenness or pagerank
+    #[structopt(long = "sort", default_value = "betweenness")]
+    sort: stats::StatsColumn,
+    #[structopt(short = "o", long = "output-file")]
+    output: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct CheckOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    /// file with the layering rules, defaults to the rules in the config
+    #[structopt(name = "RULES")]
+    rules: Option<String>,
+    #[structopt(short = "o", long = "output-file")]
+    output: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct ExportOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    /// dot or json
+    #[structopt(long = "format")]
+    format: Option<ExportFormat>,
+    /// collapse the graph to files (file) or directories (dir=DEPTH)
+    #[structopt(long = "aggregate")]
+    aggregate: Option<aggregate::Granularity>,
+    #[structopt(short = "o", long = "output-file")]
+    output: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct ServeOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    /// e.g. 127.0.0.1:8080
+    #[structopt(name = "ADDRESS")]
+    address: String,
+    /// serve the web viewer from this directory instead of the built in one
+    #[structopt(long = "viewer-dir")]
+    viewer_dir: Option<String>,
+    /// none, times-used[=N], fan-in=N or query-paths
+    #[structopt(long = "prune")]
+    prune: Option<prune::PruneSpec>,
+}
+
+#[derive(StructOpt, Debug)]
+struct DeadCodeOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    /// functions matching this regex are entry points, can be repeated
+    #[structopt(long = "entry")]
+    entry: Vec<String>,
+    /// file with exported symbols (one per line or `nm` output) that are entry points
+    #[structopt(long = "exported-symbols")]
+    exported_symbols: Option<String>,
+    /// do not treat functions whose address is taken as entry points
+    #[structopt(long = "ignore-address-taken")]
+    ignore_address_taken: bool,
+    #[structopt(short = "o", long = "output-file")]
+    output: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct ImpactOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    /// the functions changed since this git revision
+    #[structopt(long = "revision", required_unless = "diff")]
+    revision: Option<String>,
+    /// read a unified diff from this file (- for stdin) instead of asking git
+    #[structopt(long = "diff", conflicts_with = "revision")]
+    diff: Option<String>,
+    /// only follow callers up to this many calls away from a changed function
+    #[structopt(long = "depth")]
+    depth: Option<usize>,
+    /// dot writes the graph and prints the affected functions, json writes both
+    #[structopt(long = "format")]
+    format: Option<ExportFormat>,
+    #[structopt(short = "o", long = "output-file")]
+    output: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct DiffOpt {
+    #[structopt(name = "OLD")]
+    old: String,
+    #[structopt(name = "NEW")]
+    new: String,
+    #[structopt(short = "o", long = "output-file")]
+    output: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct GraphqlOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    /// the request, - for stdin
+    #[structopt(name = "REQUEST")]
+    request: String,
+    #[structopt(short = "o", long = "output-file")]
+    output: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct ReplOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    /// none, times-used[=N], fan-in=N or query-paths
+    #[structopt(long = "prune")]
+    prune: Option<prune::PruneSpec>,
+}
+
+#[derive(StructOpt, Debug)]
+struct QueryLspOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+}
+
+#[derive(StructOpt, Debug)]
+struct DaemonOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    /// socket to listen on, defaults to one in the cache directory of the profile
+    #[structopt(long = "socket")]
+    socket: Option<String>,
+    /// seconds between two scans of the project for changed files, 0 disables the watcher
+    #[structopt(long = "poll-interval", default_value = "2")]
+    poll_interval: u64,
+    /// none, times-used[=N], fan-in=N or query-paths
+    #[structopt(long = "prune")]
+    prune: Option<prune::PruneSpec>,
+}
+
+#[derive(StructOpt, Debug)]
+struct ClientOpt {
+    #[structopt(short = "q", long = "query", required_unless_one = &["saved", "stats", "reindex", "shutdown"])]
+    query: Option<String>,
+    /// run the query saved under this name in the config
+    #[structopt(short = "n", long = "saved", conflicts_with = "query")]
+    saved: Option<String>,
+    /// value of a $parameter in the query as NAME=VALUE, can be repeated
+    #[structopt(long = "param", number_of_values = 1, parse(try_from_str = parse_param))]
+    params: Vec<(String, String)>,
+    /// dot or json, defaults to the format in the config
+    #[structopt(long = "format")]
+    format: Option<ExportFormat>,
+    #[structopt(short = "o", long = "output-file")]
+    output: Option<String>,
+    /// the project the daemon was started for, used to find its socket
+    #[structopt(short = "p", long = "project-path")]
+    project_path: Option<String>,
+    #[structopt(long = "config")]
+    config: Option<String>,
+    #[structopt(long = "cache-dir")]
+    cache_dir: Option<String>,
+    #[structopt(long = "profile", default_value = "default")]
+    profile: String,
+    #[structopt(long = "compile-commands-dir")]
+    compile_commands_dir: Option<String>,
+    #[structopt(long = "socket")]
+    socket: Option<String>,
+    #[structopt(long = "stats")]
+    stats: bool,
+    /// csv or json
+    #[structopt(long = "stats-format", default_value = "csv")]
+    stats_format: stats::StatsFormat,
+    /// none, times-used[=N], fan-in=N or query-paths, defaults to the policy of the daemon
+    #[structopt(long = "prune")]
+    prune: Option<prune::PruneSpec>,
+    /// rebuild the index, optionally naming the files that changed
+    #[structopt(long = "reindex")]
+    reindex: Option<Vec<String>>,
+    #[structopt(long = "shutdown")]
+    shutdown: bool,
+}
+
+#[derive(StructOpt, Debug)]
+enum ConfigOpt {
+    /// Print the settings after applying the command line options to the config file
+    Show(ShowOpt),
+}
+
+#[derive(StructOpt, Debug)]
+struct ShowOpt {
+    #[structopt(flatten)]
+    index: IndexArgs,
+    /// dot or json
+    #[structopt(long = "format")]
+    format: Option<ExportFormat>,
+    /// none, times-used[=N], fan-in=N or query-paths
+    #[structopt(long = "prune")]
+    prune: Option<prune::PruneSpec>,
+}
+
+// Runs the command line tool and returns the exit code of the process.
+pub fn main() -> i32 {
+    match try_main() {
+        Ok(code) => code,
+        Err(err) if err.is::<MissingIndex>() => {
+            report(&err);
+            EXIT_NO_INDEX
+        }
+        Err(err) => {
+            report(&err);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn report(err: &anyhow::Error) {
+    eprintln!("ERROR: {}", err);
+    err.chain()
+        .skip(1)
+        .for_each(|cause| eprintln!("because: {}", cause));
+}
+
+fn invalid_input(err: anyhow::Error) -> i32 {
+    report(&err);
+    EXIT_INVALID_INPUT
+}
+
+fn parse_param(param: &str) -> Result<(String, String), String> {
+    match param.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.trim_start_matches('$').to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got '{}'", param)),
+    }
+}
+
+fn output(filename: &Option<String>) -> anyhow::Result<Box<dyn std::io::Write>> {
+    Ok(match filename {
+        Some(filename) => Box::new(std::fs::File::create(filename)?),
+        None => Box::new(std::io::stdout()),
+    })
+}
+
+// Every subcommand returns the exit code of the process, errors exit with 1.
+fn try_main() -> anyhow::Result<i32> {
+    match Opt::from_args() {
+        Opt::Index(opt) => index_main(opt),
+        Opt::Query(opt) => query_main(opt),
+        Opt::Stats(opt) => stats_main(opt),
+        Opt::Check(opt) => check_main(opt),
+        Opt::Export(opt) => export_main(opt),
+        Opt::Serve(opt) => serve_main(opt),
+        Opt::DeadCode(opt) => dead_code_main(opt),
+        Opt::Impact(opt) => impact_main(opt),
+        Opt::Diff(opt) => diff_main(opt),
+        Opt::Graphql(opt) => graphql_main(opt),
+        Opt::Repl(opt) => repl_main(opt),
+        Opt::QueryLsp(opt) => query_lsp_main(opt),
+        Opt::Daemon(opt) => daemon_main(opt),
+        Opt::Client(opt) => client_main(opt),
+        Opt::Config(ConfigOpt::Show(opt)) => config_show_main(opt),
+    }
+}
+
+fn index_main(opt: IndexOpt) -> anyhow::Result<i32> {
+    let start = Utc::now().time();
+    let project = opt.index.load()?;
+    let index_file = project.index_file();
+    if opt.force && index_file.exists() {
+        std::fs::remove_file(&index_file)?;
+    }
+    let modified = |path: &PathBuf| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let before = modified(&index_file);
+
+    let mut index = project.build()?;
+    let summary = index.summary();
+    index.close();
+
+    // the cached index is only rewritten when files were added, removed or
+    // modified since it was built
+    let state = if before.is_some() && before == modified(&index_file) { "up to date" } else { "indexed" };
+    println!("{}: {} in {} ms", state, summary, (Utc::now().time() - start).num_milliseconds());
+    println!("index: {}", index_file.display());
+    Ok(0)
+}
+
+fn query_main(opt: QueryOpt) -> anyhow::Result<i32> {
+    let start = Utc::now().time();
+    let mut project = opt.index.load()?;
+    if let Some(prune) = &opt.prune {
+        project.config.prune = prune.clone();
+    }
+    let query = match (&opt.query, &opt.saved) {
+        (Some(query), _) => Query::parse(query),
+        (None, Some(name)) => match project.config.saved_query(name) {
+            Some(saved) => saved.query(),
+            None => Err(anyhow::anyhow!("no saved query named '{}'", name)),
+        },
+        (None, None) => unreachable!("structopt requires --query or --saved"),
+    };
+    // values on the command line replace the saved ones
+    let query = query.map(|query| opt.params.iter().fold(query, |query, (name, value)| query.bind(name, value)));
+    let query = match query.and_then(|query| query.check_parameters().map(|_| query)) {
+        Ok(query) => query,
+        Err(err) => return Ok(invalid_input(err)),
+    };
+    let mut out = output(&opt.output)?;
+    let mut parser = project.analyzer()?;
+    let result = parser.run(&query).map(|graph| match opt.aggregate {
+        Some(granularity) => parser.aggregate(&graph, granularity),
+        None => graph,
+    });
+    parser.close_lsp();
+    let graph = result?;
+
+    if let Some(path) = &opt.save_snapshot {
+        snapshot::Snapshot::from_graph(&graph).save(path)?;
+    }
+    if !parser.pruned().is_empty() {
+        eprintln!("pruning policy {} removed {} nodes", parser.pruning_policy(), parser.pruned().len());
+        for pruned in parser.pruned() {
+            eprintln!("{}", pruned);
+        }
+    }
+    if opt.index.benchmark {
+        let diff = Utc::now().time() - start;
+        eprintln!("Time till parsing is finished: {} ns", diff.num_microseconds().unwrap());
+    }
+
+    let format = opt.format.unwrap_or(project.config.format);
+    out.write_all(format.exporter().export(&graph)?.as_bytes())?;
+    Ok(0)
+}
+
+fn stats_main(opt: StatsOpt) -> anyhow::Result<i32> {
+    let project = opt.index.load()?;
+    let mut out = output(&opt.output)?;
+    let mut index = project.load_index()?;
+    if !opt.functions {
+        let summary = index.summary();
+        index.close();
+        writeln!(out, "files: {}", summary.files)?;
+        writeln!(out, "functions: {}", summary.functions)?;
+        writeln!(out, "calls: {}", summary.calls)?;
+        let index_file = project.index_file();
+        if let Ok(metadata) = std::fs::metadata(&index_file) {
+            writeln!(out, "index: {} ({} bytes)", index_file.display(), metadata.len())?;
+        }
+        return Ok(0);
+    }
+
+    let mut parser = index.into_analyzer();
+    let mut function_stats = parser.stats();
+    parser.close_lsp();
+    stats::sort_stats(&mut function_stats, opt.sort);
+    let report = match opt.format {
+        stats::StatsFormat::Csv => stats::stats_to_csv(&function_stats),
+        stats::StatsFormat::Json => stats::stats_to_json(&function_stats)?,
+    };
+    out.write_all(report.as_bytes())?;
+    Ok(0)
+}
+
+fn check_main(opt: CheckOpt) -> anyhow::Result<i32> {
+    let project = opt.index.load()?;
+    let (rules, source) = match &opt.rules {
+        Some(file) => (rules::RuleSet::load(file), file.clone()),
+        None => (project.config.rules(), config::CONFIG_FILE.to_string()),
+    };
+    let rules = match rules {
+        Ok(rules) if rules.rules.is_empty() => {
+            return Ok(invalid_input(anyhow::anyhow!("no rules in {}, pass a rule file or add [[rule]] tables", source)));
+        }
+        Ok(rules) => rules,
+        Err(err) => return Ok(invalid_input(err)),
+    };
+    let mut out = output(&opt.output)?;
+    let mut parser = project.load_index()?.into_analyzer();
+    let violations = parser.check(&rules);
+    parser.close_lsp();
+    for violation in &violations {
+        writeln!(out, "{}", violation)?;
+    }
+    if violations.is_empty() {
+        return Ok(0);
+    }
+    eprintln!("{} calls violate the rules in {}", violations.len(), source);
+    Ok(EXIT_VIOLATIONS)
+}
+
+fn export_main(opt: ExportOpt) -> anyhow::Result<i32> {
+    let project = opt.index.load()?;
+    let mut out = output(&opt.output)?;
+    let mut parser = project.load_index()?.into_analyzer();
+    let mut graph = parser.index_graph();
+    if let Some(granularity) = opt.aggregate {
+        graph = parser.aggregate(&graph, granularity);
+    }
+    parser.close_lsp();
+    let format = opt.format.unwrap_or(project.config.format);
+    out.write_all(format.exporter().export(&graph)?.as_bytes())?;
+    Ok(0)
+}
+
+fn serve_main(opt: ServeOpt) -> anyhow::Result<i32> {
+    let mut project = opt.index.load()?;
+    if let Some(prune) = opt.prune {
+        project.config.prune = prune;
+    }
+    let mut server_config = server::ServerConfig::new(&project.path);
+    if let Some(viewer_dir) = &opt.viewer_dir {
+        server_config.viewer_dir = Some(viewer_dir.into());
+    }
+    server::serve(&opt.address, server_config, move || project.clangd_analyzer())?;
+    Ok(0)
+}
+
+fn dead_code_main(opt: DeadCodeOpt) -> anyhow::Result<i32> {
+    let project = opt.index.load()?;
+    let mut entry_points = dead_code::EntryPoints {
+        address_taken: !opt.ignore_address_taken,
+        ..dead_code::EntryPoints::default()
+    };
+    for pattern in &opt.entry {
+        entry_points.patterns.push(regex::Regex::new(pattern)?);
+    }
+    if let Some(file) = &opt.exported_symbols {
+        entry_points.add_exported_symbols(&std::fs::read_to_string(file)?);
+    }
+    let mut out = output(&opt.output)?;
+    let mut parser = project.load_index()?.into_analyzer();
+    let report = parser.dead_code(&entry_points);
+    parser.close_lsp();
+    out.write_all(report.to_string().as_bytes())?;
+    Ok(0)
+}
+
+fn impact_main(opt: ImpactOpt) -> anyhow::Result<i32> {
+    let project = opt.index.load()?;
+    let unified_diff = match (&opt.revision, &opt.diff) {
+        (Some(revision), _) => impact::git_diff(&project.path, revision)?,
+        (None, Some(path)) if path == "-" => std::io::read_to_string(std::io::stdin())?,
+        (None, Some(path)) => std::fs::read_to_string(path)?,
+        (None, None) => unreachable!("structopt requires --revision or --diff"),
+    };
+    let changes = impact::parse_unified_diff(&unified_diff);
+    let mut out = output(&opt.output)?;
+    let mut parser = project.load_index()?.into_analyzer();
+    let report = parser.impact(&changes, opt.depth);
+    let output = match opt.format.unwrap_or(project.config.format) {
+        ExportFormat::Json => report.to_json(parser.graph())?,
+        format => {
+            eprint!("{}", report);
+            format.exporter().export(parser.graph())?
+        }
+    };
+    out.write_all(output.as_bytes())?;
+    parser.close_lsp();
+    Ok(0)
+}
+
+fn diff_main(opt: DiffOpt) -> anyhow::Result<i32> {
+    let (old, new) = match (snapshot::Snapshot::load(&opt.old), snapshot::Snapshot::load(&opt.new)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(err), _) | (_, Err(err)) => return Ok(invalid_input(err)),
+    };
+    let diff = snapshot::diff(&old, &new);
+    if diff.is_empty() {
+        eprintln!("{} and {} have the same calls", opt.old, opt.new);
+    } else {
+        eprint!("{}", diff);
+    }
+    output(&opt.output)?.write_all(diff.to_graph().graph_to_dot().as_bytes())?;
+    Ok(0)
+}
+
+fn graphql_main(opt: GraphqlOpt) -> anyhow::Result<i32> {
+    let project = opt.index.load()?;
+    let request = if opt.request == "-" { std::io::read_to_string(std::io::stdin())? } else { opt.request.clone() };
+    let mut out = output(&opt.output)?;
+    let context = graphql::GraphContext::new(project.analyzer()?);
+    let response = graphql::execute(&graphql::schema(), &context, &request);
+    context.into_analyzer().close_lsp();
+    writeln!(out, "{}", response)?;
+    Ok(0)
+}
+
+fn repl_main(opt: ReplOpt) -> anyhow::Result<i32> {
+    let mut project = opt.index.load()?;
+    if let Some(prune) = opt.prune {
+        project.config.prune = prune;
+    }
+    let mut parser = project.clangd_analyzer()?;
+    let result = repl::run(&mut parser);
+    parser.close_lsp();
+    result.map(|_| 0)
+}
+
+fn query_lsp_main(opt: QueryLspOpt) -> anyhow::Result<i32> {
+    let project = opt.index.load()?;
+    let project_path = std::fs::canonicalize(&project.path)?;
+    let mut server = query_lsp::QueryLanguageServer::new(project.load_index()?.into_analyzer(), project_path);
+    let result = query_lsp::run(&mut server, &mut std::io::stdin().lock(), &mut std::io::stdout().lock());
+    server.into_analyzer().close_lsp();
+    result.map(|_| 0)
+}
+
+fn daemon_main(opt: DaemonOpt) -> anyhow::Result<i32> {
+    let mut project = opt.index.load()?;
+    if let Some(prune) = opt.prune {
+        project.config.prune = prune;
+    }
+    let socket = match &opt.socket {
+        Some(socket) => PathBuf::from(socket),
+        None => project.options.profile(&project.path).socket_file(),
+    };
+    let daemon = daemon::Daemon {
+        socket_path: socket,
+        project_path: Some(PathBuf::from(&project.path)).filter(|_| opt.poll_interval > 0),
+        sources: project.options.sources.clone(),
+        poll_interval: std::time::Duration::from_secs(opt.poll_interval),
+    };
+    let mut parser = project.clangd_analyzer()?;
+    let result = daemon.run(&mut parser);
+    parser.close_lsp();
+    result.map(|_| 0)
+}
+
+fn client_main(opt: ClientOpt) -> anyhow::Result<i32> {
+    let (project_path, config) = locate(opt.project_path.as_deref(), opt.config.as_deref())?;
+    let socket = match &opt.socket {
+        Some(socket) => PathBuf::from(socket),
+        None => {
+            let cache_dir = opt.cache_dir.clone().or_else(|| config.cache_dir(Path::new(&project_path)));
+            let sources = config.source_filter()?;
+            let profile = cache::IndexProfile::new(
+                &project_path,
+                &opt.profile,
+                opt.compile_commands_dir.clone(),
+                cache_dir,
+                &sources,
+                &config.language_server.args,
+            );
+            profile.socket_file()
+        }
+    };
+    let request = if opt.shutdown {
+        daemon::Request::Shutdown
+    } else if let Some(files) = &opt.reindex {
+        daemon::Request::Reindex { files: files.clone() }
+    } else if opt.stats {
+        daemon::Request::Stats { format: Some(opt.stats_format.to_string()) }
+    } else {
+        let format = opt.format.unwrap_or(config.format);
+        let (query, mut params) = match (&opt.query, &opt.saved) {
+            (Some(query), _) => (query.clone(), BTreeMap::new()),
+            (None, Some(name)) => match config.saved_query(name) {
+                Some(config::SavedQuery::Text(query)) => (query.clone(), BTreeMap::new()),
+                Some(config::SavedQuery::WithParams { query, params }) => (query.clone(), params.clone()),
+                None => return Ok(invalid_input(anyhow::anyhow!("no saved query named '{}'", name))),
+            },
+            (None, None) => unreachable!("structopt requires --query or --saved"),
+        };
+        // values on the command line replace the saved ones
+        params.extend(opt.params.iter().cloned());
+        daemon::Request::Query { query, format: Some(format.to_string()), prune: opt.prune.clone(), params }
+    };
+
+    let response = daemon::send(&socket, &request)?;
+    if !response.ok {
+        anyhow::bail!("{}", response.error.unwrap_or_default());
+    }
+    let output = response.output.unwrap_or_default();
+    match &opt.output {
+        Some(filename) => std::fs::write(filename, output)?,
+        None => print!("{}", output),
+    }
+    Ok(0)
+}
+
+fn config_show_main(opt: ShowOpt) -> anyhow::Result<i32> {
+    let mut project = opt.index.load()?;
+    if let Some(format) = opt.format {
+        project.config.format = format;
+    }
+    if let Some(prune) = opt.prune {
+        project.config.prune = prune;
+    }
+    println!("# project: {}", project.path);
+    print!("{}", project.config.to_toml()?);
+    Ok(0)
+}
@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::export::ExportFormat;
+use crate::prune::PruneSpec;
+use crate::query::Query;
+use crate::rules::{RuleConfig, RuleSet};
+
+pub const CONFIG_FILE: &str = "code-analysis.toml";
+
+// Settings of a project, read from code-analysis.toml at its root:
+//
+//   cache_dir = ".code-analysis"
+//   format = "json"
+//   prune = "fan-in=10"
+//
+//   [language_server]
+//   path = "/usr/lib/llvm-14/bin/clangd"
+//   args = ["--log=error"]
+//
+//   [sources]
+//   include = ["^src/"]
+//   exclude = ["_test\\.c$"]
+//   extensions = ["c", "cpp"]
+//
+//   [queries]
+//   startup = "{@main}"
+//   callers = { query = "{@$target}", params = { target = "main" } }
+//
+//   [[rule]]
+//   name = "drivers must not call into the ui"
+//   from = { file = "^drivers/" }
+//   to = { file = "^ui/" }
+//
+// Every setting is optional. Patterns are regexes on paths relative to the
+// project root, a relative cache_dir is relative to it as well. The params
+// of a saved query are defaults for its `$parameters`. Rules have the format
+// of a rule file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<String>,
+    pub format: ExportFormat,
+    pub prune: PruneSpec,
+    pub language_server: LanguageServerConfig,
+    pub sources: SourcesConfig,
+    // saved queries with params are tables, which toml emits after values
+    #[serde(serialize_with = "toml::ser::tables_last")]
+    pub queries: BTreeMap<String, SavedQuery>,
+    pub rule: Vec<RuleConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LanguageServerConfig {
+    pub path: String,
+    pub args: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourcesConfig {
+    // an empty list includes every file
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub extensions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum SavedQuery {
+    Text(String),
+    WithParams {
+        query: String,
+        #[serde(default)]
+        params: BTreeMap<String, String>,
+    },
+}
+
+impl SavedQuery {
+    pub fn text(&self) -> &str {
+        match self {
+            SavedQuery::Text(query) | SavedQuery::WithParams { query, .. } => query,
+        }
+    }
+
+    // The query with the saved values of its parameters bound.
+    pub fn query(&self) -> anyhow::Result<Query> {
+        let mut query = Query::parse(self.text())?;
+        if let SavedQuery::WithParams { params, .. } = self {
+            for (name, value) in params {
+                query = query.bind(name, value);
+            }
+        }
+        Ok(query)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            cache_dir: None,
+            format: ExportFormat::Dot,
+            prune: PruneSpec::default(),
+            language_server: LanguageServerConfig::default(),
+            sources: SourcesConfig::default(),
+            queries: BTreeMap::new(),
+            rule: Vec::new(),
+        }
+    }
+}
+
+impl Default for LanguageServerConfig {
+    fn default() -> Self {
+        LanguageServerConfig { path: "/usr/bin/clangd".to_string(), args: Vec::new() }
+    }
+}
+
+impl Default for SourcesConfig {
+    fn default() -> Self {
+        SourcesConfig {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            extensions: vec!["c".to_string(), "cpp".to_string()],
+        }
+    }
+}
+
+impl Config {
+    // Also fails on settings that only parse later, like patterns, rules
+    // and saved queries.
+    pub fn from_toml(source: &str) -> anyhow::Result<Config> {
+        let config: Config = toml::from_str(source).context("invalid config file")?;
+        config.source_filter()?;
+        config.rules()?;
+        for (name, query) in &config.queries {
+            query.query().with_context(|| format!("invalid saved query '{}'", name))?;
+        }
+        Ok(config)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Config> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).with_context(|| format!("could not read config file {}", path.display()))?;
+        Config::from_toml(&source).with_context(|| format!("could not load {}", path.display()))
+    }
+
+    // The config at the root of a project, the defaults if it has none.
+    pub fn for_project<P: AsRef<Path>>(project_path: P) -> anyhow::Result<Config> {
+        let path = project_path.as_ref().join(CONFIG_FILE);
+        if path.exists() {
+            Config::load(path)
+        } else {
+            Ok(Config::default())
+        }
+    }
+
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    pub fn source_filter(&self) -> anyhow::Result<SourceFilter> {
+        SourceFilter::new(&self.sources)
+    }
+
+    pub fn rules(&self) -> anyhow::Result<RuleSet> {
+        RuleSet::from_configs(self.rule.clone())
+    }
+
+    pub fn saved_query(&self, name: &str) -> Option<&SavedQuery> {
+        self.queries.get(name)
+    }
+
+    pub fn cache_dir(&self, project_path: &Path) -> Option<String> {
+        self.cache_dir.as_ref().map(|dir| project_path.join(dir).to_string_lossy().to_string())
+    }
+}
+
+// The nearest directory from `start` upwards that has a config file.
+pub fn find_project_root(start: &Path) -> Option<PathBuf> {
+    start.ancestors().find(|dir| dir.join(CONFIG_FILE).is_file()).map(Path::to_path_buf)
+}
+
+// Decides which files of a project are indexed.
+#[derive(Debug, Clone)]
+pub struct SourceFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    extensions: Vec<String>,
+}
+
+impl Default for SourceFilter {
+    fn default() -> Self {
+        SourceFilter::new(&SourcesConfig::default()).expect("the default sources are valid")
+    }
+}
+
+impl SourceFilter {
+    pub fn new(config: &SourcesConfig) -> anyhow::Result<SourceFilter> {
+        let compile = |patterns: &[String]| -> anyhow::Result<Vec<Regex>> {
+            patterns
+                .iter()
+                .map(|pattern| Regex::new(pattern).with_context(|| format!("invalid source pattern '{}'", pattern)))
+                .collect()
+        };
+        Ok(SourceFilter {
+            include: compile(&config.include)?,
+            exclude: compile(&config.exclude)?,
+            extensions: config.extensions.iter().map(|ext| ext.trim_start_matches('.').to_string()).collect(),
+        })
+    }
+
+    // `file` is relative to the project root
+    pub fn matches(&self, file: &str) -> bool {
+        let extension = Path::new(file).extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        self.extensions.iter().any(|ext| ext == extension)
+            && (self.include.is_empty() || self.include.iter().any(|regex| regex.is_match(file)))
+            && !self.exclude.iter().any(|regex| regex.is_match(file))
+    }
+
+    // Tells filters that select different files apart, for cache keys.
+    pub fn key(&self) -> String {
+        let patterns = |regexes: &[Regex]| regexes.iter().map(Regex::as_str).collect::<Vec<_>>().join("\0");
+        format!("{}\n{}\n{}", patterns(&self.include), patterns(&self.exclude), self.extensions.join("\0"))
+    }
+}
+
+#[cfg(test)]
+mod config_test;
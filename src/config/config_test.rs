@@ -0,0 +1,100 @@
+use super::*;
+
+const CONFIG: &str = r#"
+cache_dir = ".code-analysis"
+format = "json"
+prune = "fan-in=10"
+
+[language_server]
+path = "/opt/llvm/bin/clangd"
+args = ["--log=error"]
+
+[sources]
+include = ["^src/"]
+exclude = ["_test\\.c$"]
+
+[queries]
+startup = "{@main}"
+callers = { query = "{@$target}", params = { target = "main" } }
+
+[[rule]]
+name = "drivers must not call into the ui"
+from = { file = "^drivers/" }
+to = { file = "^ui/" }
+"#;
+
+#[test]
+fn test_parse_config() {
+    let config = Config::from_toml(CONFIG).unwrap();
+    assert_eq!(config.format, ExportFormat::Json);
+    assert_eq!(config.prune, PruneSpec::FanIn(10));
+    assert_eq!(config.language_server.path, "/opt/llvm/bin/clangd");
+    assert_eq!(config.language_server.args, vec!["--log=error".to_string()]);
+    assert_eq!(config.sources.extensions, vec!["c".to_string(), "cpp".to_string()]);
+    assert_eq!(config.saved_query("startup").unwrap().text(), "{@main}");
+    assert!(config.saved_query("missing").is_none());
+    let callers = config.saved_query("callers").unwrap().query().unwrap();
+    assert_eq!(callers.text(), "{@$target}");
+    assert_eq!(callers.parameters()["target"], "main");
+    assert_eq!(config.rules().unwrap().rules.len(), 1);
+    assert_eq!(config.cache_dir(Path::new("/project")), Some("/project/.code-analysis".to_string()));
+}
+
+#[test]
+fn test_empty_config_has_defaults() {
+    let config = Config::from_toml("").unwrap();
+    assert_eq!(config.format, ExportFormat::Dot);
+    assert_eq!(config.prune, PruneSpec::TimesUsed(2));
+    assert_eq!(config.language_server, LanguageServerConfig::default());
+    assert_eq!(config.sources, SourcesConfig::default());
+    assert!(config.cache_dir.is_none());
+}
+
+#[test]
+fn test_invalid_config() {
+    assert!(Config::from_toml("format = \"svg\"").is_err());
+    assert!(Config::from_toml("prune = \"fan-in\"").is_err());
+    assert!(Config::from_toml("unknown = 1").is_err());
+    assert!(Config::from_toml("[sources]\ninclude = [\"(\"]").is_err());
+    assert!(Config::from_toml("[queries]\nbroken = \"{@main\"").is_err());
+    assert!(Config::from_toml("[[rule]]\nname = \"r\"\nto = { file = \"(\" }").is_err());
+}
+
+#[test]
+fn test_show_parses_back() {
+    let config = Config::from_toml(CONFIG).unwrap();
+    let shown = config.to_toml().unwrap();
+    let parsed = Config::from_toml(&shown).unwrap();
+    assert_eq!(parsed.to_toml().unwrap(), shown);
+    assert!(shown.contains("prune = \"fan-in=10\""), "{}", shown);
+}
+
+#[test]
+fn test_source_filter() {
+    let sources = SourcesConfig {
+        include: vec!["^src/".to_string()],
+        exclude: vec!["_test\\.c$".to_string()],
+        extensions: vec!["c".to_string(), ".h".to_string()],
+    };
+    let filter = SourceFilter::new(&sources).unwrap();
+    assert!(filter.matches("src/main.c"));
+    assert!(filter.matches("src/main.h"));
+    assert!(!filter.matches("src/main.cpp"));
+    assert!(!filter.matches("src/main_test.c"));
+    assert!(!filter.matches("tools/gen.c"));
+    assert!(SourceFilter::default().matches("tools/gen.cpp"));
+}
+
+#[test]
+fn test_find_project_root() {
+    let root = std::env::temp_dir().join(format!("code_analysis_config_{}", std::process::id()));
+    let nested = root.join("src").join("util");
+    std::fs::create_dir_all(&nested).unwrap();
+    assert_eq!(find_project_root(&nested), None);
+
+    std::fs::write(root.join(CONFIG_FILE), "format = \"json\"\n").unwrap();
+    assert_eq!(find_project_root(&nested), Some(root.clone()));
+    assert_eq!(Config::for_project(&root).unwrap().format, ExportFormat::Json);
+    assert_eq!(Config::for_project(&nested).unwrap().format, ExportFormat::Dot);
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,38 @@
+use std::collections::HashSet;
+
+use petgraph::algo::tarjan_scc;
+use petgraph::graphmap::DiGraphMap;
+
+use crate::call_index::CallIndex;
+
+// Returns every strongly connected component of the call graph with more
+// than one function, plus every function that calls itself. The functions of
+// a cycle and the cycles themselves are sorted by name.
+pub fn find_cycles(index: &dyn CallIndex) -> Vec<Vec<String>> {
+    let mut call_graph: DiGraphMap<&str, ()> = DiGraphMap::new();
+    let mut self_recursive: HashSet<&str> = HashSet::new();
+    for caller in index.functions() {
+        call_graph.add_node(caller);
+        for callee in index.callees(caller) {
+            if callee == caller {
+                self_recursive.insert(caller);
+            }
+            call_graph.add_edge(caller, callee, ());
+        }
+    }
+
+    let mut cycles: Vec<Vec<String>> = tarjan_scc(&call_graph)
+        .into_iter()
+        .filter(|component| component.len() > 1 || self_recursive.contains(component[0]))
+        .map(|component| {
+            let mut names: Vec<String> = component.into_iter().map(|name| name.to_string()).collect();
+            names.sort();
+            names
+        })
+        .collect();
+    cycles.sort();
+    cycles
+}
+
+#[cfg(test)]
+mod cycles_test;
@@ -0,0 +1,35 @@
+use super::*;
+use std::collections::HashMap;
+use crate::call_index::MapIndex;
+
+fn calls(edges: &[(&str, &[&str])]) -> MapIndex {
+    let function_calls: HashMap<String, Vec<String>> = edges
+        .iter()
+        .map(|(caller, callees)| (caller.to_string(), callees.iter().map(|c| c.to_string()).collect()))
+        .collect();
+    MapIndex::from_calls(function_calls)
+}
+
+#[test]
+fn test_find_mutual_and_self_recursion() {
+    let function_calls = calls(&[
+        ("main", &["parse", "fact"]),
+        ("parse", &["parse_expr"]),
+        ("parse_expr", &["parse_term", "log"]),
+        ("parse_term", &["parse_expr"]),
+        ("fact", &["fact"]),
+        ("log", &[]),
+    ]);
+
+    let cycles = find_cycles(&function_calls);
+    assert_eq!(cycles, vec![
+        vec!["fact".to_string()],
+        vec!["parse_expr".to_string(), "parse_term".to_string()],
+    ]);
+}
+
+#[test]
+fn test_no_cycles_in_call_tree() {
+    let function_calls = calls(&[("main", &["a", "b"]), ("a", &["b"]), ("b", &[])]);
+    assert!(find_cycles(&function_calls).is_empty());
+}
@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::analyzer::Analyzer;
+use crate::config::SourceFilter;
+use crate::export::ExportFormat;
+use crate::prune::PruneSpec;
+use crate::query::Query;
+use crate::stats;
+
+// One JSON object per line in both directions, e.g.
+//
+//   {"command": "query", "query": "{@$entry}", "format": "json", "params": {"entry": "main"}}
+//   {"ok": true, "output": "..."}
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    Query {
+        query: String,
+        #[serde(default)]
+        format: Option<String>,
+        // the pruning policy of the daemon when not given
+        #[serde(default)]
+        prune: Option<PruneSpec>,
+        // values of the `$parameters` in the query
+        #[serde(default)]
+        params: BTreeMap<String, String>,
+    },
+    // the files that changed, relative to the project; files modified since
+    // they were indexed are picked up too
+    Reindex {
+        #[serde(default)]
+        files: Vec<String>,
+    },
+    Stats {
+        #[serde(default)]
+        format: Option<String>,
+    },
+    Shutdown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    fn output(output: String) -> Response {
+        Response { ok: true, output: Some(output), error: None }
+    }
+
+    fn error(error: impl ToString) -> Response {
+        Response { ok: false, output: None, error: Some(error.to_string()) }
+    }
+}
+
+pub fn handle_request(analyzer: &mut Analyzer, request: Request) -> Response {
+    match request {
+        Request::Query { query, format, prune, params } => {
+            let format: ExportFormat = match format.as_deref().unwrap_or("dot").parse() {
+                Ok(format) => format,
+                Err(err) => return Response::error(err),
+            };
+            let mut query = match Query::parse(&query) {
+                Ok(query) => query,
+                Err(err) => return Response::error(err),
+            };
+            if let Some(prune) = prune {
+                query = query.with_pruning(prune);
+            }
+            for (name, value) in &params {
+                query = query.bind(name, value);
+            }
+            if let Err(err) = query.check_parameters() {
+                return Response::error(err);
+            }
+            let graph = match analyzer.run(&query) {
+                Ok(graph) => graph,
+                Err(err) => return Response::error(err),
+            };
+            match format.exporter().export(&graph) {
+                Ok(output) => Response::output(output),
+                Err(err) => Response::error(err),
+            }
+        }
+        Request::Reindex { files } => {
+            analyzer.reindex(&files);
+            let indexed = analyzer.lang_server().call_index().files().count();
+            Response::output(format!("{} files changed, {} files indexed", files.len(), indexed))
+        }
+        Request::Stats { format } => {
+            let format: stats::StatsFormat = match format.as_deref().unwrap_or("csv").parse() {
+                Ok(format) => format,
+                Err(err) => return Response::error(err),
+            };
+            let mut function_stats = analyzer.stats();
+            stats::sort_stats(&mut function_stats, stats::StatsColumn::Betweenness);
+            match format {
+                stats::StatsFormat::Csv => Response::output(stats::stats_to_csv(&function_stats)),
+                stats::StatsFormat::Json => match stats::stats_to_json(&function_stats) {
+                    Ok(output) => Response::output(output),
+                    Err(err) => Response::error(err),
+                },
+            }
+        }
+        Request::Shutdown => Response::output("shutting down".to_string()),
+    }
+}
+
+enum Event {
+    Request(Request, mpsc::Sender<Response>),
+    FilesChanged(Vec<String>),
+}
+
+// Modification times of the sources below `dir` that `sources` indexes, by
+// path relative to it.
+pub fn scan_sources(dir: &Path, sources: &SourceFilter) -> HashMap<PathBuf, SystemTime> {
+    let mut found = HashMap::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let entries = match std::fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if metadata.is_dir() && !hidden {
+                pending.push(path);
+            } else if let (Ok(modified), Ok(relative)) = (metadata.modified(), path.strip_prefix(dir)) {
+                if sources.matches(&relative.to_string_lossy()) {
+                    found.insert(relative.to_path_buf(), modified);
+                }
+            }
+        }
+    }
+    found
+}
+
+// Added, removed and modified files, sorted.
+pub fn changed_files(old: &HashMap<PathBuf, SystemTime>, new: &HashMap<PathBuf, SystemTime>) -> Vec<String> {
+    let mut changed: Vec<String> = new
+        .iter()
+        .filter(|(path, modified)| old.get(*path) != Some(*modified))
+        .map(|(path, _)| path)
+        .chain(old.keys().filter(|path| !new.contains_key(*path)))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    changed.sort();
+    changed
+}
+
+fn watch(project_path: PathBuf, filter: SourceFilter, interval: Duration, events: mpsc::Sender<Event>) {
+    let mut sources = scan_sources(&project_path, &filter);
+    loop {
+        thread::sleep(interval);
+        let current = scan_sources(&project_path, &filter);
+        let changed = changed_files(&sources, &current);
+        sources = current;
+        if !changed.is_empty() && events.send(Event::FilesChanged(changed)).is_err() {
+            return;
+        }
+    }
+}
+
+fn serve_connection(stream: UnixStream, events: mpsc::Sender<Event>) -> anyhow::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let (reply, response) = mpsc::channel();
+                if events.send(Event::Request(request, reply)).is_err() {
+                    bail!("the daemon is shutting down");
+                }
+                response.recv().unwrap_or_else(|_| Response::error("the daemon is shutting down"))
+            }
+            Err(err) => Response::error(format!("invalid request: {}", err)),
+        };
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+    }
+    Ok(())
+}
+
+pub struct Daemon {
+    pub socket_path: PathBuf,
+    // the project to watch for changes, not watched when None
+    pub project_path: Option<PathBuf>,
+    // the files whose changes are picked up, those that are indexed
+    pub sources: SourceFilter,
+    pub poll_interval: Duration,
+}
+
+impl Daemon {
+    // Serves requests until a shutdown request arrives.
+    pub fn run(&self, analyzer: &mut Analyzer) -> anyhow::Result<()> {
+        if UnixStream::connect(&self.socket_path).is_ok() {
+            bail!("a daemon is already listening on {}", self.socket_path.display());
+        }
+        // left behind by a daemon that did not shut down cleanly
+        let _ = std::fs::remove_file(&self.socket_path);
+        if let Some(dir) = self.socket_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let listener = UnixListener::bind(&self.socket_path)
+            .with_context(|| format!("could not listen on {}", self.socket_path.display()))?;
+        log::info!("listening on {}", self.socket_path.display());
+
+        let (events, receiver) = mpsc::channel();
+        let connection_events = events.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let events = connection_events.clone();
+                thread::spawn(move || {
+                    if let Err(err) = serve_connection(stream, events) {
+                        log::warn!("connection closed: {}", err);
+                    }
+                });
+            }
+        });
+        if let Some(project_path) = self.project_path.clone() {
+            let (filter, interval) = (self.sources.clone(), self.poll_interval);
+            thread::spawn(move || watch(project_path, filter, interval, events));
+        }
+
+        for event in receiver {
+            match event {
+                Event::Request(request, reply) => {
+                    let shutdown = request == Request::Shutdown;
+                    let _ = reply.send(handle_request(analyzer, request));
+                    if shutdown {
+                        break;
+                    }
+                }
+                Event::FilesChanged(files) => {
+                    log::info!("{} files changed, reindexing", files.len());
+                    analyzer.reindex(&files);
+                }
+            }
+        }
+        let _ = std::fs::remove_file(&self.socket_path);
+        Ok(())
+    }
+}
+
+// Sends one request to a running daemon.
+pub fn send(socket_path: &Path, request: &Request) -> anyhow::Result<Response> {
+    let mut stream = UnixStream::connect(socket_path)
+        .with_context(|| format!("no daemon is listening on {}", socket_path.display()))?;
+    writeln!(stream, "{}", serde_json::to_string(request)?)?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    serde_json::from_str(&line).context("invalid response from the daemon")
+}
+
+#[cfg(test)]
+mod daemon_test;
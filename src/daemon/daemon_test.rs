@@ -0,0 +1,85 @@
+use super::*;
+use crate::config::SourcesConfig;
+use crate::test_support::MockLSPServer;
+
+#[test]
+fn test_request_format() {
+    let request: Request = serde_json::from_str(r#"{"command": "query", "query": "{@main}"}"#).unwrap();
+    assert_eq!(request, Request::Query { query: "{@main}".to_string(), format: None, prune: None, params: BTreeMap::new() });
+    let request: Request = serde_json::from_str(r#"{"command": "query", "query": "{@main}", "prune": "fan-in=3"}"#).unwrap();
+    assert_eq!(request, Request::Query { query: "{@main}".to_string(), format: None, prune: Some(PruneSpec::FanIn(3)), params: BTreeMap::new() });
+    assert!(serde_json::from_str::<Request>(r#"{"command": "query", "query": "{@main}", "prune": "most"}"#).is_err());
+    let request: Request = serde_json::from_str(r#"{"command": "reindex", "files": ["a.c"]}"#).unwrap();
+    assert_eq!(request, Request::Reindex { files: vec!["a.c".to_string()] });
+    assert_eq!(serde_json::to_string(&Request::Shutdown).unwrap(), r#"{"command":"shutdown"}"#);
+    assert_eq!(serde_json::to_string(&Response::error("no")).unwrap(), r#"{"ok":false,"error":"no"}"#);
+}
+
+#[test]
+fn test_handle_request() {
+    let mut analyzer = Analyzer::new(MockLSPServer::boxed());
+    let query = |format: &str| Request::Query { query: "{@parse}".to_string(), format: Some(format.to_string()), prune: None, params: BTreeMap::new() };
+
+    let response = handle_request(&mut analyzer, query("json"));
+    let graph: serde_json::Value = serde_json::from_str(&response.output.unwrap()).unwrap();
+    assert_eq!(graph["edges"][0]["caller"], "main");
+    assert!(handle_request(&mut analyzer, query("dot")).output.unwrap().starts_with("digraph"));
+    assert!(!handle_request(&mut analyzer, query("svg")).ok);
+    assert!(!handle_request(&mut analyzer, Request::Query { query: "{@parse".to_string(), format: None, prune: None, params: BTreeMap::new() }).ok);
+
+    let query = |params: &[(&str, &str)]| Request::Query {
+        query: "{@$target}".to_string(),
+        format: Some("json".to_string()),
+        prune: None,
+        params: params.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+    };
+    let response = handle_request(&mut analyzer, query(&[("target", "parse")]));
+    let graph: serde_json::Value = serde_json::from_str(&response.output.unwrap()).unwrap();
+    assert_eq!(graph["edges"][0]["callee"], "parse");
+    assert!(handle_request(&mut analyzer, query(&[])).error.unwrap().contains("$target"));
+
+    let response = handle_request(&mut analyzer, Request::Stats { format: None });
+    assert!(response.output.unwrap().starts_with("name,file,"));
+}
+
+#[test]
+fn test_changed_files() {
+    let dir = std::env::temp_dir().join(format!("code_analysis_watch_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("src/a.c"), "int a;").unwrap();
+    std::fs::write(dir.join("b.h"), "int b;").unwrap();
+    std::fs::write(dir.join("notes.txt"), "").unwrap();
+    let sources = SourceFilter::new(&SourcesConfig { extensions: vec!["c".to_string(), "h".to_string()], ..SourcesConfig::default() }).unwrap();
+    let old = scan_sources(&dir, &sources);
+    assert_eq!(old.len(), 2);
+    assert_eq!(scan_sources(&dir, &SourceFilter::default()).len(), 1);
+
+    let mut new = old.clone();
+    new.remove(Path::new("b.h"));
+    new.insert(PathBuf::from("src/a.c"), SystemTime::now() + Duration::from_secs(1));
+    new.insert(PathBuf::from("c.cpp"), SystemTime::now());
+    assert_eq!(changed_files(&old, &new), vec!["b.h", "c.cpp", "src/a.c"]);
+    assert!(changed_files(&old, &old).is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_socket_roundtrip() {
+    let socket_path = std::env::temp_dir().join(format!("code_analysis_daemon_{}.sock", std::process::id()));
+    let daemon = Daemon { socket_path: socket_path.clone(), project_path: None, sources: SourceFilter::default(), poll_interval: Duration::from_secs(1) };
+    let handle = thread::spawn(move || daemon.run(&mut Analyzer::new(MockLSPServer::boxed())));
+
+    let request = Request::Query { query: "{@parse}".to_string(), format: Some("json".to_string()), prune: None, params: BTreeMap::new() };
+    let mut response = None;
+    for _ in 0..100 {
+        if let Ok(reply) = send(&socket_path, &request) {
+            response = Some(reply);
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert!(response.unwrap().ok);
+    assert!(send(&socket_path, &Request::Shutdown).unwrap().ok);
+    handle.join().unwrap().unwrap();
+    assert!(!socket_path.exists());
+}
@@ -0,0 +1,112 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
+
+use regex::Regex;
+
+use crate::call_index::CallIndex;
+
+// Functions the program can start executing in. Functions whose address is
+// taken can be called through a pointer, so they are entry points as well
+// unless `address_taken` is disabled.
+pub struct EntryPoints {
+    pub names: HashSet<String>,
+    pub patterns: Vec<Regex>,
+    pub address_taken: bool,
+}
+
+impl Default for EntryPoints {
+    fn default() -> Self {
+        EntryPoints {
+            names: HashSet::from(["main".to_string()]),
+            patterns: Vec::new(),
+            address_taken: true,
+        }
+    }
+}
+
+impl EntryPoints {
+    // Reads exported symbols, one per line. Output of `nm` is accepted as
+    // well, only the last column of a line is used.
+    pub fn add_exported_symbols(&mut self, symbols: &str) {
+        for line in symbols.lines() {
+            if let Some(symbol) = line.split_whitespace().last() {
+                self.names.insert(symbol.trim_start_matches('_').to_string());
+            }
+        }
+    }
+
+    pub fn is_entry_point(&self, name: &str) -> bool {
+        self.names.contains(name) || self.patterns.iter().any(|pattern| pattern.is_match(name))
+    }
+}
+
+pub struct DeadCodeReport {
+    pub entry_points: usize,
+    pub reachable: usize,
+    // file -> unreachable functions defined in it, both sorted
+    pub unreachable: BTreeMap<String, Vec<String>>,
+}
+
+impl DeadCodeReport {
+    pub fn unreachable_count(&self) -> usize {
+        self.unreachable.values().map(|functions| functions.len()).sum()
+    }
+}
+
+impl fmt::Display for DeadCodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} entry points, {} reachable functions, {} unreachable functions",
+            self.entry_points,
+            self.reachable,
+            self.unreachable_count()
+        )?;
+        for (file, functions) in &self.unreachable {
+            writeln!(f, "\n{}", file)?;
+            for function in functions {
+                writeln!(f, "    {}", function)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Every function defined in `index` that cannot be reached from `roots`
+// through its calls.
+pub fn find_dead_code(index: &dyn CallIndex, roots: &HashSet<String>) -> DeadCodeReport {
+    let mut reachable: HashSet<&str> = HashSet::new();
+    let mut queue: VecDeque<&str> = roots.iter().map(|root| root.as_str()).collect();
+    while let Some(function) = queue.pop_front() {
+        if !reachable.insert(function) {
+            continue;
+        }
+        for callee in index.callees(function) {
+            if !reachable.contains(callee) {
+                queue.push_back(callee);
+            }
+        }
+    }
+
+    let mut unreachable: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut indexed: HashSet<&str> = HashSet::new();
+    for (file, function) in index.definitions() {
+        indexed.insert(function);
+        if !reachable.contains(function) {
+            unreachable.entry(file.to_string()).or_default().push(function.to_string());
+        }
+    }
+    for functions in unreachable.values_mut() {
+        functions.sort();
+        functions.dedup();
+    }
+
+    DeadCodeReport {
+        entry_points: roots.len(),
+        reachable: reachable.iter().filter(|function| indexed.contains(*function)).count(),
+        unreachable,
+    }
+}
+
+#[cfg(test)]
+mod dead_code_test;
@@ -0,0 +1,42 @@
+use super::*;
+use std::collections::HashMap;
+use crate::call_index::MapIndex;
+
+fn strings(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn test_find_dead_code_groups_by_file() {
+    let index_map = HashMap::from([
+        ("src/main.c".to_string(), strings(&["main", "init", "old_init"])),
+        ("src/util.c".to_string(), strings(&["log", "format", "legacy"])),
+        ("src/empty.c".to_string(), strings(&["handler"])),
+    ]);
+    let function_calls = HashMap::from([
+        ("main".to_string(), strings(&["init", "log"])),
+        ("log".to_string(), strings(&["format", "log"])),
+        ("old_init".to_string(), strings(&["legacy"])),
+    ]);
+    let roots = HashSet::from(["main".to_string(), "handler".to_string()]);
+
+    let report = find_dead_code(&MapIndex::new(index_map, function_calls), &roots);
+    assert_eq!(report.reachable, 5);
+    assert_eq!(report.unreachable_count(), 2);
+    assert_eq!(report.unreachable.keys().collect::<Vec<_>>(), vec!["src/main.c", "src/util.c"]);
+    assert_eq!(report.unreachable["src/main.c"], strings(&["old_init"]));
+    assert_eq!(report.unreachable["src/util.c"], strings(&["legacy"]));
+}
+
+#[test]
+fn test_entry_points() {
+    let mut entry_points = EntryPoints::default();
+    entry_points.patterns.push(Regex::new("^test_").unwrap());
+    entry_points.add_exported_symbols("0000000000001139 T _api_open\napi_close\n\n");
+
+    assert!(entry_points.is_entry_point("main"));
+    assert!(entry_points.is_entry_point("test_parser"));
+    assert!(entry_points.is_entry_point("api_open"));
+    assert!(entry_points.is_entry_point("api_close"));
+    assert!(!entry_points.is_entry_point("helper"));
+}
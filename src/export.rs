@@ -0,0 +1,89 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::graph::Graph;
+use crate::snapshot::Snapshot;
+
+// Turns a query result into something a client can render.
+pub trait GraphExporter {
+    fn content_type(&self) -> &'static str;
+    fn export(&self, graph: &Graph) -> anyhow::Result<String>;
+}
+
+pub struct DotExporter;
+
+impl GraphExporter for DotExporter {
+    fn content_type(&self) -> &'static str {
+        "text/vnd.graphviz"
+    }
+
+    fn export(&self, graph: &Graph) -> anyhow::Result<String> {
+        Ok(graph.graph_to_dot())
+    }
+}
+
+// Same format as a saved snapshot, so a result can be diffed later.
+pub struct JsonExporter;
+
+impl GraphExporter for JsonExporter {
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn export(&self, graph: &Graph) -> anyhow::Result<String> {
+        Ok(serde_json::to_string(&Snapshot::from_graph(graph))?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ExportFormat {
+    Dot,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn exporter(&self) -> Box<dyn GraphExporter> {
+        match self {
+            ExportFormat::Dot => Box::new(DotExporter),
+            ExportFormat::Json => Box::new(JsonExporter),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(ExportFormat::Dot),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(format!("unknown format '{}', expected dot or json", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for ExportFormat {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Dot => write!(f, "dot"),
+            ExportFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl From<ExportFormat> for String {
+    fn from(format: ExportFormat) -> String {
+        format.to_string()
+    }
+}
@@ -1,114 +1,306 @@
-use std::collections::HashSet;
-use petgraph::graph::NodeIndex;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use petgraph::dot::{Config, Dot};
+use petgraph::stable_graph::{EdgeReference, NodeIndex, StableGraph};
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use petgraph::Direction;
+use crate::searcher::CallSite;
 
+/// The functions found by a query and the calls between them.
+// Nodes are looked up by name through `index`. A StableGraph keeps all other
+// indices valid when a node or edge is removed.
 pub struct Graph {
-    pub pet_graph: petgraph::Graph<String, String>,
-    pub(crate) nodes: HashSet<Node>,
+    pet_graph: StableGraph<Node, Edge>,
+    index: HashMap<String, NodeIndex>,
 }
 
+#[derive(Debug)]
+pub enum GraphError {
+    MissingNode(String),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::MissingNode(name) => write!(f, "graph error: no node named {}", name),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// The calls from one function to another.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Edge {
+    pub call_sites: Vec<CallSite>,
+    /// part of a cycle found by `@cycles`
+    pub in_cycle: bool,
+    /// position of the edge in the paths of a path query, starting at 1
+    pub hops: Vec<usize>,
+    pub change: EdgeChange,
+}
+
+/// How an edge differs between two snapshots.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EdgeChange {
+    #[default]
+    Unchanged,
+    Added,
+    Removed,
+}
+
+impl Edge {
+    /// Number of places where the caller calls the callee.
+    pub fn call_count(&self) -> usize {
+        self.call_sites.len()
+    }
+}
+
+impl fmt::Display for Edge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if !self.hops.is_empty() {
+            let hops: Vec<String> = self.hops.iter().map(|hop| hop.to_string()).collect();
+            parts.push(format!("hop {}", hops.join(", ")));
+        }
+        if !self.call_sites.is_empty() {
+            parts.push(format!("called {}×", self.call_count()));
+        }
+        write!(f, "{}", parts.join(": "))
+    }
+}
+
+/// A function of the graph.
 #[derive(Hash, Eq, PartialEq, Debug, Clone)]
 pub struct Node {
     pub name: String,
+    /// number of calls to the function that the query added
     pub times_used: u32,
 }
 
+impl Default for Graph {
+    fn default() -> Self {
+        Graph::new()
+    }
+}
+
 impl Graph {
+    /// An empty graph.
+    pub fn new() -> Graph {
+        Graph {
+            pet_graph: StableGraph::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    /// (caller, callee) of every call.
     pub fn graph_to_tuple(&self) -> HashSet<(String, String)>{
         let mut result :HashSet<(String, String)> = HashSet::new();
-        for edge in self.pet_graph.raw_edges() {
-            let mut target = "".to_string();
-            if self.pet_graph.node_weight(edge.target()).is_some(){
-                target = self.pet_graph.node_weight(edge.target()).unwrap().to_owned();
-            }
-            result.insert((self.pet_graph.node_weight(edge.source()).unwrap().to_owned(), target));
+        for edge in (&self.pet_graph).edge_references() {
+            result.insert((self.pet_graph[edge.source()].name.clone(), self.pet_graph[edge.target()].name.clone()));
         }
         result
     }
 
-    pub fn add_node(&mut self, node_name: String, prio: u32) {
-        let mut node_exists = false;
-        for node in self.pet_graph.node_indices(){
-            if self.pet_graph.node_weight(node).unwrap().to_owned() == node_name {
-                node_exists = true;
-            }
-        }
-        if !node_exists {
-            self.pet_graph.add_node(node_name.clone());
-            let node = Node{name: node_name.clone(), times_used: prio};
-            self.nodes.insert(node);
+    fn node_index(&self, name: &str) -> Option<NodeIndex> {
+        self.index.get(name).copied()
+    }
+
+    /// The function called `name`, if it is in the graph.
+    pub fn node(&self, name: &str) -> Option<&Node> {
+        self.node_index(name).map(|index| &self.pet_graph[index])
+    }
+
+    /// Every function of the graph.
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.pet_graph.node_indices().map(move |index| &self.pet_graph[index])
+    }
+
+    /// (caller, callee, edge) of every call.
+    pub fn edges(&self) -> impl Iterator<Item = (&str, &str, &Edge)> {
+        (&self.pet_graph).edge_references().map(move |edge| {
+            (self.pet_graph[edge.source()].name.as_str(), self.pet_graph[edge.target()].name.as_str(), edge.weight())
+        })
+    }
+
+    /// The calls from `caller` to `callee`, if the graph has any.
+    pub fn edge(&self, caller: &str, callee: &str) -> Option<&Edge> {
+        let edge = self.pet_graph.find_edge(self.node_index(caller)?, self.node_index(callee)?)?;
+        Some(&self.pet_graph[edge])
+    }
+
+    pub(crate) fn edge_mut(&mut self, caller: &str, callee: &str) -> Option<&mut Edge> {
+        let edge = self.pet_graph.find_edge(self.node_index(caller)?, self.node_index(callee)?)?;
+        Some(&mut self.pet_graph[edge])
+    }
+
+    /// The functions of the graph that call `name`.
+    pub fn callers(&self, name: &str) -> impl Iterator<Item = &Node> {
+        self.neighbours(name, Direction::Incoming)
+    }
+
+    /// The functions of the graph that `name` calls.
+    pub fn callees(&self, name: &str) -> impl Iterator<Item = &Node> {
+        self.neighbours(name, Direction::Outgoing)
+    }
+
+    fn neighbours(&self, name: &str, direction: Direction) -> impl Iterator<Item = &Node> {
+        self.node_index(name)
+            .into_iter()
+            .flat_map(move |index| self.pet_graph.neighbors_directed(index, direction))
+            .map(move |index| &self.pet_graph[index])
+    }
+
+    /// Number of functions in the graph.
+    pub fn node_count(&self) -> usize {
+        self.pet_graph.node_count()
+    }
+
+    /// Number of caller and callee pairs in the graph.
+    pub fn edge_count(&self) -> usize {
+        self.pet_graph.edge_count()
+    }
+
+    pub(crate) fn add_node(&mut self, node_name: String, prio: u32) -> NodeIndex {
+        if let Some(index) = self.node_index(&node_name) {
+            return index;
         }
+        let index = self.pet_graph.add_node(Node { name: node_name.clone(), times_used: prio });
+        self.index.insert(node_name, index);
+        index
     }
 
-    pub fn add_edge(&mut self, start: String, end : String) -> bool {
-        let mut edge_exists = false;
-        let mut start_node: NodeIndex = NodeIndex::new(0);
-        let mut end_node: NodeIndex = NodeIndex::new(0);
-        for node in self.pet_graph.node_indices(){
-            if self.pet_graph.node_weight(node).is_some() {
-                let node_name = self.pet_graph.node_weight(node).unwrap().clone();
-                if node_name == start {
-                    start_node = node;
+    // Returns whether the end node has outgoing edges. Adding an edge that
+    // already exists only merges the call sites.
+    pub(crate) fn add_edge(&mut self, start: String, end : String, call_sites: Vec<CallSite>) -> Result<bool, GraphError> {
+        let start_node = self.node_index(&start).ok_or(GraphError::MissingNode(start))?;
+        let end_node = self.node_index(&end).ok_or(GraphError::MissingNode(end))?;
+
+        if let Some(edge) = self.pet_graph.find_edge(start_node, end_node) {
+            let weight = &mut self.pet_graph[edge];
+            for call_site in call_sites {
+                if !weight.call_sites.contains(&call_site) {
+                    weight.call_sites.push(call_site);
                 }
-                if node_name == end {
-                    end_node = node;
+            }
+        } else {
+            self.pet_graph.add_edge(start_node, end_node, Edge { call_sites, in_cycle: false, hops: Vec::new(), change: EdgeChange::Unchanged });
+            self.pet_graph[end_node].times_used += 1;
+        }
+        Ok(self.pet_graph.neighbors(end_node).next().is_some())
+    }
+
+    // Removes the node, every callee that is used at most once afterwards is
+    // removed as well. Returns the names of all removed nodes.
+    pub(crate) fn remove_node(&mut self, name: &str) -> Vec<String> {
+        let mut removed = Vec::new();
+        let mut pending = vec![name.to_string()];
+
+        while let Some(name) = pending.pop() {
+            let node_index = match self.index.remove(&name) {
+                Some(index) => index,
+                None => continue,
+            };
+            let to_nodes: HashSet<NodeIndex> = self.pet_graph
+                .neighbors(node_index)
+                .filter(|target| *target != node_index)
+                .collect();
+
+            self.pet_graph.remove_node(node_index);
+            removed.push(name);
+
+            for to_node in to_nodes {
+                let node = &mut self.pet_graph[to_node];
+                node.times_used = node.times_used.saturating_sub(1);
+                if node.times_used <= 1 {
+                    pending.push(node.name.clone());
                 }
             }
         }
-        if self.pet_graph.contains_edge(start_node, end_node) {
-            edge_exists = true;
+        removed
+    }
+
+    // Removes only the node itself, its callees keep their usage count.
+    pub(crate) fn remove_single_node(&mut self, name: &str) -> bool {
+        match self.index.remove(name) {
+            Some(index) => {
+                self.pet_graph.remove_node(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn mark_cycle_edge(&mut self, start: &str, end: &str) {
+        if let (Some(start), Some(end)) = (self.node_index(start), self.node_index(end)) {
+            if let Some(edge) = self.pet_graph.find_edge(start, end) {
+                self.pet_graph[edge].in_cycle = true;
+            }
         }
-        if !edge_exists {
-            self.pet_graph.add_edge(start_node, end_node, String::new());
-            for node in self.nodes.clone() {
-                if (node.name == end.clone()) {
-                    let new_prio = node.times_used + 1;
-                    let new_node = Node{name: node.name.clone(), times_used: new_prio};
-                    self.nodes.remove(&node.clone());
-                    self.nodes.insert(new_node);
+    }
+
+    pub(crate) fn mark_hop(&mut self, start: &str, end: &str, hop: usize) {
+        if let (Some(start), Some(end)) = (self.node_index(start), self.node_index(end)) {
+            if let Some(edge) = self.pet_graph.find_edge(start, end) {
+                let hops = &mut self.pet_graph[edge].hops;
+                if let Err(position) = hops.binary_search(&hop) {
+                    hops.insert(position, hop);
                 }
             }
         }
-        self.pet_graph.neighbors(end_node).next().is_some()
-    }
-
-    pub fn remove_node(&mut self, node: Node){
-        for node_index in self.pet_graph.node_indices() {
-            if self.pet_graph.node_weight(node_index).is_some() {
-                if self.pet_graph.node_weight(node_index).unwrap().to_owned() == node.name {
-                    let edges = self.pet_graph.raw_edges().to_owned();
-                    let mut to_nodes: HashSet<String> = HashSet::new();
-                    for edge in edges{
-                        if edge.source() == node_index && edge.target() != edge.source() {
-                            to_nodes.insert(self.pet_graph.node_weight(edge.target()).unwrap().to_owned());
-                        }
-                    }
-
-                    self.nodes.remove(&node);
-                    self.pet_graph.remove_node(node_index);
-
-                    for to_node_string in to_nodes {
-                        for to_node in self.nodes.clone() {
-                            if to_node.name == to_node_string {
-                                let new_prio = to_node.times_used - 1;
-                                let new_node = Node { name: to_node.name.clone(), times_used: new_prio };
-                                self.nodes.remove(&to_node.clone());
-                                self.nodes.insert(new_node.clone());
-
-                                if new_prio <= 1 {
-                                    self.remove_node(new_node);
-                                }
-                            }
-                        }
-                    }
+    }
+
+    pub(crate) fn mark_change(&mut self, start: &str, end: &str, change: EdgeChange) {
+        if let (Some(start), Some(end)) = (self.node_index(start), self.node_index(end)) {
+            if let Some(edge) = self.pet_graph.find_edge(start, end) {
+                self.pet_graph[edge].change = change;
+            }
+        }
+    }
+
+    // Self loops that are part of a detected cycle are kept.
+    pub(crate) fn remove_self_loop(&mut self, name: &str) {
+        if let Some(node) = self.node_index(name) {
+            if let Some(edge) = self.pet_graph.find_edge(node, node) {
+                if !self.pet_graph[edge].in_cycle {
+                    self.pet_graph.remove_edge(edge);
                 }
             }
         }
     }
 
-    pub fn graph_to_dot(&mut self) -> String {
-        format!("{:?}",  petgraph::dot::Dot::new(&self.pet_graph.clone()))
+    /// The graph in the Graphviz dot language.
+    pub fn graph_to_dot(&self) -> String {
+        let edge_attributes = |_, edge: EdgeReference<Edge>| edge_dot_attributes(edge.weight());
+        let node_attributes = |_, (_, node): (NodeIndex, &Node)| format!("label = {:?}", node.name);
+        format!("{:?}", Dot::with_attr_getters(&self.pet_graph, &[Config::EdgeNoLabel, Config::NodeNoLabel], &edge_attributes, &node_attributes))
     }
 }
+
+// Labels an edge with its number of calls and links it to the first call site,
+// all call sites are listed in the tooltip. Cycle edges are drawn in red, in a
+// snapshot diff added edges are green and removed edges red and dashed.
+fn edge_dot_attributes(edge: &Edge) -> String {
+    let highlight = match edge.change {
+        EdgeChange::Added => " color = \"green\" penwidth = 2",
+        EdgeChange::Removed => " color = \"red\" style = \"dashed\"",
+        EdgeChange::Unchanged if edge.in_cycle => " color = \"red\" penwidth = 2",
+        EdgeChange::Unchanged => "",
+    };
+    if edge.call_sites.is_empty() {
+        return format!("label = {:?}{}", edge.to_string(), highlight);
+    }
+    let first = &edge.call_sites[0];
+    let sites: Vec<String> = edge.call_sites.iter().map(|site| site.to_string()).collect();
+    format!(
+        "label = {:?} tooltip = {:?} URL = {:?}{}",
+        edge.to_string(),
+        sites.join("\n"),
+        format!("{}#L{}", first.file, first.line),
+        highlight
+    )
+}
+
 /*
 impl TryFrom<Graph> for tabbycat::Graph {
     type Error = anyhow::Error;
@@ -138,3 +330,6 @@ impl TryFrom<Graph> for tabbycat::Graph {
             .map_err(anyhow::Error::msg)
     }
 }*/
+
+#[cfg(test)]
+mod graph_test;
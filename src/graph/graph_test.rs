@@ -0,0 +1,77 @@
+use super::*;
+
+fn site(line: u32) -> CallSite {
+    CallSite { file: "main.c".to_string(), line, column: 5 }
+}
+
+#[test]
+fn test_add_edge_requires_both_nodes() {
+    let mut graph = Graph::new();
+    graph.add_node("main".to_string(), 1);
+
+    let result = graph.add_edge("main".to_string(), "missing".to_string(), vec![]);
+    assert!(matches!(result, Err(GraphError::MissingNode(name)) if name == "missing"));
+    assert_eq!(graph.edge_count(), 0);
+}
+
+#[test]
+fn test_add_edge_merges_call_sites() {
+    let mut graph = Graph::new();
+    graph.add_node("main".to_string(), 1);
+    graph.add_node("foo".to_string(), 1);
+
+    graph.add_edge("main".to_string(), "foo".to_string(), vec![site(1)]).unwrap();
+    graph.add_edge("main".to_string(), "foo".to_string(), vec![site(1), site(2)]).unwrap();
+
+    assert_eq!(graph.edge("main", "foo").unwrap().call_count(), 2);
+    assert_eq!(graph.node("foo").unwrap().times_used, 2);
+}
+
+#[test]
+fn test_remove_node_keeps_indices_valid() {
+    let mut graph = Graph::new();
+    for name in ["a", "b", "c", "d"] {
+        graph.add_node(name.to_string(), 1);
+    }
+    graph.add_edge("a".to_string(), "b".to_string(), vec![]).unwrap();
+    graph.add_edge("b".to_string(), "c".to_string(), vec![]).unwrap();
+    graph.add_edge("d".to_string(), "c".to_string(), vec![]).unwrap();
+
+    // c is still called by d after b is gone
+    assert_eq!(graph.remove_node("b"), vec!["b".to_string()]);
+    assert!(graph.node("b").is_none());
+    assert_eq!(graph.node("c").unwrap().times_used, 2);
+    assert_eq!(graph.graph_to_tuple(), HashSet::from([("d".to_string(), "c".to_string())]));
+
+    // removing d drops c to a single use, so c goes as well
+    assert_eq!(graph.remove_node("d"), vec!["d".to_string(), "c".to_string()]);
+    assert_eq!(graph.nodes().map(|n| n.name.clone()).collect::<Vec<_>>(), vec!["a".to_string()]);
+}
+
+#[test]
+fn test_graph_to_dot_labels_edges() {
+    let mut graph = Graph::new();
+    graph.add_node("main".to_string(), 1);
+    graph.add_node("foo".to_string(), 1);
+    graph.add_edge("main".to_string(), "foo".to_string(), vec![site(3), site(7)]).unwrap();
+
+    let dot = graph.graph_to_dot();
+    assert!(dot.contains("label = \"main\""));
+    assert!(dot.contains("label = \"called 2×\""));
+    assert!(dot.contains("URL = \"main.c#L3\""));
+}
+
+#[test]
+fn test_remove_self_loop_keeps_cycle_edges() {
+    let mut graph = Graph::new();
+    graph.add_node("fact".to_string(), 1);
+    graph.add_node("loop".to_string(), 1);
+    graph.add_edge("fact".to_string(), "fact".to_string(), vec![]).unwrap();
+    graph.add_edge("loop".to_string(), "loop".to_string(), vec![]).unwrap();
+    graph.mark_cycle_edge("fact", "fact");
+
+    graph.remove_self_loop("fact");
+    graph.remove_self_loop("loop");
+    assert_eq!(graph.graph_to_tuple(), HashSet::from([("fact".to_string(), "fact".to_string())]));
+    assert!(graph.graph_to_dot().contains("color = \"red\""));
+}
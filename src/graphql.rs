@@ -0,0 +1,251 @@
+use std::sync::Mutex;
+
+use juniper::{EmptyMutation, FieldResult, RootNode};
+use regex::Regex;
+
+use crate::analyzer::Analyzer;
+use crate::searcher::CallSite;
+
+const DEFAULT_PAGE_SIZE: i32 = 50;
+
+// Resolvers look up the index lazily, a client only pays for the parts of the
+// call graph it actually walks.
+pub struct GraphContext {
+    pub analyzer: Mutex<Analyzer>,
+}
+
+impl juniper::Context for GraphContext {}
+
+impl GraphContext {
+    pub fn new(analyzer: Analyzer) -> GraphContext {
+        GraphContext { analyzer: Mutex::new(analyzer) }
+    }
+
+    pub fn into_analyzer(self) -> Analyzer {
+        self.analyzer.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn analyzer(&self) -> std::sync::MutexGuard<'_, Analyzer> {
+        // a panic in another request leaves the index itself intact
+        self.analyzer.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+pub type Schema = RootNode<'static, Query, EmptyMutation<GraphContext>>;
+
+pub fn schema() -> Schema {
+    Schema::new(Query, EmptyMutation::new())
+}
+
+// Runs a GraphQL request and returns the response as JSON.
+pub fn execute(schema: &Schema, context: &GraphContext, request: &str) -> String {
+    let response = match juniper::execute(request, None, schema, &juniper::Variables::new(), context) {
+        Ok((data, errors)) if errors.is_empty() => serde_json::json!({ "data": data }),
+        Ok((data, errors)) => serde_json::json!({ "data": data, "errors": errors }),
+        Err(err) => serde_json::json!({ "errors": [{ "message": format!("{:?}", err) }] }),
+    };
+    response.to_string()
+}
+
+fn page(mut names: Vec<String>, first: i32, offset: i32) -> FunctionPage {
+    names.sort();
+    names.dedup();
+    let total_count = names.len() as i32;
+    let functions = names
+        .into_iter()
+        .skip(offset.max(0) as usize)
+        .take(first.max(0) as usize)
+        .map(|name| Function { name })
+        .collect();
+    FunctionPage { total_count, functions }
+}
+
+pub struct Function {
+    name: String,
+}
+
+#[derive(juniper::GraphQLObject)]
+pub struct Range {
+    pub start_line: i32,
+    pub end_line: i32,
+}
+
+pub struct FunctionPage {
+    total_count: i32,
+    functions: Vec<Function>,
+}
+
+#[juniper::object(Context = GraphContext)]
+impl FunctionPage {
+    fn total_count(&self) -> i32 {
+        self.total_count
+    }
+
+    fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+}
+
+#[juniper::object(Context = GraphContext)]
+impl Function {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn file(&self, context: &GraphContext) -> Option<String> {
+        context.analyzer().lang_server().function_location(&self.name).map(|(file, _, _)| file)
+    }
+
+    fn range(&self, context: &GraphContext) -> Option<Range> {
+        context
+            .analyzer()
+            .lang_server()
+            .function_location(&self.name)
+            .map(|(_, start, end)| Range { start_line: start as i32, end_line: end as i32 })
+    }
+
+    #[graphql(arguments(first(default = DEFAULT_PAGE_SIZE), offset(default = 0)))]
+    fn callers(&self, context: &GraphContext, first: i32, offset: i32) -> FunctionPage {
+        page(context.analyzer().lang_server().callers_of(&self.name), first, offset)
+    }
+
+    #[graphql(arguments(first(default = DEFAULT_PAGE_SIZE), offset(default = 0)))]
+    fn callees(&self, context: &GraphContext, first: i32, offset: i32) -> FunctionPage {
+        page(context.analyzer().lang_server().callees_of(&self.name), first, offset)
+    }
+}
+
+pub struct File {
+    path: String,
+}
+
+#[juniper::object(Context = GraphContext)]
+impl File {
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn functions(&self, context: &GraphContext) -> Vec<Function> {
+        let mut analyzer = context.analyzer();
+        let index = analyzer.lang_server().call_index();
+        index.file_functions(&self.path).map(|name| Function { name: name.to_string() }).collect()
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+#[graphql(name = "CallSite")]
+pub struct GraphQLCallSite {
+    pub file: String,
+    pub line: i32,
+    pub column: i32,
+}
+
+impl From<&CallSite> for GraphQLCallSite {
+    fn from(site: &CallSite) -> Self {
+        GraphQLCallSite { file: site.file.clone(), line: site.line as i32, column: site.column as i32 }
+    }
+}
+
+pub struct Call {
+    caller: String,
+    callee: String,
+    call_sites: Vec<GraphQLCallSite>,
+}
+
+#[juniper::object(Context = GraphContext)]
+impl Call {
+    fn caller(&self) -> Function {
+        Function { name: self.caller.clone() }
+    }
+
+    fn callee(&self) -> Function {
+        Function { name: self.callee.clone() }
+    }
+
+    fn call_sites(&self) -> &[GraphQLCallSite] {
+        &self.call_sites
+    }
+}
+
+pub struct QueryResult {
+    functions: Vec<String>,
+    calls: Vec<Call>,
+}
+
+#[juniper::object(Context = GraphContext)]
+impl QueryResult {
+    fn functions(&self) -> Vec<Function> {
+        self.functions.iter().map(|name| Function { name: name.clone() }).collect()
+    }
+
+    fn calls(&self) -> &[Call] {
+        &self.calls
+    }
+}
+
+pub struct Query;
+
+#[juniper::object(Context = GraphContext)]
+impl Query {
+    fn function(context: &GraphContext, name: String) -> Option<Function> {
+        let mut analyzer = context.analyzer();
+        let found = analyzer.lang_server().call_index().has_function(&name);
+        if found {
+            Some(Function { name })
+        } else {
+            None
+        }
+    }
+
+    #[graphql(arguments(filter(default = ".".to_string()), first(default = DEFAULT_PAGE_SIZE), offset(default = 0)))]
+    fn functions(context: &GraphContext, filter: String, first: i32, offset: i32) -> FieldResult<FunctionPage> {
+        let regex = Regex::new(&filter)?;
+        let mut analyzer = context.analyzer();
+        let index = analyzer.lang_server().call_index();
+        let names = index.definitions().map(|(_, name)| name).filter(|name| regex.is_match(name)).map(str::to_string).collect();
+        Ok(page(names, first, offset))
+    }
+
+    fn file(context: &GraphContext, path: String) -> Option<File> {
+        let found = context.analyzer().lang_server().call_index().has_file(&path);
+        if found {
+            Some(File { path })
+        } else {
+            None
+        }
+    }
+
+    #[graphql(arguments(filter(default = ".".to_string())))]
+    fn files(context: &GraphContext, filter: String) -> FieldResult<Vec<File>> {
+        let regex = Regex::new(&filter)?;
+        let mut analyzer = context.analyzer();
+        let index = analyzer.lang_server().call_index();
+        let mut paths: Vec<String> = index.files().filter(|path| regex.is_match(path)).map(str::to_string).collect();
+        paths.sort();
+        Ok(paths.into_iter().map(|path| File { path }).collect())
+    }
+
+    // Runs a query of the code-analysis DSL.
+    fn query(context: &GraphContext, text: String) -> FieldResult<QueryResult> {
+        let mut analyzer = context.analyzer();
+        analyzer.clear();
+        analyzer.try_parse(&text)?;
+
+        let graph = analyzer.graph();
+        let mut functions: Vec<String> = graph.nodes().map(|node| node.name.clone()).collect();
+        functions.sort();
+        let mut calls: Vec<Call> = graph
+            .edges()
+            .map(|(caller, callee, edge)| Call {
+                caller: caller.to_string(),
+                callee: callee.to_string(),
+                call_sites: edge.call_sites.iter().map(GraphQLCallSite::from).collect(),
+            })
+            .collect();
+        calls.sort_by(|a, b| (&a.caller, &a.callee).cmp(&(&b.caller, &b.callee)));
+        Ok(QueryResult { functions, calls })
+    }
+}
+
+#[cfg(test)]
+mod graphql_test;
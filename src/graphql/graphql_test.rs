@@ -0,0 +1,61 @@
+use super::*;
+use crate::test_support::MockLSPServer;
+
+fn run(request: &str) -> serde_json::Value {
+    let context = GraphContext::new(Analyzer::new(MockLSPServer::boxed()));
+    serde_json::from_str(&execute(&schema(), &context, request)).unwrap()
+}
+
+#[test]
+fn test_function_fields() {
+    let result = run(r#"{ function(name: "parse") { name file range { startLine endLine } callers { totalCount functions { name } } } }"#);
+    assert_eq!(
+        result,
+        serde_json::json!({ "data": { "function": {
+            "name": "parse",
+            "file": "parse.c",
+            "range": { "startLine": 2, "endLine": 4 },
+            "callers": { "totalCount": 1, "functions": [{ "name": "main" }] },
+        } } })
+    );
+}
+
+#[test]
+fn test_paging() {
+    let result = run(r#"{ functions(first: 1, offset: 1) { totalCount functions { name } } }"#);
+    assert_eq!(result["data"]["functions"], serde_json::json!({ "totalCount": 4, "functions": [{ "name": "parse" }] }));
+}
+
+#[test]
+fn test_file_functions() {
+    let result = run(r#"{ files(filter: "main") { path functions { name callees { totalCount } } } }"#);
+    assert_eq!(
+        result["data"]["files"],
+        serde_json::json!([{ "path": "main.c", "functions": [
+            { "name": "main", "callees": { "totalCount": 2 } },
+            { "name": "print", "callees": { "totalCount": 0 } },
+        ] }])
+    );
+}
+
+#[test]
+fn test_query() {
+    let result = run(r#"{ query(text: "{@parse}") { functions { name } calls { caller { name } callee { name } } } }"#);
+    assert_eq!(
+        result["data"]["query"]["calls"],
+        serde_json::json!([{ "caller": { "name": "main" }, "callee": { "name": "parse" } }])
+    );
+}
+
+#[test]
+fn test_query_syntax_error() {
+    let result = run(r#"{ query(text: "{@parse") { functions { name } } }"#);
+    assert!(result["errors"].is_array());
+    assert!(result["data"].is_null());
+}
+
+#[test]
+fn test_unknown_function() {
+    let result = run(r#"{ function(name: "missing") { name } }"#);
+    assert_eq!(result["data"]["function"], serde_json::Value::Null);
+}
@@ -0,0 +1,172 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
+use std::process::Command;
+
+use anyhow::{bail, Context};
+use serde::Serialize;
+
+use crate::call_index::CallIndex;
+use crate::graph::Graph;
+use crate::snapshot::Snapshot;
+
+// Lines of a file that were added, changed or next to a deletion, counted
+// in the new version of the file and starting at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub file: String,
+    pub lines: Vec<(u32, u32)>,
+}
+
+impl FileChange {
+    pub fn touches(&self, start: u32, end: u32) -> bool {
+        self.lines.iter().any(|(first, last)| *first <= end && start <= *last)
+    }
+}
+
+// Reads the changed lines of every file from a unified diff. Deleted files
+// are skipped because their functions are not in the index anymore.
+pub fn parse_unified_diff(diff: &str) -> Vec<FileChange> {
+    let mut changes: Vec<FileChange> = Vec::new();
+    let mut current: Option<FileChange> = None;
+    let mut new_line = 0u32;
+
+    let add_line = |change: &mut Option<FileChange>, line: u32| {
+        if let Some(change) = change {
+            match change.lines.last_mut() {
+                Some((_, last)) if *last + 1 >= line => *last = (*last).max(line),
+                _ => change.lines.push((line, line)),
+            }
+        }
+    };
+
+    // lines of the current hunk that are still to come, in the old and the new file
+    let mut remaining = (0u32, 0u32);
+    let mut after_old_header = false;
+
+    for line in diff.lines() {
+        if remaining == (0, 0) {
+            // headers only appear between hunks, inside one `--- ` and `+++ `
+            // are a removed `-- ` and an added `++ ` line
+            if let Some(path) = line.strip_prefix("+++ ").filter(|_| after_old_header) {
+                changes.extend(current.take());
+                let path = path.split('\t').next().unwrap_or("").trim();
+                if path != "/dev/null" {
+                    let file = path.strip_prefix("b/").unwrap_or(path).to_string();
+                    current = Some(FileChange { file, lines: Vec::new() });
+                }
+            } else if let Some(hunk) = line.strip_prefix("@@ ") {
+                // @@ -old_start[,old_count] +new_start[,new_count] @@
+                let range = |sign: char| -> (u32, u32) {
+                    let mut numbers = hunk
+                        .split_whitespace()
+                        .find_map(|part| part.strip_prefix(sign))
+                        .unwrap_or("")
+                        .split(',')
+                        .map(|number| number.parse().ok());
+                    let start = numbers.next().flatten().unwrap_or(0);
+                    (start, numbers.next().flatten().unwrap_or(1))
+                };
+                new_line = range('+').0;
+                remaining = (range('-').1, range('+').1);
+            }
+            after_old_header = line.starts_with("--- ");
+        } else if line.starts_with('+') {
+            add_line(&mut current, new_line.max(1));
+            new_line += 1;
+            remaining.1 = remaining.1.saturating_sub(1);
+        } else if line.starts_with('-') {
+            // a deletion changes the function around the following line
+            add_line(&mut current, new_line.max(1));
+            remaining.0 = remaining.0.saturating_sub(1);
+        } else if line.starts_with(' ') || line.is_empty() {
+            new_line += 1;
+            remaining = (remaining.0.saturating_sub(1), remaining.1.saturating_sub(1));
+        }
+    }
+    changes.extend(current);
+    changes.retain(|change| !change.lines.is_empty());
+    changes
+}
+
+// `git diff <revision>` of the working tree, with paths relative to the
+// project directory.
+pub fn git_diff(project_path: &str, revision: &str) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .args(["diff", "--relative", "--unified=0", "--no-color", revision])
+        .current_dir(project_path)
+        .output()
+        .context("could not run git")?;
+    if !output.status.success() {
+        bail!("git diff {} failed: {}", revision, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[derive(Serialize)]
+pub struct ImpactReport {
+    pub changed: Vec<String>,
+    // affected function -> number of calls between it and a changed function
+    pub affected: BTreeMap<String, usize>,
+    // caller -> callee edges between affected functions, the graph has them
+    #[serde(skip)]
+    pub edges: Vec<(String, String)>,
+}
+
+impl ImpactReport {
+    // The report with the graph of the affected functions in the snapshot
+    // format, for tools that pick the tests to run.
+    pub fn to_json(&self, graph: &Graph) -> anyhow::Result<String> {
+        #[derive(Serialize)]
+        struct Output<'a> {
+            #[serde(flatten)]
+            report: &'a ImpactReport,
+            graph: Snapshot,
+        }
+        Ok(serde_json::to_string(&Output { report: self, graph: Snapshot::from_graph(graph) })?)
+    }
+}
+
+// Every transitive caller of the changed functions, up to `max_depth` calls
+// away when a depth is given.
+pub fn transitive_callers(
+    changed: &HashSet<String>,
+    index: &dyn CallIndex,
+    max_depth: Option<usize>,
+) -> ImpactReport {
+    let mut affected: BTreeMap<String, usize> = changed.iter().map(|name| (name.clone(), 0)).collect();
+    let mut edges = Vec::new();
+    let mut queue: VecDeque<(&str, usize)> = changed.iter().map(|name| (name.as_str(), 0)).collect();
+    while let Some((function, depth)) = queue.pop_front() {
+        if max_depth.is_some_and(|max| depth >= max) {
+            continue;
+        }
+        for caller in index.callers(function).filter(|caller| *caller != function) {
+            edges.push((caller.to_string(), function.to_string()));
+            if !affected.contains_key(caller) {
+                affected.insert(caller.to_string(), depth + 1);
+                queue.push_back((caller, depth + 1));
+            }
+        }
+    }
+    edges.sort();
+    edges.dedup();
+
+    let mut changed: Vec<String> = changed.iter().cloned().collect();
+    changed.sort();
+    ImpactReport { changed, affected, edges }
+}
+
+impl fmt::Display for ImpactReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} changed functions, {} affected functions", self.changed.len(), self.affected.len())?;
+        let mut by_depth: Vec<(&String, &usize)> = self.affected.iter().collect();
+        by_depth.sort_by_key(|(name, depth)| (**depth, *name));
+        for (name, depth) in by_depth {
+            writeln!(f, "{}\t{}", depth, name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod impact_test;
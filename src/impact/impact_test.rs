@@ -0,0 +1,87 @@
+use super::*;
+use std::collections::HashMap;
+use crate::call_index::MapIndex;
+
+const DIFF: &str = "diff --git a/src/parse.c b/src/parse.c
+index 3b18e51..a9c2f4e 100644
+--- a/src/parse.c
++++ b/src/parse.c
+@@ -10,3 +10,4 @@ int parse(const char *s)
+ {
+-    return 0;
++    int n = strlen(s);
++    return n;
+ }
+@@ -40,2 +41,0 @@ static void unused(void)
+-    log(\"a\");
+-    log(\"b\");
+diff --git a/src/old.c b/src/old.c
+deleted file mode 100644
+--- a/src/old.c
++++ /dev/null
+@@ -1,2 +0,0 @@
+-int old(void)
+-{ return 1; }
+diff --git a/include/new.h b/include/new.h
+new file mode 100644
+--- /dev/null
++++ b/include/new.h
+@@ -0,0 +1,2 @@
++#pragma once
++int api(void);
+";
+
+#[test]
+fn test_parse_unified_diff() {
+    let changes = parse_unified_diff(DIFF);
+    assert_eq!(changes, vec![
+        FileChange { file: "src/parse.c".to_string(), lines: vec![(11, 12), (41, 41)] },
+        FileChange { file: "include/new.h".to_string(), lines: vec![(1, 2)] },
+    ]);
+    assert!(changes[0].touches(5, 11));
+    assert!(changes[0].touches(41, 50));
+    assert!(!changes[0].touches(13, 40));
+}
+
+#[test]
+fn test_transitive_callers() {
+    let strings = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+    let function_calls = HashMap::from([
+        ("main".to_string(), strings(&["handle", "init"])),
+        ("handle".to_string(), strings(&["parse", "handle"])),
+        ("test_parse".to_string(), strings(&["parse"])),
+        ("init".to_string(), strings(&["config"])),
+    ]);
+    let index = MapIndex::from_calls(function_calls);
+    let changed = HashSet::from(["parse".to_string()]);
+
+    let report = transitive_callers(&changed, &index, None);
+    assert_eq!(report.changed, vec!["parse".to_string()]);
+    assert_eq!(report.affected.len(), 4);
+    assert_eq!(report.affected["main"], 2);
+    assert_eq!(report.affected["test_parse"], 1);
+    assert!(report.edges.contains(&("main".to_string(), "handle".to_string())));
+    assert!(!report.affected.contains_key("init"));
+
+    let report = transitive_callers(&changed, &index, Some(1));
+    assert_eq!(report.affected.keys().collect::<Vec<_>>(), vec!["handle", "parse", "test_parse"]);
+    assert!(report.to_string().starts_with("1 changed functions, 3 affected functions\n0\tparse\n1\thandle"));
+
+    let mut graph = Graph::new();
+    for name in report.affected.keys() {
+        graph.add_node(name.clone(), 1);
+    }
+    graph.add_edge("handle".to_string(), "parse".to_string(), Vec::new()).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&report.to_json(&graph).unwrap()).unwrap();
+    assert_eq!(json["changed"], serde_json::json!(["parse"]));
+    assert_eq!(json["affected"]["test_parse"], 1);
+    assert!(json.get("edges").is_none());
+    assert!(json["graph"].is_object());
+}
+
+#[test]
+fn test_parse_unified_diff_content_that_looks_like_a_header() {
+    let diff = "--- a/notes.c\n+++ b/notes.c\n@@ -1,2 +1,3 @@\n--- removed\n+++ added\n+++ b/other.c\n x\n";
+    let changes = parse_unified_diff(diff);
+    assert_eq!(changes, vec![FileChange { file: "notes.c".to_string(), lines: vec![(1, 2)] }]);
+}
@@ -0,0 +1,224 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::analyzer::{Analyzer, FilterName};
+use crate::binary_index::BinaryIndex;
+use crate::cache::IndexProfile;
+use crate::call_index::CallIndex;
+use crate::config::SourceFilter;
+use crate::indexer::FunctionCalls;
+use crate::searcher::{self, ClangdServer, FunctionNode, LSPServer, Links};
+
+/// How [`Index::build`] runs clangd.
+#[derive(Debug, Clone)]
+pub struct IndexOptions {
+    pub clangd_path: String,
+    /// passed to clangd after the default arguments
+    pub clangd_args: Vec<String>,
+    /// the files of the project that are indexed
+    pub sources: SourceFilter,
+    /// name of the build configuration, every profile has its own cache
+    pub profile: String,
+    /// directory of `compile_commands.json`, the project directory by default
+    pub compile_commands_dir: Option<String>,
+    /// where indexes are cached, `$XDG_CACHE_HOME/code-analysis` by default
+    pub cache_dir: Option<String>,
+    /// number of clangd processes indexing in parallel
+    pub jobs: usize,
+    /// print how long indexing took to stderr
+    pub benchmark: bool,
+}
+
+impl Default for IndexOptions {
+    fn default() -> Self {
+        IndexOptions {
+            clangd_path: "/usr/bin/clangd".to_string(),
+            clangd_args: Vec::new(),
+            sources: SourceFilter::default(),
+            profile: "default".to_string(),
+            compile_commands_dir: None,
+            cache_dir: None,
+            jobs: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            benchmark: false,
+        }
+    }
+}
+
+impl IndexOptions {
+    /// Where the index of `project_path` is cached with these options.
+    pub(crate) fn profile(&self, project_path: &str) -> IndexProfile {
+        IndexProfile::new(
+            project_path,
+            &self.profile,
+            self.compile_commands_dir.clone(),
+            self.cache_dir.clone(),
+            &self.sources,
+            &self.clangd_args,
+        )
+    }
+}
+
+/// Number of files, functions and calls in an index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct IndexSummary {
+    pub files: usize,
+    pub functions: usize,
+    /// distinct caller and callee pairs
+    pub calls: usize,
+}
+
+impl fmt::Display for IndexSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} files, {} functions, {} calls", self.files, self.functions, self.calls)
+    }
+}
+
+/// The functions of a project, the files they are defined in and the calls
+/// between them.
+pub struct Index {
+    server: Box<dyn LSPServer>,
+}
+
+impl Index {
+    /// Indexes a project with clangd. A cached index is reused for the files
+    /// that did not change since. The clangd process stays alive for queries.
+    ///
+    /// Fails when clangd cannot be started or the index cannot be written.
+    pub fn build(project_path: &str, options: &IndexOptions) -> anyhow::Result<Index> {
+        let server = ClangdServer::new(project_path.to_string(), options, Utc::now().time())?;
+        Ok(Index { server: Box::new(server) })
+    }
+
+    /// Opens an index written by [`Index::save`] or by a previous run, without
+    /// starting clangd. File names in the index are relative to `project_path`.
+    pub fn load<P: AsRef<Path>>(path: P, project_path: &str) -> anyhow::Result<Index> {
+        let path = path.as_ref().to_path_buf();
+        let index = BinaryIndex::open(&path)?;
+        let server = IndexServer { project_path: project_path.to_string(), path, index };
+        Ok(Index { server: Box::new(server) })
+    }
+
+    /// Writes the index to `path` so it can be loaded without clangd.
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<()> {
+        let index_map = self.files();
+        let mut function_ranges = HashMap::new();
+        for (file, functions) in &index_map {
+            let ranges: HashMap<String, (u32, u32)> = self.server.function_ranges(file)
+                .into_iter()
+                .map(|(name, start, end)| (name, (start, end)))
+                .collect();
+            let ranges = functions.iter().map(|name| ranges.get(name).copied().unwrap_or((0, 0))).collect();
+            function_ranges.insert(file.clone(), ranges);
+        }
+
+        let names: HashSet<String> = self.server.call_index().functions().map(str::to_string).collect();
+        let mut function_index: HashMap<String, FunctionCalls> = HashMap::new();
+        for ((caller, callee), call_sites) in self.server.find_link(names.clone(), names) {
+            function_index.entry(caller).or_default().push((callee, call_sites));
+        }
+        // without modification times a cache built from this index reindexes
+        // every file once
+        BinaryIndex::write(path, &index_map, &function_ranges, &function_index, &HashMap::new())
+    }
+
+    /// Every indexed function, sorted.
+    pub fn functions(&mut self) -> Vec<String> {
+        let mut functions: Vec<String> = self.server.call_index().definitions().map(|(_, function)| function.to_string()).collect();
+        functions.sort();
+        functions.dedup();
+        functions
+    }
+
+    /// The indexed files with the functions defined in them.
+    pub fn files(&mut self) -> HashMap<String, Vec<String>> {
+        let index = self.server.call_index();
+        index.files().map(|file| (file.to_string(), index.file_functions(file).map(str::to_string).collect())).collect()
+    }
+
+    pub fn summary(&mut self) -> IndexSummary {
+        let functions = self.functions().len();
+        let index = self.server.call_index();
+        let calls: HashSet<(&str, &str)> = index.functions()
+            .flat_map(|caller| index.callees(caller).map(move |callee| (caller, callee)))
+            .collect();
+        IndexSummary { files: index.files().count(), functions, calls: calls.len() }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn server(&mut self) -> &mut dyn LSPServer {
+        &mut *self.server
+    }
+
+    /// Stops clangd, if the index was built with it.
+    pub fn close(mut self) {
+        self.server.close();
+    }
+
+    /// An analyzer that runs queries against this index.
+    pub fn into_analyzer(self) -> Analyzer {
+        Analyzer::new(self.server)
+    }
+}
+
+// Answers queries from a saved index alone.
+struct IndexServer {
+    project_path: String,
+    path: PathBuf,
+    index: BinaryIndex,
+}
+
+impl LSPServer for IndexServer {
+    fn find_func_name(&mut self, filter: Vec<HashMap<FilterName, Regex>>) -> HashSet<FunctionNode> {
+        searcher::find_functions(&self.index, filter)
+    }
+
+    fn find_link(&mut self, parent_name: HashSet<String>, child_name: HashSet<String>) -> Links {
+        searcher::find_index_links(&self.index, parent_name, child_name)
+    }
+
+    fn call_index(&mut self) -> Box<dyn CallIndex + '_> {
+        Box::new(&self.index)
+    }
+
+    fn address_taken_functions(&mut self) -> HashSet<String> {
+        searcher::address_taken(&self.project_path, &self.index)
+    }
+
+    fn external_calls(&mut self, pattern: &Regex) -> Links {
+        searcher::external_calls(&self.project_path, &self.index, pattern)
+    }
+
+    fn function_ranges(&mut self, file: &str) -> Vec<(String, u32, u32)> {
+        self.index.function_range_names(file)
+    }
+
+    fn close(&mut self) {}
+
+    // picks up the index once `index` refreshed it
+    fn reindex(&mut self, _changed: &[String]) {
+        match BinaryIndex::open(&self.path) {
+            Ok(index) => self.index = index,
+            Err(err) => log::error!("could not reload {}: {:#}", self.path.display(), err),
+        }
+    }
+
+    fn callers_of(&mut self, name: &str) -> Vec<String> {
+        self.index.caller_names(name)
+    }
+
+    fn callees_of(&mut self, name: &str) -> Vec<String> {
+        self.index.callee_names(name)
+    }
+
+    fn function_location(&mut self, name: &str) -> Option<(String, u32, u32)> {
+        self.index.function_location(name)
+    }
+}
+
+#[cfg(test)]
+mod index_test;
@@ -0,0 +1,112 @@
+use super::*;
+use crate::query::Query;
+use crate::searcher::CallSite;
+use std::path::PathBuf;
+
+fn index_path(name: &str) -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("code_analysis_index_{}_{}", name, std::process::id()))
+        .join("index.bin")
+}
+
+fn write_test_index(name: &str) -> PathBuf {
+    let index_map = HashMap::from([
+        ("src/main.c".to_string(), vec!["main".to_string(), "helper".to_string()]),
+        ("src/util.c".to_string(), vec!["util".to_string()]),
+    ]);
+    let site = |file: &str, line: u32, column: u32| CallSite { file: file.to_string(), line, column };
+    let function_index = HashMap::from([
+        ("main".to_string(), vec![
+            ("helper".to_string(), vec![site("src/main.c", 3, 5)]),
+            ("util".to_string(), vec![site("src/main.c", 4, 5)]),
+        ]),
+        ("helper".to_string(), vec![("util".to_string(), vec![site("src/main.c", 12, 5)])]),
+    ]);
+    let function_ranges = HashMap::from([
+        ("src/main.c".to_string(), vec![(1, 10), (11, 14)]),
+        ("src/util.c".to_string(), vec![(3, 8)]),
+    ]);
+    let path = index_path(name);
+    BinaryIndex::write(&path, &index_map, &function_ranges, &function_index, &HashMap::new()).unwrap();
+    path
+}
+
+#[test]
+fn test_index_load_and_run() {
+    let path = write_test_index("load");
+    let mut index = Index::load(&path, "/nonexistent").unwrap();
+    assert_eq!(index.functions(), vec!["helper".to_string(), "main".to_string(), "util".to_string()]);
+    assert_eq!(index.files()["src/util.c"], vec!["util".to_string()]);
+    assert_eq!(index.summary(), IndexSummary { files: 2, functions: 3, calls: 3 });
+    assert_eq!(index.summary().to_string(), "2 files, 3 functions, 3 calls");
+    assert_eq!(index.server().function_location("helper"), Some(("src/main.c".to_string(), 11, 14)));
+
+    let mut analyzer = index.into_analyzer();
+    let graph = analyzer.run(&Query::parse("{@util}").unwrap()).unwrap();
+    let mut edges: Vec<(String, String)> = graph.edges().map(|(a, b, _)| (a.to_string(), b.to_string())).collect();
+    edges.sort();
+    assert_eq!(edges, vec![
+        ("helper".to_string(), "util".to_string()),
+        ("main".to_string(), "util".to_string()),
+    ]);
+    assert!(analyzer.graph().nodes().next().is_none());
+}
+
+#[test]
+fn test_index_save_roundtrip() {
+    let path = write_test_index("save");
+    let mut index = Index::load(&path, "/nonexistent").unwrap();
+    let saved = index_path("saved");
+    index.save(&saved).unwrap();
+
+    let mut reloaded = Index::load(&saved, "/nonexistent").unwrap();
+    assert_eq!(reloaded.functions(), index.functions());
+    assert_eq!(reloaded.server().callers_of("util").len(), 2);
+    assert_eq!(reloaded.server().function_location("util"), Some(("src/util.c".to_string(), 3, 8)));
+}
+
+#[test]
+fn test_query_parse_rejects_invalid_queries() {
+    assert!(Query::parse("{@main}").is_ok());
+    let err = Query::parse("{@path(depth=x)}").unwrap_err().to_string();
+    assert!(err.starts_with("invalid query: "), "{}", err);
+    assert!("{@main".parse::<Query>().is_err());
+}
+
+#[test]
+fn test_run_query_with_parameters() {
+    let path = write_test_index("parameters");
+    let mut analyzer = Index::load(&path, "/nonexistent").unwrap().into_analyzer();
+    let query = Query::parse("{@$target}").unwrap();
+    assert_eq!(query.parameter_names(), vec!["target".to_string()]);
+
+    let err = analyzer.run(&query).err().unwrap().to_string();
+    assert!(err.contains("parameter $target is not bound"), "{}", err);
+    assert!(query.check_parameters().is_err());
+
+    let query = query.bind("target", "helper");
+    query.check_parameters().unwrap();
+    let graph = analyzer.run(&query).unwrap();
+    let edges: Vec<(&str, &str)> = graph.edges().map(|(a, b, _)| (a, b)).collect();
+    assert_eq!(edges, vec![("main", "helper")]);
+    assert!(analyzer.parameters().is_empty());
+    assert!(analyzer.try_parse("{@$target}").is_err());
+
+    let query = Query::parse("@filter(function=\"$caller\"){@util}").unwrap().bind("caller", "^h");
+    let graph = analyzer.run(&query).unwrap();
+    let edges: Vec<(&str, &str)> = graph.edges().map(|(a, b, _)| (a, b)).collect();
+    assert_eq!(edges, vec![("helper", "util")]);
+}
+
+#[test]
+fn test_loaded_index_reloads_on_reindex() {
+    let path = write_test_index("reload");
+    let mut index = Index::load(&path, "/nonexistent").unwrap();
+    assert_eq!(index.functions().len(), 3);
+
+    let index_map = HashMap::from([("src/util.c".to_string(), vec!["util".to_string()])]);
+    let function_ranges = HashMap::from([("src/util.c".to_string(), vec![(3, 8)])]);
+    BinaryIndex::write(&path, &index_map, &function_ranges, &HashMap::new(), &HashMap::new()).unwrap();
+    index.server().reindex(&[]);
+    assert_eq!(index.functions(), vec!["util".to_string()]);
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use chrono::{NaiveTime, Utc};
+use log::{log, Level};
+use lsp_types::{DocumentSymbolResponse, Range, SymbolKind};
+
+use crate::call_scanner::CallScanner;
+use crate::lang_server;
+use crate::lang_server::LanguageServer;
+use crate::searcher::CallSite;
+
+// The language server is restarted after this many documents, clangd tends to
+// stop answering otherwise.
+const RESTART_INTERVAL: usize = 5;
+
+// called functions of one function, with the places where they are called
+pub type FunctionCalls = Vec<(String, Vec<CallSite>)>;
+
+#[derive(Debug, Clone)]
+pub struct IndexedDocument {
+    pub file: String,
+    pub functions: Vec<String>,
+    pub ranges: Vec<Range>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexWorker {
+    pub clangd_path: String,
+    pub clangd_args: Vec<String>,
+    pub project_path: String,
+    pub compile_commands_dir: String,
+    pub benchmark: (NaiveTime, bool),
+}
+
+impl IndexWorker {
+    fn launch(&self) -> Box<dyn LanguageServer> {
+        let mut lang_server = lang_server::LanguageServerLauncher::new()
+            .server(self.clangd_path.to_owned())
+            .project(self.project_path.to_owned())
+            .compile_commands_dir(self.compile_commands_dir.to_owned())
+            .args(self.clangd_args.clone())
+            .launch()
+            .expect("Failed to spawn clangd");
+        let init_res = lang_server.initialize();
+        if init_res.is_err() {
+            log!(Level::Error, "LSP server didn't initialize: {:?}", init_res.err());
+        }
+        lang_server
+    }
+
+    fn run(&self, files: Vec<String>, progress: &AtomicUsize, total: usize) -> Vec<IndexedDocument> {
+        let mut lang_server = self.launch();
+        let mut documents = Vec::with_capacity(files.len());
+
+        for (i, file) in files.into_iter().enumerate() {
+            if i > 0 && i.is_multiple_of(RESTART_INTERVAL) {
+                let exit_res = lang_server.exit();
+                if exit_res.is_err() {
+                    log!(Level::Error, "{:?}", exit_res.err());
+                }
+                lang_server = self.launch();
+            }
+
+            let (functions, ranges) = self.document_functions(&mut lang_server, &file);
+            documents.push(IndexedDocument { file, functions, ranges });
+
+            let done = progress.fetch_add(1, Ordering::SeqCst) + 1;
+            if done.is_multiple_of(RESTART_INTERVAL) {
+                eprintln!("indexing project, please wait ({}/{})", done, total);
+                if self.benchmark.1 {
+                    let diff = Utc::now().time() - self.benchmark.0;
+                    eprintln!("time till now: {:?}", diff.num_milliseconds());
+                }
+            }
+        }
+
+        let _ = lang_server.shutdown();
+        let _ = lang_server.exit();
+        documents
+    }
+
+    fn document_functions(&self, lang_server: &mut Box<dyn LanguageServer>, file: &str) -> (Vec<String>, Vec<Range>) {
+        let mut functions: Vec<String> = vec![];
+        let mut ranges: Vec<Range> = vec![];
+
+        let start = Utc::now().time();
+        let document = match lang_server.document_open(file) {
+            Ok(document) => document,
+            Err(err) => {
+                log!(Level::Warn, "unable to open {}: {:?}", file, err);
+                return (functions, ranges);
+            }
+        };
+        if self.benchmark.1 {
+            let diff = Utc::now().time() - start;
+            eprintln!("lsp: document_open: {:?}ns", diff.num_nanoseconds().unwrap());
+        }
+
+        let start = Utc::now().time();
+        let doc_symbol = lang_server.document_symbol(&document);
+        if self.benchmark.1 {
+            let diff = Utc::now().time() - start;
+            eprintln!("lsp: document_symbol: {:?}", diff.num_milliseconds());
+        }
+
+        match doc_symbol {
+            Ok(Some(DocumentSymbolResponse::Flat(_))) => {
+                log!(Level::Warn, "unsupported symbols found");
+            }
+            Ok(Some(DocumentSymbolResponse::Nested(doc_symbols))) => {
+                for symbol in doc_symbols {
+                    if symbol.kind == SymbolKind::FUNCTION {
+                        functions.push(symbol.name.trim_start_matches('_').to_string());
+                        ranges.push(symbol.range);
+                    }
+                }
+            }
+            Ok(None) => {
+                log!(Level::Warn, "no symbols found");
+            }
+            Err(err) => {
+                log!(Level::Warn, "no symbols for {}: {:?}", file, err);
+            }
+        }
+        (functions, ranges)
+    }
+}
+
+// Splits the files between `jobs` language server instances. The result is
+// sorted by file name, so it doesn't depend on how the work was scheduled.
+pub fn index_documents(worker: &IndexWorker, files: &[String], jobs: usize) -> Vec<IndexedDocument> {
+    let jobs = jobs.max(1).min(files.len().max(1));
+    let progress = AtomicUsize::new(0);
+
+    let mut batches: Vec<Vec<String>> = vec![Vec::new(); jobs];
+    for (i, file) in files.iter().enumerate() {
+        batches[i % jobs].push(file.clone());
+    }
+
+    let mut documents: Vec<IndexedDocument> = thread::scope(|scope| {
+        let handles: Vec<_> = batches
+            .into_iter()
+            .map(|batch| {
+                let progress = &progress;
+                scope.spawn(move || worker.run(batch, progress, files.len()))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("indexing worker panicked"))
+            .collect()
+    });
+    documents.sort_by(|a, b| a.file.cmp(&b.file));
+    documents
+}
+
+// Second indexing phase: finds the calls inside every function body. Documents
+// are split into contiguous chunks and the chunks are merged in order.
+pub fn index_function_calls(project_path: &str, documents: &[IndexedDocument], jobs: usize) -> HashMap<String, FunctionCalls> {
+    let names: Vec<String> = documents.iter().flat_map(|d| d.functions.iter().cloned()).collect();
+    let scanner = CallScanner::new(&names);
+    let jobs = jobs.max(1);
+    let chunk_size = documents.len().div_ceil(jobs).max(1);
+    let progress = AtomicUsize::new(0);
+
+    let results: Vec<Vec<(String, FunctionCalls)>> = thread::scope(|scope| {
+        let handles: Vec<_> = documents
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let names = &names;
+                let scanner = &scanner;
+                let progress = &progress;
+                scope.spawn(move || {
+                    let mut calls = Vec::new();
+                    for document in chunk {
+                        calls.extend(document_calls(project_path, document, scanner, names));
+                        let done = progress.fetch_add(1, Ordering::SeqCst) + 1;
+                        if done.is_multiple_of(25) {
+                            eprintln!("indexing functions, please wait ({}%)", done * 100 / documents.len());
+                        }
+                    }
+                    calls
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("indexing worker panicked"))
+            .collect()
+    });
+
+    let mut function_index: HashMap<String, FunctionCalls> = HashMap::new();
+    for (name, called_functions) in results.into_iter().flatten() {
+        function_index.insert(name, called_functions);
+    }
+    function_index
+}
+
+fn document_calls(project_path: &str, document: &IndexedDocument, scanner: &CallScanner, names: &[String]) -> Vec<(String, FunctionCalls)> {
+    let doc_path = project_path.to_string() + "/" + document.file.as_str();
+    let source = match fs::read_to_string(&doc_path) {
+        Ok(source) => source,
+        Err(err) => {
+            log!(Level::Warn, "could not read {}: {}", doc_path, err);
+            return Vec::new();
+        }
+    };
+    let doc_lines: Vec<&str> = source.split('\n').collect();
+
+    let mut calls = Vec::new();
+    for (name, range) in document.functions.iter().zip(document.ranges.iter()) {
+        let start = (range.start.line + 1) as usize;
+        let end = (range.end.line as usize).min(doc_lines.len());
+        let mut called_functions = Vec::new();
+        if start < end {
+            let function_data = doc_lines[start..end].join("\n");
+            called_functions = find_called_functions(&function_data, scanner, names);
+            for (_, sites) in called_functions.iter_mut() {
+                for site in sites.iter_mut() {
+                    site.file = document.file.clone();
+                    site.line += start as u32;
+                }
+            }
+        }
+        calls.push((name.clone(), called_functions));
+    }
+    calls
+}
+
+// Called functions in order of their first call. The call sites are relative
+// to `function_data` and don't have a file yet.
+pub fn find_called_functions(function_data: &str, scanner: &CallScanner, names: &[String]) -> FunctionCalls {
+    let mut positions: HashMap<usize, usize> = HashMap::new();
+    let mut called_functions: FunctionCalls = Vec::new();
+    for call in scanner.scan(function_data) {
+        let position = *positions.entry(call.function).or_insert_with(|| {
+            called_functions.push((names[call.function].clone(), Vec::new()));
+            called_functions.len() - 1
+        });
+        called_functions[position].1.push(CallSite {
+            file: String::new(),
+            line: call.line as u32 + 1,
+            column: call.column as u32 + 1,
+        });
+    }
+    called_functions
+}
@@ -7,9 +7,6 @@ use std::str;
 
 use log;
 use log::{debug, trace};
-use lsp_types::request::{
-    CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
-};
 
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -34,7 +31,6 @@ pub(crate) type Error = Box<dyn std::error::Error>;
 
 pub trait LanguageServer: Send {
     fn initialize(&mut self) -> Result<InitializeResult, Error>;
-    fn initialized(&mut self) -> Result<(), Error>;
     fn shutdown(&mut self) -> Result<(), Error>;
     fn exit(&mut self) -> Result<(), Error>;
     fn document_open(&mut self, path: &str) -> Result<TextDocumentItem, Error>;
@@ -42,19 +38,6 @@ pub trait LanguageServer: Send {
         &mut self,
         document: &TextDocumentItem,
     ) -> Result<Option<DocumentSymbolResponse>, Error>;
-    fn call_hierarchy_item(
-        &mut self,
-        document: &TextDocumentItem,
-        position: Position,
-    ) -> Result<Option<Vec<CallHierarchyItem>>, Error>;
-    fn call_hierarchy_item_outgoing(
-        &mut self,
-        call_hierarchy_item: CallHierarchyItem,
-    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>, Error>;
-    fn call_hierarchy_item_incoming(
-        &mut self,
-        call_hierarchy_item: CallHierarchyItem,
-    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>, Error>;
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -100,8 +83,8 @@ impl Notification {
     }
 }
 
-#[serde(untagged)]
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
 enum ServerMessage {
     Response(Response),
     Notification(Notification),
@@ -119,7 +102,8 @@ impl ClangdLanguageServer {
         Ok(Box::new(ClangdLanguageServer {
             cmd: Command::new(launcher.server_path)
                 .args(ClangdLanguageServer::compose_args(
-                    launcher.project_path.clone(),
+                    launcher.compile_commands_dir.clone(),
+                    launcher.args.clone(),
                 ))
                 .stdin(Stdio::piped())
                 .stdout(Stdio::piped())
@@ -131,14 +115,16 @@ impl ClangdLanguageServer {
         }))
     }
 
-    fn compose_args(project_path: String) -> Vec<String> {
-        vec![
+    fn compose_args(compile_commands_dir: String, args: Vec<String>) -> Vec<String> {
+        let mut composed = vec![
             "--background-index".to_owned(),
             //"--cross-file-rename".to_owned(),
-            format!("--compile-commands-dir=\"{}\"", project_path).to_owned(),
+            format!("--compile-commands-dir={}", compile_commands_dir),
             //project_path,
             //"--completion-style=detailed".to_owned(),
-        ]
+        ];
+        composed.extend(args);
+        composed
     }
 
     fn languages_supported(languages: Vec<String>) -> bool {
@@ -303,10 +289,6 @@ impl LanguageServer for ClangdLanguageServer {
         response
     }
 
-    fn initialized(&mut self) -> Result<(), Error> {
-        self.notify(Notification::new::<Initialized>(InitializedParams {}))
-    }
-
     fn shutdown(&mut self) -> Result<(), Error> {
         let params = Request::<Shutdown>::new(());
         self.request(params)
@@ -352,61 +334,13 @@ impl LanguageServer for ClangdLanguageServer {
         self.request(params)
     }
 
-    fn call_hierarchy_item(
-        &mut self,
-        document: &TextDocumentItem,
-        position: Position,
-    ) -> Result<Option<Vec<CallHierarchyItem>>, Error> {
-        let params = Request::<CallHierarchyPrepare>::new(CallHierarchyPrepareParams {
-            text_document_position_params: TextDocumentPositionParams {
-                text_document: TextDocumentIdentifier {
-                    uri: document.uri.clone(),
-                },
-                position,
-            },
-            work_done_progress_params: WorkDoneProgressParams {
-                work_done_token: None,
-            },
-        });
-        self.request(params)
-    }
-
-    fn call_hierarchy_item_outgoing(
-        &mut self,
-        call_hierarchy_item: CallHierarchyItem,
-    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>, Error> {
-        let params = Request::<CallHierarchyOutgoingCalls>::new(CallHierarchyOutgoingCallsParams {
-            item: call_hierarchy_item,
-            work_done_progress_params: WorkDoneProgressParams {
-                work_done_token: None,
-            },
-            partial_result_params: PartialResultParams {
-                partial_result_token: None,
-            },
-        });
-        self.request(params)
-    }
-
-    fn call_hierarchy_item_incoming(
-        &mut self,
-        call_hierarchy_item: CallHierarchyItem,
-    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>, Error> {
-        let params = Request::<CallHierarchyIncomingCalls>::new(CallHierarchyIncomingCallsParams {
-            item: call_hierarchy_item,
-            work_done_progress_params: WorkDoneProgressParams {
-                work_done_token: None,
-            },
-            partial_result_params: PartialResultParams {
-                partial_result_token: None,
-            },
-        });
-        self.request(params)
-    }
 }
 
 pub struct LanguageServerLauncher {
     server_path: String,
     project_path: String,
+    compile_commands_dir: String,
+    args: Vec<String>,
     languages: Vec<String>,
 }
 
@@ -415,6 +349,8 @@ impl LanguageServerLauncher {
         LanguageServerLauncher {
             server_path: "".to_owned(),
             project_path: "".to_owned(),
+            compile_commands_dir: "".to_owned(),
+            args: Vec::new(),
             languages: Vec::new(),
         }
     }
@@ -425,10 +361,24 @@ impl LanguageServerLauncher {
     }
 
     pub fn project(mut self, path: String) -> LanguageServerLauncher {
+        if self.compile_commands_dir.is_empty() {
+            self.compile_commands_dir = path.clone();
+        }
         self.project_path = path;
         self
     }
 
+    pub fn compile_commands_dir(mut self, path: String) -> LanguageServerLauncher {
+        self.compile_commands_dir = path;
+        self
+    }
+
+    // passed to the server after the default arguments
+    pub fn args(mut self, args: Vec<String>) -> LanguageServerLauncher {
+        self.args = args;
+        self
+    }
+
     pub fn launch(self) -> Result<Box<dyn LanguageServer>, Error> {
         if ClangdLanguageServer::languages_supported(self.languages.clone()) {
             ClangdLanguageServer::new(self)
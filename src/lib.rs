@@ -0,0 +1,65 @@
+//! Call graphs of C and C++ projects, indexed with clangd and queried with a
+//! small query language.
+//!
+//! ```no_run
+//! use code_analysis::{DotExporter, GraphExporter, Index, IndexOptions, Query};
+//!
+//! let index = Index::build("path/to/project", &IndexOptions::default())?;
+//! let mut analyzer = index.into_analyzer();
+//! let graph = analyzer.run(&Query::parse("{@main}")?)?;
+//! println!("{}", DotExporter.export(&graph)?);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+use lsp_types::notification::Notification as LspNotification;
+use lsp_types::notification::{DidOpenTextDocument, Exit};
+use lsp_types::request::Request as LspRequest;
+use lsp_types::request::{DocumentSymbolRequest, Initialize, Shutdown};
+use lsp_types::*;
+
+mod aggregate;
+mod analyzer;
+mod ast_generator;
+mod binary_index;
+mod cache;
+mod call_index;
+mod call_scanner;
+// the command line tool, public for src/main.rs only
+#[doc(hidden)]
+pub mod cli;
+mod config;
+mod cycles;
+mod daemon;
+mod dead_code;
+mod export;
+mod graph;
+mod graphql;
+mod impact;
+mod index;
+mod indexer;
+mod lang_server;
+mod paths;
+mod prune;
+mod query;
+mod query_lsp;
+mod repl;
+mod rules;
+mod searcher;
+mod server;
+mod snapshot;
+mod stats;
+mod validate;
+
+pub use analyzer::Analyzer;
+pub use config::{SourceFilter, SourcesConfig};
+pub use export::{DotExporter, ExportFormat, GraphExporter, JsonExporter};
+pub use graph::{Edge, EdgeChange, Graph as CallGraph, Node};
+pub use index::{Index, IndexOptions, IndexSummary};
+pub use prune::{PruneSpec, PrunedNode};
+pub use query::Query;
+pub use searcher::CallSite;
+pub use validate::{QueryDiagnostic, Severity};
+
+#[cfg(test)]
+mod grammar_test;
+#[cfg(test)]
+mod test_support;
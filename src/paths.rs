@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::call_index::CallIndex;
+
+pub const DEFAULT_MAX_DEPTH: usize = 12;
+
+// Call chains from a function in `from` to a function in `to` with at most
+// `max_depth` calls, shortest first. With `count == 1` this is a breadth first
+// search, otherwise the first `count` simple paths are found by iterative
+// deepening.
+pub fn find_paths(
+    index: &dyn CallIndex,
+    from: &HashSet<String>,
+    to: &HashSet<String>,
+    count: usize,
+    max_depth: usize,
+) -> Vec<Vec<String>> {
+    if count == 0 {
+        return Vec::new();
+    }
+    if count == 1 {
+        return shortest_path(index, from, to, max_depth).into_iter().collect();
+    }
+
+    let distances = distances_to(index, to, max_depth);
+    let mut starts: Vec<&String> = from.iter().filter(|start| distances.contains_key(start.as_str())).collect();
+    starts.sort();
+    let mut paths = Vec::new();
+    for depth in 1..=max_depth {
+        for start in &starts {
+            let mut path = vec![start.as_str()];
+            let mut on_path = HashSet::from([start.as_str()]);
+            paths_of_length(index, &distances, depth, count, &mut path, &mut on_path, &mut paths);
+            if paths.len() >= count {
+                return paths;
+            }
+        }
+    }
+    paths
+}
+
+fn shortest_path<'a>(
+    index: &'a dyn CallIndex,
+    from: &'a HashSet<String>,
+    to: &HashSet<String>,
+    max_depth: usize,
+) -> Option<Vec<String>> {
+    let mut previous: HashMap<&'a str, Option<&'a str>> = HashMap::new();
+    let mut queue: VecDeque<(&'a str, usize)> = VecDeque::new();
+    let mut starts: Vec<&'a str> = from.iter().map(String::as_str).collect();
+    starts.sort();
+    for start in starts {
+        previous.insert(start, None);
+        queue.push_back((start, 0));
+    }
+
+    while let Some((function, depth)) = queue.pop_front() {
+        if depth >= max_depth {
+            continue;
+        }
+        for callee in index.callees(function) {
+            if previous.contains_key(callee) {
+                continue;
+            }
+            previous.insert(callee, Some(function));
+            if to.contains(callee) {
+                let mut path = vec![callee.to_string()];
+                let mut current = function;
+                loop {
+                    path.push(current.to_string());
+                    match previous[current] {
+                        Some(caller) => current = caller,
+                        None => break,
+                    }
+                }
+                path.reverse();
+                return Some(path);
+            }
+            queue.push_back((callee, depth + 1));
+        }
+    }
+    None
+}
+
+// Number of calls from every function to the nearest function of `to`, for
+// the functions at most `max_depth` calls away. Found by a breadth first
+// search over the callers.
+fn distances_to<'a>(index: &'a dyn CallIndex, to: &'a HashSet<String>, max_depth: usize) -> HashMap<&'a str, usize> {
+    let mut distances: HashMap<&str, usize> = to.iter().map(|name| (name.as_str(), 0)).collect();
+    let mut queue: VecDeque<&str> = to.iter().map(String::as_str).collect();
+    while let Some(function) = queue.pop_front() {
+        let distance = distances[function] + 1;
+        if distance > max_depth {
+            continue;
+        }
+        for caller in index.callers(function) {
+            if !distances.contains_key(caller) {
+                distances.insert(caller, distance);
+                queue.push_back(caller);
+            }
+        }
+    }
+    distances
+}
+
+// Depth first search for simple paths with exactly `depth` calls to a
+// function at distance 0. Callees that cannot get there with the calls left
+// are skipped.
+fn paths_of_length<'a>(
+    index: &'a dyn CallIndex,
+    distances: &HashMap<&str, usize>,
+    depth: usize,
+    count: usize,
+    path: &mut Vec<&'a str>,
+    on_path: &mut HashSet<&'a str>,
+    paths: &mut Vec<Vec<String>>,
+) {
+    let function = path[path.len() - 1];
+    for callee in index.callees(function) {
+        if paths.len() >= count {
+            return;
+        }
+        if on_path.contains(callee) {
+            continue;
+        }
+        let remaining = depth - path.len();
+        if distances.get(callee).is_none_or(|distance| *distance > remaining) {
+            continue;
+        }
+        path.push(callee);
+        if path.len() == depth + 1 {
+            // with no calls left only functions of `to` get here
+            paths.push(path.iter().map(|name| name.to_string()).collect());
+        } else {
+            on_path.insert(callee);
+            paths_of_length(index, distances, depth, count, path, on_path, paths);
+            on_path.remove(callee);
+        }
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod paths_test;
@@ -0,0 +1,79 @@
+use super::*;
+use crate::call_index::MapIndex;
+
+fn calls(edges: &[(&str, &[&str])]) -> MapIndex {
+    let function_calls: HashMap<String, Vec<String>> = edges
+        .iter()
+        .map(|(caller, callees)| (caller.to_string(), callees.iter().map(|c| c.to_string()).collect()))
+        .collect();
+    MapIndex::from_calls(function_calls)
+}
+
+fn set(names: &[&str]) -> HashSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn path(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn request_calls() -> MapIndex {
+    calls(&[
+        ("handle_request", &["parse", "dispatch"]),
+        ("parse", &["parse_header", "fail"]),
+        ("parse_header", &["fail"]),
+        ("dispatch", &["handle_request", "run"]),
+        ("run", &["fail"]),
+        ("fail", &["abort"]),
+    ])
+}
+
+#[test]
+fn test_shortest_path() {
+    let paths = find_paths(&request_calls(), &set(&["handle_request"]), &set(&["abort"]), 1, DEFAULT_MAX_DEPTH);
+    assert_eq!(paths, vec![path(&["handle_request", "parse", "fail", "abort"])]);
+}
+
+#[test]
+fn test_k_simple_paths_shortest_first() {
+    let paths = find_paths(&request_calls(), &set(&["handle_request"]), &set(&["abort"]), 3, DEFAULT_MAX_DEPTH);
+    assert_eq!(paths.len(), 3);
+    assert_eq!(paths[0], path(&["handle_request", "parse", "fail", "abort"]));
+    assert_eq!(paths[1].len(), 5);
+    assert_eq!(paths[2].len(), 5);
+    assert!(paths.contains(&path(&["handle_request", "dispatch", "run", "fail", "abort"])));
+    assert!(paths.contains(&path(&["handle_request", "parse", "parse_header", "fail", "abort"])));
+}
+
+#[test]
+fn test_depth_limit() {
+    let function_calls = request_calls();
+    assert!(find_paths(&function_calls, &set(&["handle_request"]), &set(&["abort"]), 1, 2).is_empty());
+    assert!(find_paths(&function_calls, &set(&["handle_request"]), &set(&["abort"]), 5, 2).is_empty());
+    assert_eq!(find_paths(&function_calls, &set(&["handle_request"]), &set(&["abort"]), 5, 3).len(), 1);
+}
+
+// Every function of the clique calls every other one, only the last one
+// calls `sink`.
+fn clique(size: usize) -> MapIndex {
+    let names: Vec<String> = (0..size).map(|i| format!("f{}", i)).collect();
+    let mut function_calls: HashMap<String, Vec<String>> = names
+        .iter()
+        .map(|caller| (caller.clone(), names.iter().filter(|callee| *callee != caller).cloned().collect()))
+        .collect();
+    function_calls.get_mut(&names[size - 1]).unwrap().push("sink".to_string());
+    MapIndex::from_calls(function_calls)
+}
+
+#[test]
+fn test_dense_graph_is_pruned() {
+    let index = clique(40);
+    let paths = find_paths(&index, &set(&["f0"]), &set(&["sink"]), 5, DEFAULT_MAX_DEPTH);
+    assert_eq!(paths.len(), 5);
+    assert_eq!(paths[0], path(&["f0", "f39", "sink"]));
+    assert!(paths[1..].iter().all(|found| found.len() == 4 && found[2] == "f39"));
+
+    // nothing calls `missing`, without pruning this walks every simple path
+    // of up to 12 calls through the clique
+    assert!(find_paths(&index, &set(&["f0"]), &set(&["missing"]), 5, DEFAULT_MAX_DEPTH).is_empty());
+}
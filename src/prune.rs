@@ -0,0 +1,240 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::graph::{Graph, Node};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrunedNode {
+    pub name: String,
+    pub reason: String,
+}
+
+impl fmt::Display for PrunedNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pruned {}: {}", self.name, self.reason)
+    }
+}
+
+// Decides which nodes of a query result are dropped. `prune_children` runs
+// after the edges from one parent to the functions of a scope were added,
+// `prune_result` once after the whole query was evaluated.
+pub trait PruningPolicy {
+    fn name(&self) -> String;
+
+    fn prune_children(&self, graph: &mut Graph, children: &HashSet<String>, found_descendants: bool) -> Vec<PrunedNode> {
+        let _ = (graph, children, found_descendants);
+        Vec::new()
+    }
+
+    fn prune_result(&self, graph: &mut Graph, targets: &HashSet<String>) -> Vec<PrunedNode> {
+        let _ = (graph, targets);
+        Vec::new()
+    }
+}
+
+pub struct NoPruning;
+
+impl PruningPolicy for NoPruning {
+    fn name(&self) -> String {
+        "none".to_string()
+    }
+}
+
+// The original heuristic: once a child has children of its own, the other
+// children that are used less than `min` times are removed. Removing a node
+// also removes its callees that are used at most once afterwards.
+pub struct TimesUsedPolicy {
+    pub min: u32,
+}
+
+impl PruningPolicy for TimesUsedPolicy {
+    fn name(&self) -> String {
+        format!("times-used={}", self.min)
+    }
+
+    fn prune_children(&self, graph: &mut Graph, children: &HashSet<String>, found_descendants: bool) -> Vec<PrunedNode> {
+        let mut pruned = Vec::new();
+        if !found_descendants {
+            return pruned;
+        }
+        let unimportant: Vec<(String, u32)> = graph
+            .nodes()
+            .filter(|node| children.contains(&node.name) && node.times_used < self.min)
+            .map(|node| (node.name.clone(), node.times_used))
+            .collect();
+        for (name, times_used) in unimportant {
+            let removed = graph.remove_node(&name);
+            for (i, removed_name) in removed.into_iter().enumerate() {
+                let reason = if i == 0 {
+                    format!("used {}× (less than {})", times_used, self.min)
+                } else {
+                    format!("used at most once after {} was pruned", name)
+                };
+                pruned.push(PrunedNode { name: removed_name, reason });
+            }
+        }
+        pruned
+    }
+}
+
+// Removes called functions with fewer than `min` callers in the result.
+// Functions matched by a filter of the query are always kept.
+pub struct FanInPolicy {
+    pub min: usize,
+}
+
+impl PruningPolicy for FanInPolicy {
+    fn name(&self) -> String {
+        format!("fan-in={}", self.min)
+    }
+
+    fn prune_result(&self, graph: &mut Graph, targets: &HashSet<String>) -> Vec<PrunedNode> {
+        let low_fan_in: Vec<(String, usize)> = graph
+            .nodes()
+            .filter_map(|node| {
+                let fan_in = graph.callers(&node.name).count();
+                if fan_in > 0 && fan_in < self.min && !targets.contains(&node.name) {
+                    Some((node.name.clone(), fan_in))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let mut pruned = Vec::new();
+        for (name, fan_in) in low_fan_in {
+            if graph.remove_single_node(&name) {
+                pruned.push(PrunedNode { name, reason: format!("fan-in {} (less than {})", fan_in, self.min) });
+            }
+        }
+        pruned
+    }
+}
+
+// Keeps only the functions matched by the query and the nodes connected to
+// them, i.e. nodes on a call path that leads to or starts from a match.
+pub struct QueryPathPolicy;
+
+impl PruningPolicy for QueryPathPolicy {
+    fn name(&self) -> String {
+        "query-paths".to_string()
+    }
+
+    fn prune_result(&self, graph: &mut Graph, targets: &HashSet<String>) -> Vec<PrunedNode> {
+        if targets.is_empty() {
+            return Vec::new();
+        }
+        let mut on_path: HashSet<&str> = HashSet::new();
+        for callers in [true, false] {
+            let mut queue: VecDeque<&str> = targets.iter().filter_map(|t| graph.node(t)).map(|node| node.name.as_str()).collect();
+            let mut seen: HashSet<&str> = queue.iter().copied().collect();
+            while let Some(name) = queue.pop_front() {
+                on_path.insert(name);
+                let next: Vec<&Node> = if callers { graph.callers(name).collect() } else { graph.callees(name).collect() };
+                for node in next {
+                    if seen.insert(&node.name) {
+                        queue.push_back(&node.name);
+                    }
+                }
+            }
+        }
+
+        let off_path: Vec<String> = graph
+            .nodes()
+            .filter(|node| !on_path.contains(node.name.as_str()))
+            .map(|node| node.name.clone())
+            .collect();
+        let mut pruned = Vec::new();
+        for name in off_path {
+            if graph.remove_single_node(&name) {
+                pruned.push(PrunedNode { name, reason: "not on a path to a queried function".to_string() });
+            }
+        }
+        pruned
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PruneSpec {
+    None,
+    TimesUsed(u32),
+    FanIn(usize),
+    QueryPaths,
+}
+
+impl Default for PruneSpec {
+    fn default() -> Self {
+        PruneSpec::TimesUsed(2)
+    }
+}
+
+impl PruneSpec {
+    pub fn policy(&self) -> Box<dyn PruningPolicy> {
+        match self {
+            PruneSpec::None => Box::new(NoPruning),
+            PruneSpec::TimesUsed(min) => Box::new(TimesUsedPolicy { min: *min }),
+            PruneSpec::FanIn(min) => Box::new(FanInPolicy { min: *min }),
+            PruneSpec::QueryPaths => Box::new(QueryPathPolicy),
+        }
+    }
+}
+
+impl FromStr for PruneSpec {
+    type Err = String;
+
+    // none | times-used[=N] | fan-in=N | query-paths
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (s.trim(), None),
+        };
+        match name {
+            "none" => Ok(PruneSpec::None),
+            "times-used" => Ok(PruneSpec::TimesUsed(threshold(name, value, Some(2))?)),
+            "fan-in" => Ok(PruneSpec::FanIn(threshold(name, value, None)?)),
+            "query-paths" => Ok(PruneSpec::QueryPaths),
+            _ => Err(format!("unknown pruning policy '{}', expected none, times-used, fan-in or query-paths", s)),
+        }
+    }
+}
+
+// Out of range values are invalid as well, instead of wrapping around.
+fn threshold<T: FromStr>(name: &str, value: Option<&str>, default: Option<T>) -> Result<T, String> {
+    match (value, default) {
+        (Some(v), _) => v.parse().map_err(|_| format!("invalid threshold '{}' for pruning policy {}", v, name)),
+        (None, Some(d)) => Ok(d),
+        (None, None) => Err(format!("pruning policy {} needs a threshold, e.g. {}=2", name, name)),
+    }
+}
+
+impl TryFrom<String> for PruneSpec {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for PruneSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PruneSpec::None => write!(f, "none"),
+            PruneSpec::TimesUsed(min) => write!(f, "times-used={}", min),
+            PruneSpec::FanIn(min) => write!(f, "fan-in={}", min),
+            PruneSpec::QueryPaths => write!(f, "query-paths"),
+        }
+    }
+}
+
+impl From<PruneSpec> for String {
+    fn from(spec: PruneSpec) -> String {
+        spec.to_string()
+    }
+}
+
+#[cfg(test)]
+mod prune_test;
@@ -0,0 +1,81 @@
+use super::*;
+
+fn graph(edges: &[(&str, &str)]) -> Graph {
+    let mut graph = Graph::new();
+    for (start, end) in edges {
+        graph.add_node(start.to_string(), 1);
+        graph.add_node(end.to_string(), 1);
+        graph.add_edge(start.to_string(), end.to_string(), vec![]).unwrap();
+    }
+    graph
+}
+
+fn names(pruned: &[PrunedNode]) -> Vec<String> {
+    let mut names: Vec<String> = pruned.iter().map(|node| node.name.clone()).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_parse_prune_spec() {
+    assert_eq!("none".parse::<PruneSpec>(), Ok(PruneSpec::None));
+    assert_eq!("times-used".parse::<PruneSpec>(), Ok(PruneSpec::TimesUsed(2)));
+    assert_eq!("times-used=3".parse::<PruneSpec>(), Ok(PruneSpec::TimesUsed(3)));
+    assert_eq!("fan-in=4".parse::<PruneSpec>(), Ok(PruneSpec::FanIn(4)));
+    assert_eq!("query-paths".parse::<PruneSpec>(), Ok(PruneSpec::QueryPaths));
+    assert!("fan-in".parse::<PruneSpec>().is_err());
+    assert!("fan-in=many".parse::<PruneSpec>().is_err());
+    assert!("times-used=4294967297".parse::<PruneSpec>().is_err());
+    assert!("random".parse::<PruneSpec>().is_err());
+}
+
+#[test]
+fn test_prune_spec_display_parses_back() {
+    for spec in [PruneSpec::None, PruneSpec::TimesUsed(3), PruneSpec::FanIn(4), PruneSpec::QueryPaths] {
+        assert_eq!(spec.to_string().parse::<PruneSpec>(), Ok(spec));
+    }
+}
+
+#[test]
+fn test_times_used_reports_cascade() {
+    let mut graph = graph(&[("main", "a"), ("c", "e")]);
+    let children = HashSet::from(["a".to_string(), "c".to_string()]);
+
+    let policy = TimesUsedPolicy { min: 2 };
+    assert!(policy.prune_children(&mut graph, &children, false).is_empty());
+
+    let pruned = policy.prune_children(&mut graph, &children, true);
+    assert_eq!(names(&pruned), vec!["c".to_string(), "e".to_string()]);
+    assert!(pruned.iter().any(|node| node.name == "e" && node.reason.contains("after c was pruned")));
+    assert!(graph.node("a").is_some());
+}
+
+#[test]
+fn test_fan_in_keeps_targets_and_roots() {
+    let mut graph = graph(&[("main", "a"), ("main", "b"), ("other", "b"), ("main", "target")]);
+    let targets = HashSet::from(["target".to_string()]);
+
+    let pruned = FanInPolicy { min: 2 }.prune_result(&mut graph, &targets);
+    assert_eq!(names(&pruned), vec!["a".to_string()]);
+    assert!(graph.node("main").is_some());
+    assert!(graph.node("target").is_some());
+}
+
+#[test]
+fn test_query_paths_drops_unconnected_nodes() {
+    let mut graph = graph(&[("main", "target"), ("target", "leaf"), ("other", "unrelated")]);
+    let targets = HashSet::from(["target".to_string()]);
+
+    let pruned = QueryPathPolicy.prune_result(&mut graph, &targets);
+    assert_eq!(names(&pruned), vec!["other".to_string(), "unrelated".to_string()]);
+    assert_eq!(graph.nodes().count(), 3);
+}
+
+#[test]
+fn test_no_pruning() {
+    let mut graph = graph(&[("main", "a")]);
+    let children = HashSet::from(["a".to_string()]);
+    assert!(NoPruning.prune_children(&mut graph, &children, true).is_empty());
+    assert!(NoPruning.prune_result(&mut graph, &children).is_empty());
+    assert_eq!(graph.nodes().count(), 2);
+}
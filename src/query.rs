@@ -0,0 +1,89 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use crate::ast_generator;
+use crate::prune::PruneSpec;
+use crate::validate::{self, QueryDiagnostic, Severity};
+
+/// A query of the query language that parsed and passed validation, with the
+/// values of its `$parameters`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    text: String,
+    parameters: HashMap<String, String>,
+    pruning: Option<PruneSpec>,
+}
+
+impl Query {
+    /// Fails with every syntax error and invalid parameter of `text`.
+    /// `$parameters` may still be unbound.
+    pub fn parse(text: &str) -> anyhow::Result<Query> {
+        let errors: Vec<String> = validate::validate(text, None)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        if !errors.is_empty() {
+            anyhow::bail!("invalid query: {}", errors.join("; "));
+        }
+        Ok(Query { text: text.to_string(), parameters: HashMap::new(), pruning: None })
+    }
+
+    /// Sets the value of `$name`, replacing an earlier value.
+    pub fn bind(mut self, name: &str, value: &str) -> Query {
+        self.parameters.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Prunes the result of this query with `spec` instead of the policy of
+    /// the analyzer.
+    pub fn with_pruning(mut self, spec: PruneSpec) -> Query {
+        self.pruning = Some(spec);
+        self
+    }
+
+    /// Fails if a parameter is not bound or its value is not valid where the
+    /// parameter is used.
+    pub fn check_parameters(&self) -> anyhow::Result<()> {
+        let errors: Vec<String> = validate::validate_with(&self.text, None, Some(&self.parameters))
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        if !errors.is_empty() {
+            anyhow::bail!("invalid parameters: {}", errors.join("; "));
+        }
+        Ok(())
+    }
+
+    /// Warnings about the query, given the indexed functions also about
+    /// functions that are not in the index.
+    pub fn validate(&self, functions: Option<&HashSet<String>>) -> Vec<QueryDiagnostic> {
+        validate::validate_with(&self.text, functions, Some(&self.parameters))
+    }
+
+    /// Names of the `$parameters` in the query, sorted.
+    pub fn parameter_names(&self) -> Vec<String> {
+        ast_generator::parameter_names(&self.text).unwrap_or_default()
+    }
+
+    pub fn parameters(&self) -> &HashMap<String, String> {
+        &self.parameters
+    }
+
+    pub fn pruning(&self) -> Option<&PruneSpec> {
+        self.pruning.as_ref()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl FromStr for Query {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse(s)
+    }
+}
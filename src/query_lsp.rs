@@ -0,0 +1,320 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use anyhow::Context;
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionList, CompletionOptions, CompletionParams, Diagnostic,
+    DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    GotoDefinitionParams, Hover, HoverContents, HoverParams, HoverProviderCapability, InitializeResult, Location,
+    MarkupContent, MarkupKind, OneOf, Position, PublishDiagnosticsParams, Range, ServerCapabilities, ServerInfo,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::analyzer::Analyzer;
+use crate::validate::{self, QueryDiagnostic, Severity};
+
+const MAX_COMPLETIONS: usize = 100;
+
+// A language server for query files (stdio, JSON-RPC), backed by the index of
+// the project the queries run against.
+pub struct QueryLanguageServer {
+    analyzer: Analyzer,
+    project_path: PathBuf,
+    documents: HashMap<Url, String>,
+    // sorted for completion
+    functions: Vec<String>,
+    // the same names for validating documents
+    function_set: HashSet<String>,
+}
+
+// What the word under the cursor is.
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Verb(String),
+    Other,
+}
+
+impl QueryLanguageServer {
+    pub fn new(mut analyzer: Analyzer, project_path: PathBuf) -> QueryLanguageServer {
+        let mut functions: Vec<String> = analyzer.lang_server().call_index().definitions().map(|(_, name)| name.to_string()).collect();
+        functions.sort();
+        functions.dedup();
+        let function_set = functions.iter().cloned().collect();
+        QueryLanguageServer { analyzer, project_path, documents: HashMap::new(), functions, function_set }
+    }
+
+    pub fn into_analyzer(self) -> Analyzer {
+        self.analyzer
+    }
+
+    // Handles one message from the client and returns the messages to send
+    // back. `None` means the client asked the server to exit.
+    pub fn handle(&mut self, message: &Value) -> Option<Vec<Value>> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = message["params"].clone();
+        let id = message.get("id").cloned();
+
+        let result = match method {
+            "exit" => return None,
+            "initialize" => Ok(serde_json::to_value(self.initialize()).unwrap_or_default()),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/didOpen" => {
+                return Some(parse(params).map(|params: DidOpenTextDocumentParams| {
+                    self.update(params.text_document.uri, params.text_document.text)
+                }).unwrap_or_default());
+            }
+            "textDocument/didChange" => {
+                return Some(parse(params).map(|mut params: DidChangeTextDocumentParams| {
+                    // full sync, the last change holds the whole document
+                    match params.content_changes.pop() {
+                        Some(change) => self.update(params.text_document.uri, change.text),
+                        None => Vec::new(),
+                    }
+                }).unwrap_or_default());
+            }
+            "textDocument/didClose" => {
+                return Some(parse(params).map(|params: DidCloseTextDocumentParams| {
+                    self.documents.remove(&params.text_document.uri);
+                    vec![publish(params.text_document.uri, Vec::new())]
+                }).unwrap_or_default());
+            }
+            "textDocument/completion" => parse(params).map(|params: CompletionParams| {
+                let position = params.text_document_position;
+                json!(self.completion(&position.text_document.uri, position.position))
+            }),
+            "textDocument/hover" => parse(params).map(|params: HoverParams| {
+                let position = params.text_document_position_params;
+                json!(self.hover(&position.text_document.uri, position.position))
+            }),
+            "textDocument/definition" => parse(params).map(|params: GotoDefinitionParams| {
+                let position = params.text_document_position_params;
+                json!(self.definition(&position.text_document.uri, position.position))
+            }),
+            _ if id.is_some() => Err((-32601, format!("unknown method {}", method))),
+            // notifications the server does not care about
+            _ => return Some(Vec::new()),
+        };
+
+        let id = match id {
+            Some(id) => id,
+            None => return Some(Vec::new()),
+        };
+        Some(vec![match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+        }])
+    }
+
+    fn initialize(&self) -> InitializeResult {
+        InitializeResult {
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec!["@".to_string(), "(".to_string(), ",".to_string(), "\"".to_string()]),
+                    ..CompletionOptions::default()
+                }),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                ..ServerCapabilities::default()
+            },
+            server_info: Some(ServerInfo { name: "code-analysis-query".to_string(), version: None }),
+        }
+    }
+
+    fn update(&mut self, uri: Url, text: String) -> Vec<Value> {
+        let diagnostics = validate::validate(&text, Some(&self.function_set)).iter().map(to_lsp_diagnostic).collect();
+        self.documents.insert(uri.clone(), text);
+        vec![publish(uri, diagnostics)]
+    }
+
+    fn line_before(&self, uri: &Url, position: Position) -> Option<String> {
+        let line = self.documents.get(uri)?.lines().nth(position.line as usize)?;
+        Some(line.chars().take(position.character as usize).collect())
+    }
+
+    fn completion(&self, uri: &Url, position: Position) -> CompletionList {
+        let before = self.line_before(uri, position).unwrap_or_default();
+        let parameter_value = Regex::new(r#"(\w+)="([^"]*)$"#).unwrap();
+        let parameter_name = Regex::new(r#"@(\w+)\((?:[^)]*,)?(\w*)$"#).unwrap();
+        let verb = Regex::new(r"@(\w*)$").unwrap();
+
+        let mut items = Vec::new();
+        if let Some(captures) = parameter_value.captures(&before) {
+            if ["function", "from", "to"].contains(&captures[1].to_lowercase().as_str()) {
+                items = self.function_items(&captures[2]);
+            }
+        } else if let Some(captures) = parameter_name.captures(&before) {
+            items = validate::verb_parameters(&captures[1])
+                .iter()
+                .filter(|name| name.starts_with(&captures[2]))
+                .map(|name| CompletionItem {
+                    label: name.to_string(),
+                    kind: Some(CompletionItemKind::PROPERTY),
+                    insert_text: Some(format!("{}=\"", name)),
+                    ..CompletionItem::default()
+                })
+                .collect();
+        } else if let Some(captures) = verb.captures(&before) {
+            items = validate::KEYWORD_VERBS
+                .iter()
+                .filter(|keyword| keyword.starts_with(&captures[1]))
+                .map(|keyword| CompletionItem {
+                    label: keyword.to_string(),
+                    kind: Some(CompletionItemKind::KEYWORD),
+                    detail: Some(keyword_documentation(keyword).to_string()),
+                    ..CompletionItem::default()
+                })
+                .collect();
+            items.extend(self.function_items(&captures[1]));
+        }
+        CompletionList { is_incomplete: items.len() > MAX_COMPLETIONS, items: items.into_iter().take(MAX_COMPLETIONS).collect() }
+    }
+
+    fn function_items(&self, prefix: &str) -> Vec<CompletionItem> {
+        let first = self.functions.partition_point(|name| name.as_str() < prefix);
+        self.functions[first..]
+            .iter()
+            .take_while(|name| name.starts_with(prefix))
+            .take(MAX_COMPLETIONS + 1)
+            .map(|name| CompletionItem {
+                label: name.clone(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                ..CompletionItem::default()
+            })
+            .collect()
+    }
+
+    fn token_at(&self, uri: &Url, position: Position) -> Option<(Token, Range)> {
+        let line: Vec<char> = self.documents.get(uri)?.lines().nth(position.line as usize)?.chars().collect();
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        let column = (position.character as usize).min(line.len());
+        let start = line[..column].iter().rposition(|c| !is_word(c)).map_or(0, |i| i + 1);
+        let end = line[column..].iter().position(|c| !is_word(c)).map_or(line.len(), |i| column + i);
+        if start == end {
+            return None;
+        }
+        let range = Range::new(Position::new(position.line, start as u32), Position::new(position.line, end as u32));
+        let word: String = line[start..end].iter().collect();
+        if start > 0 && line[start - 1] == '@' {
+            Some((Token::Verb(word), range))
+        } else {
+            Some((Token::Other, range))
+        }
+    }
+
+    fn hover(&mut self, uri: &Url, position: Position) -> Option<Hover> {
+        let (token, range) = self.token_at(uri, position)?;
+        let name = match token {
+            Token::Verb(name) => name,
+            Token::Other => return None,
+        };
+        let text = if validate::KEYWORD_VERBS.contains(&name.as_str()) {
+            format!("**@{}**\n\n{}", name, keyword_documentation(&name))
+        } else {
+            let lang_server = self.analyzer.lang_server();
+            let location = lang_server.function_location(&name);
+            if location.is_none() && self.functions.binary_search(&name).is_err() {
+                return None;
+            }
+            let callers = lang_server.callers_of(&name).len();
+            let callees = lang_server.callees_of(&name).len();
+            let location = location.map_or_else(|| "unknown location".to_string(), |(file, start, end)| format!("{}:{}-{}", file, start, end));
+            format!("**{}**\n\n{}\n\n{} callers, {} callees", name, location, callers, callees)
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value: text }),
+            range: Some(range),
+        })
+    }
+
+    fn definition(&mut self, uri: &Url, position: Position) -> Option<Location> {
+        let name = match self.token_at(uri, position)? {
+            (Token::Verb(name), _) if !validate::KEYWORD_VERBS.contains(&name.as_str()) => name,
+            _ => return None,
+        };
+        let (file, start, end) = self.analyzer.lang_server().function_location(&name)?;
+        let uri = Url::from_file_path(self.project_path.join(file)).ok()?;
+        Some(Location::new(uri, Range::new(Position::new(start.saturating_sub(1), 0), Position::new(end.saturating_sub(1), 0))))
+    }
+}
+
+fn keyword_documentation(keyword: &str) -> &'static str {
+    match keyword {
+        "filter" => "restricts the functions of the scope by function and file regex",
+        "forced" => "adds the edges to the parent scope even without a call",
+        "path" => "call paths between two functions, up to k paths of at most depth calls",
+        "recursive" | "cycles" => "functions that call themselves directly or through others",
+        _ => "",
+    }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, (i64, String)> {
+    serde_json::from_value(params).map_err(|err| (-32602, err.to_string()))
+}
+
+fn publish(uri: Url, diagnostics: Vec<Diagnostic>) -> Value {
+    let params = PublishDiagnosticsParams { uri, diagnostics, version: None };
+    json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": params })
+}
+
+fn to_lsp_diagnostic(diagnostic: &QueryDiagnostic) -> Diagnostic {
+    let position = |(line, column): (usize, usize)| Position::new(line as u32, column as u32);
+    Diagnostic {
+        range: Range::new(position(diagnostic.start), position(diagnostic.end)),
+        severity: Some(match diagnostic.severity {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+        }),
+        source: Some("code-analysis".to_string()),
+        message: diagnostic.message.clone(),
+        ..Diagnostic::default()
+    }
+}
+
+// Reads one `Content-Length` framed message, `None` at the end of the input.
+fn read_message(reader: &mut impl BufRead) -> anyhow::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(("Content-Length", value)) = header.split_once(": ") {
+            content_length = Some(value.parse::<usize>().context("invalid Content-Length")?);
+        }
+    }
+    let mut content = vec![0u8; content_length.context("message without Content-Length")?];
+    reader.read_exact(&mut content)?;
+    Ok(Some(serde_json::from_slice(&content)?))
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> anyhow::Result<()> {
+    let content = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn run(server: &mut QueryLanguageServer, reader: &mut impl BufRead, writer: &mut impl Write) -> anyhow::Result<()> {
+    while let Some(message) = read_message(reader)? {
+        match server.handle(&message) {
+            Some(replies) => {
+                for reply in &replies {
+                    write_message(writer, reply)?;
+                }
+            }
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod query_lsp_test;
@@ -0,0 +1,86 @@
+use super::*;
+use crate::test_support::MockLSPServer;
+
+fn server(text: &str) -> QueryLanguageServer {
+    let mut server = QueryLanguageServer::new(Analyzer::new(MockLSPServer::boxed()), PathBuf::from("/project"));
+    server.handle(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": { "textDocument": { "uri": "file:///query.q", "languageId": "query", "version": 1, "text": text } },
+    }));
+    server
+}
+
+fn request(server: &mut QueryLanguageServer, method: &str, line: u32, character: u32) -> Value {
+    let replies = server
+        .handle(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": { "textDocument": { "uri": "file:///query.q" }, "position": { "line": line, "character": character } },
+        }))
+        .unwrap();
+    replies[0]["result"].clone()
+}
+
+fn labels(completion: &Value) -> Vec<&str> {
+    completion["items"].as_array().unwrap().iter().map(|item| item["label"].as_str().unwrap()).collect()
+}
+
+#[test]
+fn test_diagnostics() {
+    let mut server = QueryLanguageServer::new(Analyzer::new(MockLSPServer::boxed()), PathBuf::from("/project"));
+    let replies = server
+        .handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": "file:///query.q", "languageId": "query", "version": 1, "text": "@filter(name=\"x\")\n{@mian}" } },
+        }))
+        .unwrap();
+    assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+    let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[1]["severity"], 2);
+    assert_eq!(diagnostics[1]["range"], json!({ "start": { "line": 1, "character": 2 }, "end": { "line": 1, "character": 6 } }));
+}
+
+#[test]
+fn test_completion() {
+    let mut server = server("{@pa}\n@path(fr)\n@filter(function=\"par\")");
+    assert_eq!(labels(&request(&mut server, "textDocument/completion", 0, 4)), vec!["path", "parse", "parse_args"]);
+    assert_eq!(labels(&request(&mut server, "textDocument/completion", 1, 8)), vec!["from"]);
+    assert_eq!(labels(&request(&mut server, "textDocument/completion", 2, 21)), vec!["parse", "parse_args"]);
+}
+
+#[test]
+fn test_hover() {
+    let mut server = server("{@parse}");
+    let hover = request(&mut server, "textDocument/hover", 0, 4);
+    assert_eq!(hover["contents"]["value"], "**parse**\n\nparse.c:2-4\n\n1 callers, 1 callees");
+    assert_eq!(request(&mut server, "textDocument/hover", 0, 0), Value::Null);
+}
+
+#[test]
+fn test_definition() {
+    let mut server = server("{@parse}");
+    let location = request(&mut server, "textDocument/definition", 0, 3);
+    assert_eq!(location["uri"], "file:///project/parse.c");
+    assert_eq!(location["range"]["start"]["line"], 1);
+    assert_eq!(request(&mut server, "textDocument/definition", 0, 8), Value::Null);
+}
+
+#[test]
+fn test_transport() {
+    let mut server = QueryLanguageServer::new(Analyzer::new(MockLSPServer::boxed()), PathBuf::from("/project"));
+    let mut input = Vec::new();
+    for message in [json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" }), json!({ "jsonrpc": "2.0", "method": "exit" })] {
+        write_message(&mut input, &message).unwrap();
+    }
+    let mut output = Vec::new();
+    run(&mut server, &mut input.as_slice(), &mut output).unwrap();
+    let mut output = output.as_slice();
+    let reply = read_message(&mut output).unwrap().unwrap();
+    assert_eq!(reply, json!({ "jsonrpc": "2.0", "id": 1, "result": null }));
+    assert!(read_message(&mut output).unwrap().is_none());
+}
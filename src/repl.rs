@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::PathBuf;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use crate::analyzer::Analyzer;
+use crate::export::ExportFormat;
+use crate::prune::PruneSpec;
+use crate::stats;
+
+const COMMANDS: &[&str] = &[":show", ":export", ":clear", ":stats", ":prune", ":param", ":reindex", ":help", ":quit"];
+
+const HELP: &str = "\
+statements of the query language are added to the current graph
+:show                     list the calls in the current graph
+:export dot|json FILE     write the current graph to FILE
+:clear                    start with an empty graph
+:stats                    fan-in, fan-out and centrality of the functions in the graph
+:prune [SPEC]             show or set how later queries are pruned: none,
+                          times-used[=N], fan-in=N or query-paths
+:param [NAME=VALUE]       list the $parameters or set the value of one
+:reindex                  reload the index after files changed
+:quit                     leave, as does ctrl-d
+";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Query(String),
+    Show,
+    Export(ExportFormat, String),
+    Clear,
+    Stats,
+    // None shows the current policy
+    Prune(Option<PruneSpec>),
+    // None lists the parameters
+    Param(Option<(String, String)>),
+    Reindex,
+    Help,
+    Quit,
+}
+
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    if !line.starts_with(':') {
+        return Ok(Command::Query(line.to_string()));
+    }
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [":show"] => Ok(Command::Show),
+        [":export", format, path] => Ok(Command::Export(format.parse()?, path.to_string())),
+        [":export", ..] => Err("usage: :export dot|json FILE".to_string()),
+        [":clear"] => Ok(Command::Clear),
+        [":stats"] => Ok(Command::Stats),
+        [":prune"] => Ok(Command::Prune(None)),
+        [":prune", spec] => Ok(Command::Prune(Some(spec.parse()?))),
+        [":prune", ..] => Err("usage: :prune [SPEC]".to_string()),
+        [":param"] => Ok(Command::Param(None)),
+        [":param", binding] => match binding.split_once('=') {
+            Some((name, value)) if !name.is_empty() => Ok(Command::Param(Some((name.trim_start_matches('$').to_string(), value.to_string())))),
+            _ => Err("usage: :param NAME=VALUE".to_string()),
+        },
+        [":param", ..] => Err("usage: :param [NAME=VALUE]".to_string()),
+        [":reindex"] => Ok(Command::Reindex),
+        [":help"] => Ok(Command::Help),
+        [":quit"] | [":q"] => Ok(Command::Quit),
+        _ => Err(format!("unknown command '{}', :help lists the commands", line)),
+    }
+}
+
+// Runs one command, returns false when the REPL should stop.
+pub fn execute(analyzer: &mut Analyzer, command: Command, out: &mut dyn Write) -> anyhow::Result<bool> {
+    match command {
+        Command::Query(query) if query.is_empty() => {}
+        Command::Query(query) => {
+            let before = analyzer.graph().graph_to_tuple();
+            if let Err(err) = analyzer.try_parse(&query) {
+                writeln!(out, "{}", err)?;
+                return Ok(true);
+            }
+            let mut added: Vec<(String, String)> = analyzer.graph().graph_to_tuple().difference(&before).cloned().collect();
+            added.sort();
+            for (caller, callee) in &added {
+                writeln!(out, "+ {} -> {}", caller, callee)?;
+            }
+            writeln!(out, "{} new calls, {}", added.len(), summary(analyzer))?;
+        }
+        Command::Show => {
+            let mut calls: Vec<(String, String)> = analyzer.graph().graph_to_tuple().into_iter().collect();
+            calls.sort();
+            for (caller, callee) in &calls {
+                writeln!(out, "{} -> {}", caller, callee)?;
+            }
+            writeln!(out, "{}", summary(analyzer))?;
+        }
+        Command::Export(format, path) => {
+            std::fs::write(&path, format.exporter().export(analyzer.graph())?)?;
+            writeln!(out, "wrote {}", path)?;
+        }
+        Command::Clear => analyzer.clear(),
+        Command::Stats => {
+            let functions: HashSet<String> = analyzer.graph().nodes().map(|node| node.name.clone()).collect();
+            let mut function_stats = analyzer.stats();
+            function_stats.retain(|function| functions.contains(&function.name));
+            stats::sort_stats(&mut function_stats, stats::StatsColumn::Betweenness);
+            out.write_all(stats::stats_to_csv(&function_stats).as_bytes())?;
+        }
+        Command::Prune(None) => writeln!(out, "pruning with {}", analyzer.pruning_policy())?,
+        Command::Prune(Some(spec)) => {
+            analyzer.set_pruning_policy(spec.policy());
+            writeln!(out, "pruning with {}", analyzer.pruning_policy())?;
+        }
+        Command::Param(None) => {
+            let mut parameters: Vec<(&String, &String)> = analyzer.parameters().iter().collect();
+            parameters.sort();
+            for (name, value) in parameters {
+                writeln!(out, "${} = {}", name, value)?;
+            }
+        }
+        Command::Param(Some((name, value))) => {
+            let mut parameters = analyzer.parameters().clone();
+            parameters.insert(name, value);
+            analyzer.set_parameters(parameters);
+        }
+        Command::Reindex => {
+            analyzer.reindex(&[]);
+            writeln!(out, "reindexed {} files", analyzer.lang_server().call_index().files().count())?;
+        }
+        Command::Help => out.write_all(HELP.as_bytes())?,
+        Command::Quit => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn summary(analyzer: &Analyzer) -> String {
+    format!("graph has {} functions and {} calls", analyzer.graph().nodes().count(), analyzer.graph().edge_count())
+}
+
+// Completes commands and the names of indexed functions.
+pub struct ReplHelper {
+    names: Vec<String>,
+}
+
+impl ReplHelper {
+    pub fn new(analyzer: &mut Analyzer) -> ReplHelper {
+        let mut names: Vec<String> = analyzer.lang_server().call_index().definitions().map(|(_, name)| name.to_string()).collect();
+        names.extend(COMMANDS.iter().map(|command| command.to_string()));
+        names.sort();
+        names.dedup();
+        ReplHelper { names }
+    }
+
+    pub fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos]
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':' || c == '~'))
+            .map_or(0, |i| i + 1);
+        let prefix = &line[start..pos];
+        if prefix.is_empty() {
+            return (start, Vec::new());
+        }
+        let first = self.names.partition_point(|name| name.as_str() < prefix);
+        let matches = self.names[first..]
+            .iter()
+            .take_while(|name| name.starts_with(prefix))
+            .cloned()
+            .collect();
+        (start, matches)
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+fn history_file() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".code_analysis_history"))
+}
+
+pub fn run(analyzer: &mut Analyzer) -> anyhow::Result<()> {
+    let mut editor: Editor<ReplHelper, FileHistory> = Editor::new()?;
+    editor.set_helper(Some(ReplHelper::new(analyzer)));
+    let history = history_file();
+    if let Some(path) = &history {
+        // there is no history before the first session
+        let _ = editor.load_history(path);
+    }
+
+    let mut stdout = std::io::stdout();
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+        if !line.trim().is_empty() {
+            editor.add_history_entry(line.as_str())?;
+        }
+        let command = match parse_command(&line) {
+            Ok(command) => command,
+            Err(err) => {
+                eprintln!("{}", err);
+                continue;
+            }
+        };
+        let reindex = command == Command::Reindex;
+        match execute(analyzer, command, &mut stdout) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => eprintln!("ERROR: {}", err),
+        }
+        if reindex {
+            editor.set_helper(Some(ReplHelper::new(analyzer)));
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(err) = editor.save_history(path) {
+            log::warn!("could not save history to {}: {}", path.display(), err);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod repl_test;
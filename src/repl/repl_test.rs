@@ -0,0 +1,50 @@
+use super::*;
+use crate::test_support::MockLSPServer;
+
+fn run(analyzer: &mut Analyzer, line: &str) -> String {
+    let mut out = Vec::new();
+    execute(analyzer, parse_command(line).unwrap(), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_parse_command() {
+    assert_eq!(parse_command(" {@main} "), Ok(Command::Query("{@main}".to_string())));
+    assert_eq!(parse_command(":show"), Ok(Command::Show));
+    assert_eq!(parse_command(":export dot out.dot"), Ok(Command::Export(ExportFormat::Dot, "out.dot".to_string())));
+    assert!(parse_command(":export svg out.svg").is_err());
+    assert!(parse_command(":export dot").is_err());
+    assert_eq!(parse_command(":q"), Ok(Command::Quit));
+    assert!(parse_command(":frobnicate").is_err());
+    assert_eq!(parse_command(":prune fan-in=2"), Ok(Command::Prune(Some(PruneSpec::FanIn(2)))));
+    assert_eq!(parse_command(":prune"), Ok(Command::Prune(None)));
+    assert!(parse_command(":prune often").is_err());
+    assert_eq!(parse_command(":param $target=main"), Ok(Command::Param(Some(("target".to_string(), "main".to_string())))));
+    assert!(parse_command(":param target").is_err());
+}
+
+#[test]
+fn test_incremental_queries() {
+    let mut analyzer = Analyzer::new(MockLSPServer::boxed());
+    assert_eq!(run(&mut analyzer, "{@print}"), "+ main -> print\n+ parse -> print\n2 new calls, graph has 3 functions and 2 calls\n");
+    assert_eq!(run(&mut analyzer, "{@parse}"), "+ main -> parse\n1 new calls, graph has 3 functions and 3 calls\n");
+    assert!(run(&mut analyzer, ":show").starts_with("main -> parse\nmain -> print\nparse -> print\n"));
+
+    run(&mut analyzer, ":clear");
+    assert_eq!(run(&mut analyzer, ":show"), "graph has 0 functions and 0 calls\n");
+    assert!(run(&mut analyzer, "{@parse").contains("expected"));
+    assert!(run(&mut analyzer, "{@$target}").contains("$target"));
+    run(&mut analyzer, ":param target=parse");
+    assert_eq!(run(&mut analyzer, ":param"), "$target = parse\n");
+    assert!(run(&mut analyzer, "{@$target}").starts_with("+ main -> parse\n"));
+    assert!(!execute(&mut analyzer, Command::Quit, &mut Vec::new()).unwrap());
+}
+
+#[test]
+fn test_completion() {
+    let mut analyzer = Analyzer::new(MockLSPServer::boxed());
+    let helper = ReplHelper::new(&mut analyzer);
+    assert_eq!(helper.candidates("{@pars", 6), (2, vec!["parse".to_string(), "parse_args".to_string()]));
+    assert_eq!(helper.candidates(":st", 3), (0, vec![":stats".to_string()]));
+    assert_eq!(helper.candidates("{@", 2), (2, Vec::new()));
+}
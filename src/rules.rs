@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::Context;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::call_index::CallIndex;
+use crate::searcher::{CallSite, Links};
+
+// Layering rules, read from a TOML file:
+//
+//   [[rule]]
+//   name = "drivers must not call into the ui"
+//   from = { file = "^drivers/" }
+//   to = { file = "^ui/" }
+//
+//   [[rule]]
+//   name = "only db may use sqlite"
+//   to = { function = "^sqlite3_" }
+//   only_from = { file = "^db/[^/]*\\.c$" }
+//
+// A rule without `only_from` forbids every call from `from` to `to`. With
+// `only_from` calls to `to` are only allowed from functions matching it.
+// Calls to functions outside the index, like a system sqlite, are only seen
+// by rules whose `to` selects functions by name alone.
+#[derive(Deserialize, Debug)]
+struct RuleFile {
+    #[serde(default)]
+    rule: Vec<RuleConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RuleConfig {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "SelectorConfig::is_empty")]
+    pub from: SelectorConfig,
+    pub to: SelectorConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub only_from: Option<SelectorConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SelectorConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+}
+
+impl SelectorConfig {
+    fn is_empty(&self) -> bool {
+        self.file.is_none() && self.function.is_none()
+    }
+}
+
+// Matches a function when all given regexes match.
+#[derive(Debug, Default)]
+pub struct Selector {
+    file: Option<Regex>,
+    function: Option<Regex>,
+}
+
+impl Selector {
+    fn from_config(config: SelectorConfig) -> anyhow::Result<Selector> {
+        let compile = |regex: Option<String>| regex.map(|r| Regex::new(&r)).transpose();
+        Ok(Selector {
+            file: compile(config.file)?,
+            function: compile(config.function)?,
+        })
+    }
+
+    pub fn matches(&self, function: &str, file: Option<&str>) -> bool {
+        let file_matches = match (&self.file, file) {
+            (Some(regex), Some(file)) => regex.is_match(file),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        file_matches && self.function.as_ref().is_none_or(|regex| regex.is_match(function))
+    }
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    pub name: String,
+    from: Selector,
+    to: Selector,
+    only_from: Option<Selector>,
+}
+
+impl Rule {
+    fn is_violated_by(&self, caller: &str, caller_file: Option<&str>, callee: &str, callee_file: Option<&str>) -> bool {
+        if !self.from.matches(caller, caller_file) || !self.to.matches(callee, callee_file) {
+            return false;
+        }
+        match &self.only_from {
+            Some(allowed) => !allowed.matches(caller, caller_file),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub rule: String,
+    pub caller: String,
+    pub caller_file: Option<String>,
+    pub callee: String,
+    pub callee_file: Option<String>,
+    pub call_sites: Vec<CallSite>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = "?".to_string();
+        write!(
+            f,
+            "[{}] {} ({}) calls {} ({})",
+            self.rule,
+            self.caller,
+            self.caller_file.as_ref().unwrap_or(&unknown),
+            self.callee,
+            self.callee_file.as_ref().unwrap_or(&unknown)
+        )?;
+        for call_site in &self.call_sites {
+            write!(f, "\n    at {}", call_site)?;
+        }
+        Ok(())
+    }
+}
+
+impl RuleSet {
+    pub fn from_toml(source: &str) -> anyhow::Result<RuleSet> {
+        let config: RuleFile = toml::from_str(source).context("invalid rule file")?;
+        RuleSet::from_configs(config.rule)
+    }
+
+    pub fn from_configs(configs: Vec<RuleConfig>) -> anyhow::Result<RuleSet> {
+        let mut rules = Vec::new();
+        for rule in configs {
+            let context = format!("invalid regex in rule '{}'", rule.name);
+            rules.push(Rule {
+                from: Selector::from_config(rule.from).context(context.clone())?,
+                to: Selector::from_config(rule.to).context(context.clone())?,
+                only_from: rule.only_from.map(Selector::from_config).transpose().context(context)?,
+                name: rule.name,
+            });
+        }
+        Ok(RuleSet { rules })
+    }
+
+    pub fn load(path: &str) -> anyhow::Result<RuleSet> {
+        let source = std::fs::read_to_string(path).with_context(|| format!("could not read rule file {}", path))?;
+        RuleSet::from_toml(&source)
+    }
+
+    // Calls to functions outside the index that a rule could forbid, the
+    // `to.function` of every rule without `to.file`.
+    pub fn external_callees(&self) -> Option<Regex> {
+        let patterns: Vec<String> = self.rules
+            .iter()
+            .filter(|rule| rule.to.file.is_none())
+            .filter_map(|rule| rule.to.function.as_ref())
+            .map(|regex| format!("(?:{})", regex.as_str()))
+            .collect();
+        if patterns.is_empty() {
+            return None;
+        }
+        Regex::new(&patterns.join("|")).ok()
+    }
+
+    // Every call that breaks a rule. Calls between indexed functions come
+    // without call sites, `external` calls keep theirs. Sorted by rule, caller
+    // and callee.
+    pub fn check(&self, index: &dyn CallIndex, external: &Links) -> Vec<Violation> {
+        let mut files: HashMap<&str, &str> = HashMap::new();
+        for (file, function) in index.definitions() {
+            files.entry(function).or_insert(file);
+        }
+
+        let mut violations = Vec::new();
+        for caller in index.functions() {
+            let caller_file = files.get(caller).copied();
+            for callee in index.callees(caller) {
+                let callee_file = files.get(callee).copied();
+                for rule in &self.rules {
+                    if rule.is_violated_by(caller, caller_file, callee, callee_file) {
+                        violations.push(Violation {
+                            rule: rule.name.clone(),
+                            caller: caller.to_string(),
+                            caller_file: caller_file.map(str::to_string),
+                            callee: callee.to_string(),
+                            callee_file: callee_file.map(str::to_string),
+                            call_sites: Vec::new(),
+                        });
+                    }
+                }
+            }
+        }
+        for ((caller, callee), call_sites) in external {
+            let caller_file = files.get(caller.as_str()).copied();
+            for rule in &self.rules {
+                if rule.is_violated_by(caller, caller_file, callee, None) {
+                    violations.push(Violation {
+                        rule: rule.name.clone(),
+                        caller: caller.clone(),
+                        caller_file: caller_file.map(str::to_string),
+                        callee: callee.clone(),
+                        callee_file: None,
+                        call_sites: call_sites.clone(),
+                    });
+                }
+            }
+        }
+        violations.sort_by(|a, b| (&a.rule, &a.caller, &a.callee).cmp(&(&b.rule, &b.caller, &b.callee)));
+        violations.dedup_by(|a, b| a.rule == b.rule && a.caller == b.caller && a.callee == b.callee);
+        violations
+    }
+}
+
+#[cfg(test)]
+mod rules_test;
@@ -0,0 +1,92 @@
+use super::*;
+use crate::call_index::MapIndex;
+
+const RULES: &str = r#"
+[[rule]]
+name = "drivers must not call into the ui"
+from = { file = "^drivers/" }
+to = { file = "^ui/" }
+
+[[rule]]
+name = "only db may use sqlite"
+to = { function = "^sqlite3_" }
+only_from = { file = "^db/[^/]*\\.c$" }
+"#;
+
+fn strings(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn project() -> MapIndex {
+    let index_map = HashMap::from([
+        ("drivers/uart.c".to_string(), strings(&["uart_irq"])),
+        ("ui/status.c".to_string(), strings(&["show_status"])),
+        ("db/store.c".to_string(), strings(&["store_put"])),
+        ("sqlite/sqlite3.c".to_string(), strings(&["sqlite3_exec"])),
+        ("app/main.c".to_string(), strings(&["main"])),
+    ]);
+    let function_calls = HashMap::from([
+        ("uart_irq".to_string(), strings(&["show_status", "store_put"])),
+        ("store_put".to_string(), strings(&["sqlite3_exec"])),
+        ("main".to_string(), strings(&["store_put", "sqlite3_exec", "show_status"])),
+    ]);
+    MapIndex::new(index_map, function_calls)
+}
+
+#[test]
+fn test_check_reports_violations() {
+    let rules = RuleSet::from_toml(RULES).unwrap();
+    assert_eq!(rules.rules.len(), 2);
+
+    let violations = rules.check(&project(), &Links::new());
+    let edges: Vec<(&str, &str, &str)> = violations
+        .iter()
+        .map(|v| (v.rule.as_str(), v.caller.as_str(), v.callee.as_str()))
+        .collect();
+    assert_eq!(edges, vec![
+        ("drivers must not call into the ui", "uart_irq", "show_status"),
+        ("only db may use sqlite", "main", "sqlite3_exec"),
+    ]);
+    assert_eq!(violations[0].caller_file.as_deref(), Some("drivers/uart.c"));
+}
+
+#[test]
+fn test_rule_file_errors() {
+    assert!(RuleSet::from_toml("[[rule]]\nname = \"missing to\"\n").is_err());
+    assert!(RuleSet::from_toml("[[rule]]\nname = \"bad\"\nto = { file = \"(\" }\n").is_err());
+    assert!(RuleSet::from_toml("").unwrap().rules.is_empty());
+}
+
+#[test]
+fn test_violation_display() {
+    let violation = Violation {
+        rule: "layering".to_string(),
+        caller: "a".to_string(),
+        caller_file: Some("drivers/a.c".to_string()),
+        callee: "b".to_string(),
+        callee_file: None,
+        call_sites: vec![CallSite { file: "drivers/a.c".to_string(), line: 3, column: 5 }],
+    };
+    assert_eq!(violation.to_string(), "[layering] a (drivers/a.c) calls b (?)\n    at drivers/a.c:3:5");
+}
+
+#[test]
+fn test_check_external_callee() {
+    let rules = RuleSet::from_toml(RULES).unwrap();
+    let external_callees = rules.external_callees().unwrap();
+    assert!(external_callees.is_match("sqlite3_prepare_v2"));
+    assert!(!external_callees.is_match("show_status"));
+
+    let call_site = CallSite { file: "app/main.c".to_string(), line: 12, column: 5 };
+    let external = Links::from([
+        (("main".to_string(), "sqlite3_prepare_v2".to_string()), vec![call_site.clone()]),
+        (("store_put".to_string(), "sqlite3_step".to_string()), Vec::new()),
+    ]);
+    let violations = rules.check(&project(), &external);
+    let external_violations: Vec<&Violation> = violations.iter().filter(|v| v.callee != "sqlite3_exec" && v.callee != "show_status").collect();
+    assert_eq!(external_violations.len(), 1);
+    assert_eq!(external_violations[0].caller, "main");
+    assert_eq!(external_violations[0].callee, "sqlite3_prepare_v2");
+    assert_eq!(external_violations[0].callee_file, None);
+    assert_eq!(external_violations[0].call_sites, vec![call_site]);
+}
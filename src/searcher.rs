@@ -1,36 +1,99 @@
 use crate::lang_server::LanguageServer;
 use crate::lang_server;
-use lsp_types::{DocumentSymbolResponse, Range, SymbolKind};
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
 use std::{fmt, fs};
-use std::fs::File;
+use std::path::Path;
 use std::hash::{Hash, Hasher};
-use std::io::prelude::*;
 use chrono::{NaiveTime, Utc};
 use log::{Level, log};
-use serde_json::Value;
 use crate::analyzer::FilterName;
+use crate::binary_index::{self, BinaryIndex};
+use crate::call_index::{CallIndex, MapIndex};
+use crate::cache::IndexProfile;
+use crate::config::SourceFilter;
+use crate::index::IndexOptions;
+use crate::call_scanner::{self, CallScanner};
+use crate::indexer;
+use crate::indexer::{index_function_calls, IndexedDocument, IndexWorker};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+// Location of a call, `line` and `column` start at 1.
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct CallSite {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl fmt::Display for CallSite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+// (caller, callee) -> the places where the caller calls the callee
+pub type Links = HashMap<(String, String), Vec<CallSite>>;
 
 pub trait LSPServer {
-    fn restart(&mut self,);
     fn find_func_name(
         &mut self,
         filter: Vec<HashMap<FilterName, Regex>>,
     ) -> HashSet<FunctionNode>;
-    fn find_link(&mut self, parent_name: HashSet<String>, child_name: HashSet<String>) -> HashSet<(String, String)>;
+    fn find_link(&mut self, parent_name: HashSet<String>, child_name: HashSet<String>) -> Links;
+    // the files, functions and calls of the index, borrowed from the server
+    fn call_index(&mut self) -> Box<dyn CallIndex + '_>;
+    fn address_taken_functions(&mut self) -> HashSet<String>;
+    // (function, first line, last line) of the functions defined in a file, lines start at 1
+    fn function_ranges(&mut self, file: &str) -> Vec<(String, u32, u32)>;
     fn close(&mut self);
+
+    // Picks up files that changed since they were indexed, along with the
+    // `changed` ones. Servers without an index have nothing to reload.
+    fn reindex(&mut self, _changed: &[String]) {}
+
+    // Calls to functions outside the index whose name matches `pattern`, with
+    // their call sites. Servers without sources find none.
+    fn external_calls(&mut self, _pattern: &Regex) -> Links {
+        HashMap::new()
+    }
+
+    fn callers_of(&mut self, name: &str) -> Vec<String> {
+        let mut callers: Vec<String> = self.call_index().callers(name).map(str::to_string).collect();
+        callers.sort();
+        callers.dedup();
+        callers
+    }
+
+    fn callees_of(&mut self, name: &str) -> Vec<String> {
+        self.call_index().callees(name).map(str::to_string).collect()
+    }
+
+    // (file, first line, last line) of the definition of a function
+    fn function_location(&mut self, name: &str) -> Option<(String, u32, u32)> {
+        let mut files: Vec<String> = {
+            let index = self.call_index();
+            index.files().filter(|file| index.file_functions(file).any(|function| function == name)).map(str::to_string).collect()
+        };
+        files.sort();
+        let file = files.into_iter().next()?;
+        let (_, start, end) = self.function_ranges(&file).into_iter().find(|(function, _, _)| function == name)?;
+        Some((file, start, end))
+    }
 }
 
 pub struct ClangdServer {
     pub lang_server: Box<dyn LanguageServer>,
     pub project_path: String,
-    pub index_map: HashMap<String, Vec<String>>,
-    function_index: HashMap<String, Vec<String>>,
-    inv_function_index: HashMap<String, Vec<String>>,
+    call_index: Option<BinaryIndex>,
     use_call_hierarchy_outgoing: bool,
     clangd_path: String,
+    clangd_args: Vec<String>,
+    sources: SourceFilter,
+    profile: IndexProfile,
+    jobs: usize,
     benchmark: (NaiveTime, bool),
 }
 
@@ -88,7 +151,7 @@ impl fmt::Debug for FunctionNode{
 }
 
 pub trait MatchFunctionEdge {
-    fn do_match(&mut self, match_target: FunctionNode, lsp_server: &mut Box<dyn LSPServer>) -> HashSet<(String, String)>;
+    fn do_match(&mut self, match_target: FunctionNode, lsp_server: &mut Box<dyn LSPServer>) -> Links;
     fn get_implementation(&self) -> String;
 }
 
@@ -102,13 +165,13 @@ pub struct ParentChildNode {
 }
 
 impl MatchFunctionEdge for ForcedNode {
-    fn do_match(&mut self, match_target: FunctionNode, lsp_server: &mut Box<dyn LSPServer>) -> HashSet<(String, String)> {
+    fn do_match(&mut self, match_target: FunctionNode, lsp_server: &mut Box<dyn LSPServer>) -> Links {
         #[allow(dead_code)]
         if false { drop(lsp_server); unimplemented!()}
-        let mut result = HashSet::new();
+        let mut result = HashMap::new();
         for child in self.function_name.clone() {
             for parent in match_target.function_name.clone() {
-                result.insert((parent.clone(), child.clone()));
+                result.insert((parent.clone(), child.clone()), Vec::new());
             }
         }
         result
@@ -120,7 +183,7 @@ impl MatchFunctionEdge for ForcedNode {
 }
 
 impl MatchFunctionEdge for ParentChildNode {
-    fn do_match(&mut self, match_target: FunctionNode, lsp_server: &mut Box<dyn LSPServer>) -> HashSet<(String, String)> {
+    fn do_match(&mut self, match_target: FunctionNode, lsp_server: &mut Box<dyn LSPServer>) -> Links {
         lsp_server.find_link(
             match_target.function_name.clone(),
             self.function_name.clone()
@@ -133,364 +196,151 @@ impl MatchFunctionEdge for ParentChildNode {
 }
 
 impl ClangdServer {
-    pub fn new(project_path: String, clangd_path: String, benchmark: (chrono::NaiveTime, bool)) -> Box<dyn LSPServer> {
+    pub fn new(project_path: String, options: &IndexOptions, start: chrono::NaiveTime) -> anyhow::Result<ClangdServer> {
+        anyhow::ensure!(Path::new(&project_path).is_dir(), "project directory {} not found", project_path);
+        let profile = options.profile(&project_path);
+        let benchmark = (start, options.benchmark);
+        let lang_server = lang_server::LanguageServerLauncher::new()
+            .server(options.clangd_path.to_owned())
+            .project(project_path.to_owned())
+            .compile_commands_dir(profile.compile_commands_dir.to_owned())
+            .args(options.clangd_args.clone())
+            .launch()
+            .map_err(|err| anyhow::anyhow!("could not start {}: {}", options.clangd_path, err))?;
         let mut lsp_server = Self {
-            lang_server: lang_server::LanguageServerLauncher::new()
-                .server(clangd_path.to_owned())
-                .project(project_path.to_owned())
-                .launch()
-                .expect("Failed to spawn clangd"),
+            lang_server,
             project_path,
-            index_map: HashMap::new(),
-            function_index: Default::default(),
-            inv_function_index: Default::default(),
+            call_index: None,
             use_call_hierarchy_outgoing: true,
-            clangd_path,
+            clangd_path: options.clangd_path.clone(),
+            clangd_args: options.clangd_args.clone(),
+            sources: options.sources.clone(),
+            profile,
+            jobs: options.jobs,
             benchmark: benchmark
         };
         let res = lsp_server.lang_server.initialize();
         if res.is_err() {
             log!(Level::Error,"LSP server didn't initialize: {:?}", res.err());
         }
-        lsp_server.get_all_files_in_project();
+        lsp_server.get_all_files_in_project()?;
         if benchmark.1 {
             let now = Utc::now().time();
             let diff = now - benchmark.0;
             eprintln!("Time till index is finished: {} ms", diff.num_milliseconds());
-            eprintln!("number of functions: {}", lsp_server.function_index.len())
+            if let Some(index) = lsp_server.call_index.as_ref() {
+                eprintln!("number of files: {}", index.file_count());
+                eprintln!("number of functions: {}", index.symbol_count());
+                eprintln!("number of calls: {}", index.edge_count());
+            }
         }
-        Box::new(lsp_server)
+        Ok(lsp_server)
     }
 
-    pub fn restart_server(&mut self){
-        let shutdown_res = self.lang_server.exit();
-        if shutdown_res.is_err() {
-            log!(Level::Error, "{:?}", shutdown_res.err());
-        }
-        let new_lsp = lang_server::LanguageServerLauncher::new()
-            .server(self.clangd_path.to_owned())
-            .project(self.project_path.to_owned())
-            .launch()
-            .expect("Failed to spawn clangd");
-        self.lang_server = new_lsp;
-
-        let init_res = self.lang_server.initialize();
-        if init_res.is_err() {
-            eprintln!("{:?}", init_res.err());
-        }
+    pub fn get_all_files_in_project(&mut self) -> anyhow::Result<Vec<String>> {
+        let files = self.project_files();
+        self.update_index(&files, &[])?;
+        Ok(files)
     }
 
-    pub fn get_all_files_in_project(&mut self) -> Vec<String> {
-        let files: Vec<String>;
-        let path_to_index = self.project_path.clone() + "/.cache/clangd/index";
-        let index_dir  = fs::read_dir(path_to_index.clone());
-
-        if index_dir.is_ok() {
-            let mut index_file_names: Vec<String> = vec![];
-            for file in index_dir.unwrap() {
-                let mut file_str = file.as_ref().unwrap().path().to_str().unwrap().to_owned();
-                file_str = file_str.replace(&(path_to_index.clone() + "/"), "");
-                file_str = file_str[..file_str.find(".").unwrap()].to_owned();
-                index_file_names.push(file_str);
+    fn project_files(&self) -> Vec<String> {
+        let path_to_index = self.profile.clangd_index_dir();
+        match fs::read_dir(path_to_index.clone()) {
+            Ok(index_dir) => {
+                let mut index_file_names: Vec<String> = vec![];
+                for file in index_dir {
+                    let mut file_str = file.as_ref().unwrap().path().to_str().unwrap().to_owned();
+                    file_str = file_str.replace(&(path_to_index.clone() + "/"), "");
+                    file_str = file_str[..file_str.find(".").unwrap()].to_owned();
+                    index_file_names.push(file_str);
+                }
+                self.get_files_in_dir(self.project_path.clone(), self.project_path.clone(), Some(index_file_names))
             }
-            files = self.get_files_in_dir(self.project_path.clone(), self.project_path.clone(), Some(index_file_names.clone()));
-
-            self.index_map = self.check_index_file(files.clone());
-        } else {
-            files = self.get_files_in_dir(self.project_path.clone(), self.project_path.clone(), None);
-            self.index_map = self.check_index_file(files.clone());
-
-
+            Err(_) => self.get_files_in_dir(self.project_path.clone(), self.project_path.clone(), None),
         }
-        files
     }
 
-    fn check_index_file(&mut self, files: Vec<String>) -> HashMap<String, Vec<String>> {
-        let mut index_map : HashMap<String, Vec<String>> = HashMap::new();
-        let mut range_index: HashMap<String, Vec<Range>> = HashMap::new();
-
-        let path = self.project_path.clone() + "/.cache/index.json";
-        let mut needs_indexing = false;
-        let file =  File::open(&path);
-        if file.is_err() {
-            needs_indexing = true;
-        } else {
-            let mut s = String::new();
-            match file.unwrap().read_to_string(&mut s) {
-                Err(why) => panic!("could not read: {}", why),
-                Ok(_) => {}
-            }
-
-            let json = serde_json::from_str::<Value>(s.as_str());
-            if json.is_err() {
+    // Brings the index in line with `files`. Only files that are new, were
+    // modified since they were indexed or are listed in `changed` go through
+    // clangd, the functions of the other files are taken from the index.
+    fn update_index(&mut self, files: &[String], changed: &[String]) -> anyhow::Result<()> {
+        let path = self.profile.index_file();
+        let previous = match self.call_index.take() {
+            Some(index) => Some(index),
+            None => BinaryIndex::open(&path).map_err(|err| log!(Level::Debug, "{:?}", err)).ok(),
+        };
+        let up_to_date = |index: &BinaryIndex| {
+            index.covers_files(&self.project_path, files) && !changed.iter().any(|file| files.contains(file))
+        };
+        if previous.as_ref().is_some_and(up_to_date) {
+            self.call_index = previous;
+            return Ok(());
+        }
 
-                needs_indexing = true;
-            } else {
-                let json_map = json.unwrap().as_object().unwrap().to_owned();
-                if json_map.len() == files.len() {
-                    for file in json_map{
-                        if files.contains(&file.0) {
-                            let mut functions: Vec<String> = vec![];
-                            let symbols = file.1.as_array().unwrap().to_owned();
-                            for symbol in symbols {
-                                functions.push(symbol.to_string().replace("\"", ""));
-                            }
-                            index_map.insert(file.0, functions.clone());
-                        } else {
+        let stamps: HashMap<String, u64> = files
+            .iter()
+            .map(|file| (file.clone(), binary_index::modified_stamp(&Path::new(&self.project_path).join(file))))
+            .collect();
 
-                            needs_indexing = true;
-                        }
-                    }
-                } else {
-                    needs_indexing = true;
-                }
+        let mut stale = match &previous {
+            Some(index) => index.stale_files(&self.project_path, files),
+            None => files.to_vec(),
+        };
+        for file in changed {
+            if files.contains(file) && !stale.contains(file) {
+                stale.push(file.clone());
             }
-
         }
-
-        let file2 =  File::open(self.project_path.clone() + "/.cache/called.json");
-        if file2.is_err() {
-            needs_indexing = true;
-        } else {
-            let mut s = String::new();
-            match file2.unwrap().read_to_string(&mut s) {
-                Err(why) => panic!("could not read: {}", why),
-                Ok(_) => {}
-            }
-
-            let json2 = serde_json::from_str::<Value>(s.as_str());
-            if json2.is_err() {
-                needs_indexing = true;
-            } else {
-                let json_map = json2.unwrap().as_object().unwrap().to_owned();
-                if json_map.len() > 1 {
-                    for file in json_map{
-                        let mut functions: Vec<String> = vec![];
-                        let symbols = file.1.as_array().unwrap().to_owned();
-                        for symbol in symbols {
-                            functions.push(symbol.to_string().replace("\"", ""));
-                        }
-                        self.function_index.insert(file.0, functions.clone());
-                    }
-                } else {
-                    needs_indexing = true;
+        let mut documents: Vec<IndexedDocument> = Vec::with_capacity(files.len());
+        if let Some(index) = &previous {
+            for file in files.iter().filter(|file| !stale.contains(file)) {
+                let mut document = IndexedDocument { file: file.clone(), functions: Vec::new(), ranges: Vec::new() };
+                for (function, start, end) in index.function_range_names(file) {
+                    document.functions.push(function);
+                    document.ranges.push(lsp_types::Range::new(
+                        lsp_types::Position::new(start.saturating_sub(1), 0),
+                        lsp_types::Position::new(end.saturating_sub(1), 0),
+                    ));
                 }
+                documents.push(document);
             }
-
         }
-        let file3 =  File::open(self.project_path.clone() + "/.cache/caller.json");
-        if file3.is_err() {
-            needs_indexing = true;
-        } else {
-            let mut s = String::new();
-            match file3.unwrap().read_to_string(&mut s) {
-                Err(why) => panic!("could not read: {}", why),
-                Ok(_) => {}
-            }
-
-            let json3 = serde_json::from_str::<Value>(s.as_str());
-            if json3.is_err() {
-                needs_indexing = true;
+        if !stale.is_empty() {
+            if previous.is_some() {
+                eprintln!("reindexing {} changed files", stale.len());
             } else {
-                let json_map = json3.unwrap().as_object().unwrap().to_owned();
-                if json_map.len() > 1 {
-                    for file in json_map{
-                        let mut functions: Vec<String> = vec![];
-                        let symbols = file.1.as_array().unwrap().to_owned();
-                        for symbol in symbols {
-                            functions.push(symbol.to_string().replace("\"", ""));
-                        }
-                        self.inv_function_index.insert(file.0, functions.clone());
-                    }
-                } else {
-                    needs_indexing = true;
-                }
+                eprintln!("start indexing, there should be a message displaying the progress every coupe of seconds, please restart the program if the messages stop unexpectedly");
             }
-
+            documents.extend(self.index_documents(&stale));
         }
 
-        if needs_indexing {
-            let mut i = 0;
-            let mut i_total = 0;
-            eprintln!("start indexing, there should be a message displaying the progress every coupe of seconds, please restart the program if the messages stop unexpectedly");
-            for file in files.clone() {
-                i += 1; i_total += 1;
-                let mut functions:Vec<String> = vec![];
-                let mut ranges: Vec<Range> = vec![];
-
-                if i >= 5 {
-                    //break;
-                    i = 0;
-                    eprintln!("indexing project, please wait ({}/{})", i_total, files.clone().len());
-                    if self.benchmark.1 {
-                        let now = Utc::now().time();
-                        let diff = now - self.benchmark.0;
-                        eprintln!("time till now: {:?}", diff.num_milliseconds());
-                    }
-                    self.restart_server();
-                }
-                let start = Utc::now().time();
-                let document_res = self.lang_server.document_open(file.as_str());
-                if self.benchmark.1 {
-                    let finish = Utc::now().time();
-                    let diff = finish-start;
-                    eprintln!("lsp: document_open: {:?}ns", diff.num_nanoseconds().unwrap());
-                }
-                if document_res.is_ok() {
-                    let document = document_res.unwrap();
-
-                    let start = Utc::now().time();
-                    let doc_symbol = self.lang_server.document_symbol(&document);
-                    if self.benchmark.1 {
-                        let finish = Utc::now().time();
-                        let diff = finish-start;
-                        eprintln!("lsp: document_symbol: {:?}", diff.num_milliseconds());
-                    }
-
-                    if doc_symbol.is_ok() {
-                        match doc_symbol.unwrap() {
-                            Some(DocumentSymbolResponse::Flat(_)) => {
-                                log!(Level::Warn ,"unsupported symbols found");
-                            }
-                            Some(DocumentSymbolResponse::Nested(doc_symbols)) => {
-                                for symbol in doc_symbols {
-                                    if symbol.kind == SymbolKind::FUNCTION {
-                                        let mut func_name = symbol.name;
-                                        while func_name.starts_with('_'){
-                                            func_name = func_name.strip_prefix(&"_".to_string()).unwrap().to_string();
-                                        }
-
-                                        functions.push(func_name);
-                                        ranges.push(symbol.range.clone());
-                                    }
-                                }
-                            }
-                            None => {
-                                log!(Level::Warn, "no symbols found");
-                            }
-                        }
-                    }
-                }
-                index_map.insert(file.clone(), functions);
-                range_index.insert(file.clone(), ranges);
-            }
-            if self.benchmark.1 {
-                let document_res = self.lang_server.document_open("/criu/fsnotify.c");
-                if document_res.is_ok() {
-                    let document = document_res.unwrap();
-                    let doc_symbol = self.lang_server.document_symbol(&document);
-                    if doc_symbol.is_ok() {
-                        match doc_symbol.unwrap() {
-                            Some(DocumentSymbolResponse::Flat(_)) => {}
-                            Some(DocumentSymbolResponse::Nested(doc_symbols)) => {
-                                let symbol = doc_symbols[6].to_owned();
-                                println!("{:?}", symbol);
-                                let start = Utc::now().time();
-                                let hierarchy_item = self.lang_server.call_hierarchy_item(&document, symbol.range.start).unwrap().unwrap()[0].clone();
-                                let finish = Utc::now().time();
-                                let diff = finish-start;
-                                eprintln!("lsp: call_hierarchy_item: {:?}", diff.num_nanoseconds());
-                                let start = Utc::now().time();
-                                self.lang_server.call_hierarchy_item_incoming(hierarchy_item.clone());
-                                let finish = Utc::now().time();
-                                let diff = finish-start;
-                                eprintln!("lsp: call_hierarchy_item_incomming: {:?}", diff.num_nanoseconds());
-                                let start = Utc::now().time();
-                                self.lang_server.call_hierarchy_item_outgoing(hierarchy_item);
-                                let finish = Utc::now().time();
-                                let diff = finish-start;
-                                eprintln!("lsp: call_hierarchy_item_outgoing: {:?}", diff.num_nanoseconds());
-
-                            }
-                            None => {}
-                        }
-                    }
-                }
-            }
-
-            let new_json = serde_json::to_string(&index_map).unwrap();
-            let mut file_ref = File::create(path).expect("create failed");
-            file_ref.write_all(new_json.as_bytes()).expect("write failed");
-
-            eprintln!("Done Step 1. Now indexing all the function calls. Please wait a little further");
-            let mut i = 0;
-            for document in index_map.clone() {
-                i += 1;
-                if i % 25 == 0 {
-                    eprintln!("indexing functions, please wait ({}%)", i*100/index_map.len());
-
-                }
-                let ranges = range_index.get(document.0.clone().as_str()).unwrap().to_owned();
-                let functions = document.1.clone();
-                let max = ranges.len();
-                for i in 0..max {
-                    let name = functions[i].clone();
-                    let range = ranges[i].clone();
-                    let mut called_functions = Vec::new();
-
-                    let doc_path = self.project_path.clone() + "/" + document.0.clone().as_str();
-                    let file =  File::open(&doc_path);
-                    if file.is_err() {
-                        needs_indexing = true;
-                    } else {
-                        let mut s = String::new();
-                        match file.unwrap().read_to_string(&mut s) {
-                            Err(why) => panic!("could not read: {}", why),
-                            Ok(_) => {}
-                        }
-                        let doc_lines: Vec<&str> = s.split("\n").collect();
-                        let start: usize = (range.start.line + 1) as usize;
-                        let end: usize = range.end.line as usize;
-                        if start < end {
-                            let function_data = doc_lines[start..end].concat();
-                            for doc_2 in index_map.clone() {
-                                let func_names = doc_2.1;
-                                for func_name in func_names {
-                                    let search_name = func_name.clone() + "(";
-                                    if function_data.contains(&search_name) {
-                                        let mut ignore = false;
-                                        let find = function_data.find("#");
-                                        if find.is_some() {
-                                            if find.unwrap() != 0 {
-                                                let name_i = function_data.find(&search_name).unwrap();
-                                                if find.unwrap() < name_i &&
-                                                    !(function_data[find.unwrap()..name_i].contains("\n")||
-                                                        function_data[find.unwrap()..name_i].contains("\t")) {
-                                                    ignore = true;
-                                                }
-                                            }
-                                        }
-                                        if !ignore {
-                                            called_functions.push(func_name.clone());
-                                            let mut caller_function: Vec<String> = Vec::new();
-                                            if self.inv_function_index.contains_key(func_name.clone().as_str()) {
-                                                caller_function = self.inv_function_index.get(func_name.clone().as_str()).unwrap().to_owned();
-                                            }
-                                            caller_function.push(name.clone());
-                                            self.inv_function_index.insert(func_name.clone(), caller_function.clone());
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    self.function_index.insert(name, called_functions);
-                }
-            }
-
+        eprintln!("Done Step 1. Now indexing all the function calls. Please wait a little further");
+        let function_index = index_function_calls(&self.project_path, &documents, self.jobs);
+        let mut index_map = HashMap::new();
+        let mut function_ranges = HashMap::new();
+        for document in documents {
+            let ranges = document.ranges.iter().map(|range| (range.start.line + 1, range.end.line + 1)).collect();
+            function_ranges.insert(document.file.clone(), ranges);
+            index_map.insert(document.file, document.functions);
+        }
 
-            let new_json2 = serde_json::to_string(&self.function_index).unwrap();
-            let mut file_ref2 = File::create(self.project_path.clone() + "/.cache/called.json").expect("create failed");
-            file_ref2.write_all(new_json2.as_bytes()).expect("write failed");
-            let new_json3 = serde_json::to_string(&self.inv_function_index).unwrap();
-            let mut file_ref3 = File::create(self.project_path.clone() + "/.cache/caller.json").expect("create failed");
-            file_ref3.write_all(new_json3.as_bytes()).expect("write failed");
+        // the previous index stays mapped until the new one replaces it
+        BinaryIndex::write(&path, &index_map, &function_ranges, &function_index, &stamps)
+            .with_context(|| format!("could not write the index {}", path.display()))?;
+        drop(previous);
+        self.call_index = Some(BinaryIndex::open(&path)?);
+        Ok(())
+    }
 
-        }
-        else {
-            //eprintln!("done loading index files");
-        }
-        index_map
+    fn index_documents(&self, files: &[String]) -> Vec<IndexedDocument> {
+        let worker = IndexWorker {
+            clangd_path: self.clangd_path.clone(),
+            clangd_args: self.clangd_args.clone(),
+            project_path: self.project_path.clone(),
+            compile_commands_dir: self.profile.compile_commands_dir.clone(),
+            benchmark: self.benchmark,
+        };
+        indexer::index_documents(&worker, files, self.jobs)
     }
 
     fn get_files_in_dir(&self, dir: String, project_path: String, index_file_name: Option<Vec<String>>) -> Vec<String> {
@@ -513,15 +363,17 @@ impl ClangdServer {
                         name = name[..name.find(".").unwrap()].to_owned();
 
                         if index_file_name.clone().unwrap().contains(&name){
-                            if path_str.ends_with(".cpp") || path_str.ends_with(".c") {
-                                files.push(path_str.replace(&(project_path.clone().as_str().to_owned() + "/"), ""));
+                            let file = path_str.replace(&(project_path.clone().as_str().to_owned() + "/"), "");
+                            if self.sources.matches(&file) {
+                                files.push(file);
                             }
                         }
                     }
 
                 } else {
-                    if path_str.ends_with(".cpp") || path_str.ends_with(".c") {
-                        files.push(path_str.replace(&(project_path.clone().as_str().to_owned() + "/"), ""));
+                    let file = path_str.replace(&(project_path.clone().as_str().to_owned() + "/"), "");
+                    if self.sources.matches(&file) {
+                        files.push(file);
                     }
                 }
             }
@@ -531,122 +383,217 @@ impl ClangdServer {
 }
 
 impl LSPServer for ClangdServer {
-    fn restart(&mut self) {
-        self.restart_server();
+    fn reindex(&mut self, changed: &[String]) {
+        let files = self.project_files();
+        if let Err(err) = self.update_index(&files, changed) {
+            log!(Level::Error, "{:#}", err);
+        }
     }
 
     fn find_func_name(
         &mut self,
         filter: Vec<HashMap<FilterName, Regex>>,
     ) -> HashSet<FunctionNode> {
-        let mut func_nodes:HashSet<FunctionNode> = HashSet::new();
+        find_functions(&self.call_index(), filter)
+    }
 
-        for f in filter {
+    fn find_link(&mut self, parent_name: HashSet<String>, child_name: HashSet<String>) -> Links {
+        match &self.call_index {
+            Some(index) => find_index_links(index, parent_name, child_name),
+            None => HashMap::new(),
+        }
+    }
 
-            let mut forced = false;
-            let mut only_ident = false;
-            let mut ident = String::new();
-            let mut file_filter = Regex::new(".").unwrap();
-            let mut function_filter = Regex::new(".").unwrap();
+    fn call_index(&mut self) -> Box<dyn CallIndex + '_> {
+        match &self.call_index {
+            Some(index) => Box::new(index),
+            None => Box::new(MapIndex::default()),
+        }
+    }
 
+    fn address_taken_functions(&mut self) -> HashSet<String> {
+        let project_path = self.project_path.clone();
+        address_taken(&project_path, &self.call_index())
+    }
 
-            if f.contains_key(&FilterName::Forced) {
-                forced = true;
-            }
+    fn external_calls(&mut self, pattern: &Regex) -> Links {
+        match &self.call_index {
+            Some(index) => external_calls(&self.project_path, index, pattern),
+            None => HashMap::new(),
+        }
+    }
 
-            if f.contains_key(&FilterName::FunctionNameFromIdent) {
-                ident = f.get(&FilterName::FunctionNameFromIdent).unwrap().to_string();
-                only_ident = true;
-            }
+    fn function_ranges(&mut self, file: &str) -> Vec<(String, u32, u32)> {
+        match &self.call_index {
+            Some(index) => index.function_range_names(file),
+            None => Vec::new(),
+        }
+    }
 
-            if f.contains_key(&FilterName::File) {
-                let regex = f.get(&FilterName::File).unwrap();
-                file_filter = regex.to_owned();
-            }
+    fn callers_of(&mut self, name: &str) -> Vec<String> {
+        self.call_index.as_ref().map(|index| index.caller_names(name)).unwrap_or_default()
+    }
 
-            if f.contains_key(&FilterName::Function) {
-                let regex = f.get(&FilterName::Function).unwrap();
-                function_filter = regex.to_owned();
-            }
+    fn callees_of(&mut self, name: &str) -> Vec<String> {
+        self.call_index.as_ref().map(|index| index.callee_names(name)).unwrap_or_default()
+    }
 
-            let mut function_names: HashSet<String> = HashSet::new();
+    fn function_location(&mut self, name: &str) -> Option<(String, u32, u32)> {
+        self.call_index.as_ref()?.function_location(name)
+    }
 
-            for document in self.index_map.clone() {
-                let file = document.0;
-                if file_filter.is_match(file.as_str()) {
-                    for function in document.1.clone() {
-                        let mut found = false;
-                        if only_ident {
-                            if ident == function {
-                                found = true
-                            }
-                        } else {
-                            if function_filter.is_match(function.as_str()) {
-                                found = true;
-                            }
+    fn close(&mut self){
+        log!(Level::Info, "{:?}", self.lang_server.shutdown());
+        log!(Level::Info, "{:?}", self.lang_server.exit());
+    }
+}
+
+// The functions of the index that match each filter of a verb.
+pub(crate) fn find_functions(
+    index: &dyn CallIndex,
+    filter: Vec<HashMap<FilterName, Regex>>,
+) -> HashSet<FunctionNode> {
+    let mut func_nodes:HashSet<FunctionNode> = HashSet::new();
+
+    for f in filter {
+
+        let mut forced = false;
+        let mut only_ident = false;
+        let mut ident = String::new();
+        let mut file_filter = Regex::new(".").unwrap();
+        let mut function_filter = Regex::new(".").unwrap();
+
+
+        if f.contains_key(&FilterName::Forced) {
+            forced = true;
+        }
+
+        if f.contains_key(&FilterName::FunctionNameFromIdent) {
+            ident = f.get(&FilterName::FunctionNameFromIdent).unwrap().to_string();
+            only_ident = true;
+        }
+
+        if f.contains_key(&FilterName::File) {
+            let regex = f.get(&FilterName::File).unwrap();
+            file_filter = regex.to_owned();
+        }
+
+        if f.contains_key(&FilterName::Function) {
+            let regex = f.get(&FilterName::Function).unwrap();
+            function_filter = regex.to_owned();
+        }
+
+        let mut function_names: HashSet<String> = HashSet::new();
+
+        for file in index.files() {
+            if file_filter.is_match(file) {
+                for function in index.file_functions(file) {
+                    let mut found = false;
+                    if only_ident {
+                        if ident == function {
+                            found = true
                         }
-                        if found {
-                            function_names.insert(function.clone());
+                    } else {
+                        if function_filter.is_match(function) {
+                            found = true;
                         }
                     }
+                    if found {
+                        function_names.insert(function.to_string());
+                    }
                 }
             }
-            if function_names.len() == 0 && only_ident {
-                function_names.insert(ident);
-            }
-            if function_names.len() > 0 {
-                if forced {
-                    let node = ForcedNode {
-                        function_name: function_names.clone(),
-                    };
-                    func_nodes.insert(FunctionNode { function_name: function_names.clone(), match_strategy: Box::new(node) });
-                } else {
-                    let node = ParentChildNode {
-                        function_name: function_names.clone(),
-                    };
-                    func_nodes.insert(FunctionNode { function_name: function_names.clone(), match_strategy: Box::new(node) });
-                }
+        }
+        if function_names.len() == 0 && only_ident {
+            function_names.insert(ident);
+        }
+        if function_names.len() > 0 {
+            if forced {
+                let node = ForcedNode {
+                    function_name: function_names.clone(),
+                };
+                func_nodes.insert(FunctionNode { function_name: function_names.clone(), match_strategy: Box::new(node) });
+            } else {
+                let node = ParentChildNode {
+                    function_name: function_names.clone(),
+                };
+                func_nodes.insert(FunctionNode { function_name: function_names.clone(), match_strategy: Box::new(node) });
             }
         }
-
-        func_nodes
     }
 
-    fn find_link(&mut self, parent_name: HashSet<String>, child_name: HashSet<String>) -> HashSet<(String, String)> {
-        //println!("{:?} -> {:?}", parent_name, child_name);
-        let mut connections : HashSet<(String, String)> = HashSet::new();
+    func_nodes
+}
 
+// The calls from `parent_name` to `child_name` with their call sites.
+pub(crate) fn find_index_links(index: &BinaryIndex, parent_name: HashSet<String>, child_name: HashSet<String>) -> Links {
+    let mut connections : Links = HashMap::new();
 
-        if parent_name.len() > child_name.len() {
-            for child in child_name{
-                if self.inv_function_index.contains_key(child.clone().as_str()){
-                    let caller_names = self.inv_function_index.get(child.clone().as_str()).unwrap().to_owned();
-                    for name in caller_names {
-                        if parent_name.contains(name.clone().as_str()){
-                            let p_name = parent_name.get(name.clone().as_str()).unwrap().to_owned();
-                            connections.insert((p_name.clone(),child.clone()));
-                        }
-                    }
+    if parent_name.len() > child_name.len() {
+        for child in child_name{
+            for name in index.caller_names(child.as_str()) {
+                if parent_name.contains(name.as_str()){
+                    let call_sites = index.call_site_names(&name, &child);
+                    connections.insert((name, child.clone()), call_sites);
                 }
             }
-        } else {
-            for parent in parent_name {
-                if self.function_index.contains_key(parent.clone().as_str()){
-                    let called_names = self.function_index.get(parent.clone().as_str()).unwrap().to_owned();
-                    for name in called_names {
-                        if child_name.contains(name.clone().as_str()){
-                            let c_name = child_name.get(name.clone().as_str()).unwrap().to_owned();
-                            connections.insert((parent.clone(),c_name.clone()));
-                        }
-                    }
+        }
+    } else {
+        for parent in parent_name {
+            for name in index.callee_names(parent.as_str()) {
+                if child_name.contains(name.as_str()){
+                    let call_sites = index.call_site_names(&parent, &name);
+                    connections.insert((parent.clone(), name), call_sites);
                 }
             }
         }
-        connections
     }
+    connections
+}
 
-    fn close(&mut self){
-        log!(Level::Info, "{:?}", self.lang_server.shutdown());
-        log!(Level::Info, "{:?}", self.lang_server.exit());
+// Functions whose address is taken somewhere in the indexed files.
+pub(crate) fn address_taken(project_path: &str, index: &dyn CallIndex) -> HashSet<String> {
+    let names: Vec<String> = index.definitions().map(|(_, function)| function.to_string()).collect();
+    let scanner = CallScanner::new(&names);
+    let mut address_taken = HashSet::new();
+    for file in index.files() {
+        let path = project_path.to_string() + "/" + file;
+        match fs::read_to_string(&path) {
+            Ok(source) => {
+                for reference in scanner.scan_references(&source) {
+                    address_taken.insert(names[reference.function].clone());
+                }
+            }
+            Err(err) => log!(Level::Warn, "could not read {}: {}", path, err),
+        }
+    }
+    address_taken
+}
+
+// Calls from indexed functions to functions outside the index whose name
+// matches `pattern`, found by scanning the indexed files.
+pub(crate) fn external_calls(project_path: &str, index: &BinaryIndex, pattern: &Regex) -> Links {
+    let mut links: Links = HashMap::new();
+    for file in index.files() {
+        let path = project_path.to_string() + "/" + file;
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(err) => {
+                log!(Level::Warn, "could not read {}: {}", path, err);
+                continue;
+            }
+        };
+        let ranges = index.function_range_names(file);
+        call_scanner::scan_calls(&source, |callee, line, column| {
+            if !pattern.is_match(callee) || index.has_function(callee) {
+                return;
+            }
+            let line = line as u32 + 1;
+            if let Some((caller, _, _)) = ranges.iter().find(|(_, start, end)| *start <= line && line <= *end) {
+                let call_site = CallSite { file: file.to_string(), line, column: column as u32 + 1 };
+                links.entry((caller.clone(), callee.to_string())).or_default().push(call_site);
+            }
+        });
     }
+    links
 }
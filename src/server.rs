@@ -0,0 +1,293 @@
+use std::collections::{BTreeMap, HashMap};
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use actix_files::Files;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use anyhow::Context;
+use futures::channel::oneshot;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::analyzer::Analyzer;
+use crate::export::ExportFormat;
+use crate::graphql::{self, GraphContext};
+use crate::prune::PruneSpec;
+use crate::query::Query;
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    // indexed file names are relative to the project
+    pub project_path: PathBuf,
+    // serves the web viewer from this directory instead of the copy built
+    // into the binary
+    pub viewer_dir: Option<PathBuf>,
+}
+
+impl ServerConfig {
+    pub fn new(project_path: &str) -> ServerConfig {
+        ServerConfig { project_path: PathBuf::from(project_path), viewer_dir: None }
+    }
+}
+
+// (path, content type, content) of the web viewer built into the binary
+const VIEWER: &[(&str, &str, &str)] = &[
+    ("index.html", "text/html; charset=utf-8", include_str!("../static/viewer/index.html")),
+    ("viewer.css", "text/css; charset=utf-8", include_str!("../static/viewer/viewer.css")),
+    ("viewer.js", "application/javascript; charset=utf-8", include_str!("../static/viewer/viewer.js")),
+];
+
+type Job = Box<dyn FnOnce(&GraphContext) + Send>;
+
+// The language server is not thread safe, so one thread owns the analyzer and
+// requests are queued to it. The index is loaded once when the thread starts,
+// spawning fails if it cannot be loaded.
+#[derive(Clone)]
+pub struct AnalyzerWorker {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl AnalyzerWorker {
+    pub fn spawn<F>(create_analyzer: F) -> anyhow::Result<(AnalyzerWorker, thread::JoinHandle<()>)>
+    where
+        F: FnOnce() -> anyhow::Result<Analyzer> + Send + 'static,
+    {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let (ready, started) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let analyzer = match create_analyzer() {
+                Ok(analyzer) => analyzer,
+                Err(err) => {
+                    let _ = ready.send(Err(err));
+                    return;
+                }
+            };
+            let _ = ready.send(Ok(()));
+            let context = GraphContext::new(analyzer);
+            for job in receiver {
+                // a failing query must not take the index down with it
+                if std::panic::catch_unwind(AssertUnwindSafe(|| job(&context))).is_err() {
+                    log::error!("request panicked");
+                }
+            }
+            context.into_analyzer().close_lsp();
+        });
+        started.recv().map_err(|_| anyhow::anyhow!("the analyzer thread panicked"))??;
+        Ok((AnalyzerWorker { jobs }, handle))
+    }
+
+    pub async fn run<R, F>(&self, job: F) -> anyhow::Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&GraphContext) -> R + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move |context| {
+                let _ = sender.send(job(context));
+            }))
+            .map_err(|_| anyhow::anyhow!("the analyzer has stopped"))?;
+        receiver.await.map_err(|_| anyhow::anyhow!("the request failed"))
+    }
+}
+
+#[derive(Deserialize)]
+struct QueryParams {
+    format: Option<String>,
+    prune: Option<String>,
+    // `$name=value` binds a parameter of the query
+    #[serde(flatten)]
+    rest: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct FunctionsParams {
+    filter: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SourceParams {
+    function: String,
+}
+
+#[derive(Deserialize)]
+struct GraphQLRequest {
+    query: String,
+}
+
+fn error(status: actix_web::http::StatusCode, message: impl ToString) -> HttpResponse {
+    HttpResponse::build(status).json(serde_json::json!({ "error": message.to_string() }))
+}
+
+fn internal_error(err: anyhow::Error) -> HttpResponse {
+    error(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, err)
+}
+
+async fn health(worker: web::Data<AnalyzerWorker>) -> HttpResponse {
+    let counts = worker
+        .run(|context| {
+            let mut analyzer = context.analyzer();
+            let index = analyzer.lang_server().call_index();
+            (index.files().count(), index.definitions().count())
+        })
+        .await;
+    match counts {
+        Ok((files, functions)) => HttpResponse::Ok().json(serde_json::json!({ "status": "ok", "files": files, "functions": functions })),
+        Err(err) => internal_error(err),
+    }
+}
+
+// POST /query?format=json|dot&prune=SPEC&$NAME=VALUE with the query in the body.
+async fn query(worker: web::Data<AnalyzerWorker>, params: web::Query<QueryParams>, body: String) -> HttpResponse {
+    let format: ExportFormat = match params.format.as_deref().unwrap_or("json").parse() {
+        Ok(format) => format,
+        Err(err) => return error(actix_web::http::StatusCode::BAD_REQUEST, err),
+    };
+    let mut query = match Query::parse(&body) {
+        Ok(query) => query,
+        Err(err) => return error(actix_web::http::StatusCode::BAD_REQUEST, err),
+    };
+    if let Some(prune) = params.prune.as_deref() {
+        match prune.parse::<PruneSpec>() {
+            Ok(spec) => query = query.with_pruning(spec),
+            Err(err) => return error(actix_web::http::StatusCode::BAD_REQUEST, err),
+        }
+    }
+    for (name, value) in &params.rest {
+        if let Some(name) = name.strip_prefix('$') {
+            query = query.bind(name, value);
+        }
+    }
+    if let Err(err) = query.check_parameters() {
+        return error(actix_web::http::StatusCode::BAD_REQUEST, err);
+    }
+    let content_type = format.exporter().content_type();
+    let result = worker
+        .run(move |context| {
+            let graph = context.analyzer().run(&query).map_err(|err| err.to_string())?;
+            format.exporter().export(&graph).map_err(|err| err.to_string())
+        })
+        .await;
+    match result {
+        Ok(Ok(output)) => HttpResponse::Ok().content_type(content_type).body(output),
+        Ok(Err(err)) => error(actix_web::http::StatusCode::BAD_REQUEST, err),
+        Err(err) => internal_error(err),
+    }
+}
+
+// GET /functions?filter=REGEX, every matching function with its file.
+async fn functions(worker: web::Data<AnalyzerWorker>, params: web::Query<FunctionsParams>) -> HttpResponse {
+    let filter = match Regex::new(params.filter.as_deref().unwrap_or(".")) {
+        Ok(filter) => filter,
+        Err(err) => return error(actix_web::http::StatusCode::BAD_REQUEST, err),
+    };
+    let result = worker
+        .run(move |context| {
+            let mut analyzer = context.analyzer();
+            let index = analyzer.lang_server().call_index();
+            let mut functions = BTreeMap::new();
+            for (file, name) in index.definitions().filter(|(_, name)| filter.is_match(name)) {
+                functions.entry(name.to_string()).or_insert_with(|| file.to_string());
+            }
+            functions
+        })
+        .await;
+    match result {
+        Ok(functions) => {
+            let functions: Vec<serde_json::Value> = functions
+                .into_iter()
+                .map(|(name, file)| serde_json::json!({ "name": name, "file": file }))
+                .collect();
+            HttpResponse::Ok().json(functions)
+        }
+        Err(err) => internal_error(err),
+    }
+}
+
+// GET /source?function=NAME, the lines of a function in its file.
+async fn source(worker: web::Data<AnalyzerWorker>, config: web::Data<ServerConfig>, params: web::Query<SourceParams>) -> HttpResponse {
+    let name = params.into_inner().function;
+    let lookup = name.clone();
+    let location = match worker.run(move |context| context.analyzer().lang_server().function_location(&lookup)).await {
+        Ok(Some(location)) => location,
+        Ok(None) => return error(actix_web::http::StatusCode::NOT_FOUND, format!("no location for {}", name)),
+        Err(err) => return internal_error(err),
+    };
+    let (file, start, end) = location;
+    let text = match std::fs::read_to_string(config.project_path.join(&file)) {
+        Ok(text) => text,
+        Err(err) => return error(actix_web::http::StatusCode::NOT_FOUND, format!("could not read {}: {}", file, err)),
+    };
+    let lines: Vec<&str> = text.lines().skip(start.saturating_sub(1) as usize).take((end + 1).saturating_sub(start) as usize).collect();
+    HttpResponse::Ok().json(serde_json::json!({
+        "name": name,
+        "file": file,
+        "start_line": start,
+        "end_line": end,
+        "lines": lines,
+    }))
+}
+
+async fn graphql(worker: web::Data<AnalyzerWorker>, request: web::Json<GraphQLRequest>) -> HttpResponse {
+    let request = request.into_inner();
+    match worker.run(move |context| graphql::execute(&graphql::schema(), context, &request.query)).await {
+        Ok(response) => HttpResponse::Ok().content_type("application/json").body(response),
+        Err(err) => internal_error(err),
+    }
+}
+
+async fn viewer(path: web::Path<String>) -> HttpResponse {
+    let path = path.into_inner();
+    let path = if path.is_empty() { "index.html" } else { path.as_str() };
+    match VIEWER.iter().find(|(name, _, _)| *name == path) {
+        Some((_, content_type, content)) => HttpResponse::Ok().content_type(*content_type).body(*content),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+pub fn configure(worker: AnalyzerWorker, server_config: ServerConfig) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |config| {
+        config
+            .app_data(web::Data::new(worker))
+            .app_data(web::Data::new(server_config.clone()))
+            .route("/health", web::get().to(health))
+            .route("/query", web::post().to(query))
+            .route("/functions", web::get().to(functions))
+            .route("/source", web::get().to(source))
+            .route("/graphql", web::post().to(graphql));
+        // registered last, it answers every path that is not an endpoint
+        match server_config.viewer_dir.as_ref().filter(|dir| dir.is_dir()) {
+            Some(dir) => {
+                config.service(Files::new("/", dir).index_file("index.html"));
+            }
+            None => {
+                config.route("/{path:.*}", web::get().to(viewer));
+            }
+        }
+    }
+}
+
+pub fn serve<F>(address: &str, server_config: ServerConfig, create_analyzer: F) -> anyhow::Result<()>
+where
+    F: FnOnce() -> anyhow::Result<Analyzer> + Send + 'static,
+{
+    if let Some(dir) = server_config.viewer_dir.as_ref().filter(|dir| !dir.is_dir()) {
+        log::warn!("viewer directory {} not found, serving the built in viewer", dir.display());
+    }
+    let (worker, handle) = AnalyzerWorker::spawn(create_analyzer)?;
+    let server_worker = worker.clone();
+    let server = HttpServer::new(move || App::new().configure(configure(server_worker.clone(), server_config.clone())))
+        .bind(address)
+        .with_context(|| format!("could not listen on {}", address))?;
+    log::info!("serving queries on {}", address);
+    actix_web::rt::System::new().block_on(server.run())?;
+
+    // the worker thread closes clangd once the last sender is gone
+    drop(worker);
+    handle.join().map_err(|_| anyhow::anyhow!("the analyzer thread panicked"))
+}
+
+#[cfg(test)]
+mod server_test;
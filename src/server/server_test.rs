@@ -0,0 +1,129 @@
+use super::*;
+use crate::test_support::MockLSPServer;
+use actix_web::test;
+
+fn worker() -> AnalyzerWorker {
+    AnalyzerWorker::spawn(|| Ok(Analyzer::new(MockLSPServer::boxed()))).unwrap().0
+}
+
+fn config() -> ServerConfig {
+    ServerConfig::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+#[actix_web::test]
+async fn test_health() {
+    let app = test::init_service(App::new().configure(configure(worker(), config()))).await;
+    let response: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/health").to_request()).await;
+    assert_eq!(response, serde_json::json!({ "status": "ok", "files": 2, "functions": 4 }));
+}
+
+#[actix_web::test]
+async fn test_functions() {
+    let app = test::init_service(App::new().configure(configure(worker(), config()))).await;
+    let request = test::TestRequest::get().uri("/functions?filter=parse").to_request();
+    let response: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(
+        response,
+        serde_json::json!([{ "name": "parse", "file": "parse.c" }, { "name": "parse_args", "file": "parse.c" }])
+    );
+}
+
+#[actix_web::test]
+async fn test_query() {
+    let app = test::init_service(App::new().configure(configure(worker(), config()))).await;
+    let request = test::TestRequest::post().uri("/query").set_payload("{@parse}").to_request();
+    let response: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(response["edges"][0]["caller"], "main");
+    assert_eq!(response["edges"][0]["callee"], "parse");
+
+    let request = test::TestRequest::post().uri("/query?format=dot").set_payload("{@parse}").to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.headers().get("content-type").unwrap(), "text/vnd.graphviz");
+    let body = test::read_body(response).await;
+    assert!(String::from_utf8_lossy(&body).starts_with("digraph"));
+
+    let request = test::TestRequest::post().uri("/query?prune=none").set_payload("{@parse}").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 200);
+
+    let request = test::TestRequest::post().uri("/query?%24target=parse").set_payload("{@$target}").to_request();
+    let response: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(response["edges"][0]["callee"], "parse");
+    let request = test::TestRequest::post().uri("/query").set_payload("{@$target}").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_query_errors() {
+    let app = test::init_service(App::new().configure(configure(worker(), config()))).await;
+    let request = test::TestRequest::post().uri("/query").set_payload("{@parse").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 400);
+
+    let request = test::TestRequest::post().uri("/query?format=svg").set_payload("{@parse}").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 400);
+
+    let request = test::TestRequest::post().uri("/query?prune=often").set_payload("{@parse}").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_graphql() {
+    let app = test::init_service(App::new().configure(configure(worker(), config()))).await;
+    let request = test::TestRequest::post()
+        .uri("/graphql")
+        .set_json(serde_json::json!({ "query": "{ function(name: \"parse\") { callers { totalCount } } }" }))
+        .to_request();
+    let response: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(response["data"]["function"]["callers"]["totalCount"], 1);
+}
+
+#[actix_web::test]
+async fn test_source() {
+    let project = std::env::temp_dir().join(format!("code_analysis_server_{}", std::process::id()));
+    std::fs::create_dir_all(&project).unwrap();
+    std::fs::write(project.join("parse.c"), "#include <stdio.h>\nint parse() {\n    return 0;\n}\n").unwrap();
+    let config = ServerConfig::new(project.to_str().unwrap());
+    let app = test::init_service(App::new().configure(configure(worker(), config))).await;
+
+    let request = test::TestRequest::get().uri("/source?function=parse").to_request();
+    let response: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(response["file"], "parse.c");
+    assert_eq!(response["lines"], serde_json::json!(["int parse() {", "    return 0;", "}"]));
+
+    let request = test::TestRequest::get().uri("/source?function=main").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 404);
+    std::fs::remove_dir_all(project).unwrap();
+}
+
+#[actix_web::test]
+async fn test_viewer() {
+    let app = test::init_service(App::new().configure(configure(worker(), config()))).await;
+    let response = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+    assert!(response.status().is_success());
+    let body = test::read_body(response).await;
+    assert!(String::from_utf8_lossy(&body).contains("viewer.js"));
+
+    let response = test::call_service(&app, test::TestRequest::get().uri("/viewer.js").to_request()).await;
+    assert_eq!(response.headers().get("content-type").unwrap(), "application/javascript; charset=utf-8");
+    let response = test::call_service(&app, test::TestRequest::get().uri("/missing.js").to_request()).await;
+    assert_eq!(response.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_viewer_dir_override() {
+    let dir = std::env::temp_dir().join(format!("code-analysis-viewer-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("index.html"), "custom viewer").unwrap();
+    let mut config = config();
+    config.viewer_dir = Some(dir.clone());
+
+    let app = test::init_service(App::new().configure(configure(worker(), config))).await;
+    let body = test::call_and_read_body(&app, test::TestRequest::get().uri("/").to_request()).await;
+    assert_eq!(body, "custom viewer");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[actix_web::test]
+async fn test_worker_fails_without_analyzer() {
+    let err = AnalyzerWorker::spawn(|| Err(anyhow::anyhow!("no index"))).err().unwrap();
+    assert_eq!(err.to_string(), "no index");
+}
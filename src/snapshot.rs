@@ -0,0 +1,159 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::graph::{EdgeChange, Graph};
+use crate::searcher::CallSite;
+
+// A query result or the whole index, saved as JSON so two revisions of a
+// project can be compared later without clangd.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Snapshot {
+    pub nodes: BTreeSet<String>,
+    pub edges: Vec<SnapshotEdge>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnapshotEdge {
+    pub caller: String,
+    pub callee: String,
+    pub call_sites: Vec<CallSite>,
+}
+
+impl Snapshot {
+    pub fn from_graph(graph: &Graph) -> Snapshot {
+        let nodes = graph.nodes().map(|node| node.name.clone()).collect();
+        let mut edges: Vec<SnapshotEdge> = graph
+            .edges()
+            .map(|(caller, callee, edge)| SnapshotEdge {
+                caller: caller.to_string(),
+                callee: callee.to_string(),
+                call_sites: edge.call_sites.clone(),
+            })
+            .collect();
+        edges.sort_by(|a, b| (&a.caller, &a.callee).cmp(&(&b.caller, &b.callee)));
+        Snapshot { nodes, edges }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("could not write snapshot {}", path.display()))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Snapshot> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).with_context(|| format!("could not read snapshot {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("{} is not a snapshot", path.display()))
+    }
+
+    fn edge_map(&self) -> BTreeMap<(&str, &str), &SnapshotEdge> {
+        self.edges.iter().map(|edge| ((edge.caller.as_str(), edge.callee.as_str()), edge)).collect()
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SnapshotDiff {
+    pub added_nodes: Vec<String>,
+    pub removed_nodes: Vec<String>,
+    pub added_edges: Vec<SnapshotEdge>,
+    pub removed_edges: Vec<SnapshotEdge>,
+    // edges in both snapshots: (old, new) with a different number of calls
+    pub changed_edges: Vec<(SnapshotEdge, SnapshotEdge)>,
+    unchanged_edges: Vec<SnapshotEdge>,
+}
+
+// Edges are compared by caller and callee, call sites only count the calls
+// because line numbers change with every edit.
+pub fn diff(old: &Snapshot, new: &Snapshot) -> SnapshotDiff {
+    let old_edges = old.edge_map();
+    let new_edges = new.edge_map();
+
+    let mut result = SnapshotDiff {
+        added_nodes: new.nodes.difference(&old.nodes).cloned().collect(),
+        removed_nodes: old.nodes.difference(&new.nodes).cloned().collect(),
+        ..SnapshotDiff::default()
+    };
+    for (key, edge) in &new_edges {
+        match old_edges.get(key) {
+            None => result.added_edges.push((*edge).clone()),
+            Some(old_edge) if old_edge.call_sites.len() != edge.call_sites.len() => {
+                result.changed_edges.push(((*old_edge).clone(), (*edge).clone()))
+            }
+            Some(_) => result.unchanged_edges.push((*edge).clone()),
+        }
+    }
+    for (key, edge) in &old_edges {
+        if !new_edges.contains_key(key) {
+            result.removed_edges.push((*edge).clone());
+        }
+    }
+    result
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+            && self.changed_edges.is_empty()
+    }
+
+    // Both snapshots in one graph, added edges are green and removed edges red.
+    pub fn to_graph(&self) -> Graph {
+        let mut graph = Graph::new();
+        let edges = self.added_edges.iter().map(|edge| (edge, EdgeChange::Added))
+            .chain(self.removed_edges.iter().map(|edge| (edge, EdgeChange::Removed)))
+            .chain(self.changed_edges.iter().map(|(_, edge)| (edge, EdgeChange::Unchanged)))
+            .chain(self.unchanged_edges.iter().map(|edge| (edge, EdgeChange::Unchanged)));
+        for (edge, change) in edges {
+            graph.add_node(edge.caller.clone(), 1);
+            graph.add_node(edge.callee.clone(), 1);
+            if let Err(err) = graph.add_edge(edge.caller.clone(), edge.callee.clone(), edge.call_sites.clone()) {
+                log::error!("{}", err);
+            }
+            graph.mark_change(&edge.caller, &edge.callee, change);
+        }
+        for name in self.added_nodes.iter().chain(self.removed_nodes.iter()) {
+            graph.add_node(name.clone(), 1);
+        }
+        graph
+    }
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} edges added, {} edges removed, {} edges with changed calls, {} functions added, {} functions removed",
+            self.added_edges.len(),
+            self.removed_edges.len(),
+            self.changed_edges.len(),
+            self.added_nodes.len(),
+            self.removed_nodes.len()
+        )?;
+        for edge in &self.added_edges {
+            writeln!(f, "+ {} -> {}", edge.caller, edge.callee)?;
+        }
+        for edge in &self.removed_edges {
+            writeln!(f, "- {} -> {}", edge.caller, edge.callee)?;
+        }
+        for (old, new) in &self.changed_edges {
+            writeln!(f, "~ {} -> {} ({} -> {} calls)", new.caller, new.callee, old.call_sites.len(), new.call_sites.len())?;
+        }
+        for name in &self.added_nodes {
+            writeln!(f, "+ {}", name)?;
+        }
+        for name in &self.removed_nodes {
+            writeln!(f, "- {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod snapshot_test;
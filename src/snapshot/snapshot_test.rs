@@ -0,0 +1,61 @@
+use super::*;
+
+fn site(line: u32) -> CallSite {
+    CallSite { file: "main.c".to_string(), line, column: 5 }
+}
+
+fn graph(edges: &[(&str, &str, usize)]) -> Graph {
+    let mut graph = Graph::new();
+    for (start, end, calls) in edges {
+        graph.add_node(start.to_string(), 1);
+        graph.add_node(end.to_string(), 1);
+        let sites = (0..*calls).map(|i| site(i as u32 + 1)).collect();
+        graph.add_edge(start.to_string(), end.to_string(), sites).unwrap();
+    }
+    graph
+}
+
+#[test]
+fn test_snapshot_roundtrip() {
+    let snapshot = Snapshot::from_graph(&graph(&[("main", "init", 1), ("main", "run", 2)]));
+    let path = std::env::temp_dir().join(format!("code_analysis_snapshot_{}.json", std::process::id()));
+    snapshot.save(&path).unwrap();
+
+    let loaded = Snapshot::load(&path).unwrap();
+    assert_eq!(loaded, snapshot);
+    assert_eq!(loaded.edges[1].call_sites.len(), 2);
+    assert!(Snapshot::load(std::env::temp_dir().join("code_analysis_missing_snapshot.json")).is_err());
+}
+
+#[test]
+fn test_diff() {
+    let old = Snapshot::from_graph(&graph(&[("main", "init", 1), ("main", "legacy", 1), ("main", "run", 1)]));
+    let new = Snapshot::from_graph(&graph(&[("main", "init", 1), ("main", "run", 3), ("run", "worker", 1)]));
+
+    let result = diff(&old, &new);
+    assert_eq!(result.added_nodes, vec!["worker".to_string()]);
+    assert_eq!(result.removed_nodes, vec!["legacy".to_string()]);
+    assert_eq!(result.added_edges.len(), 1);
+    assert_eq!(result.removed_edges[0].callee, "legacy");
+    assert_eq!(result.changed_edges.len(), 1);
+    assert!(!result.is_empty());
+    assert!(diff(&new, &new).is_empty());
+
+    let summary = result.to_string();
+    assert!(summary.starts_with("1 edges added, 1 edges removed, 1 edges with changed calls"));
+    assert!(summary.contains("+ run -> worker"));
+    assert!(summary.contains("- main -> legacy"));
+    assert!(summary.contains("~ main -> run (1 -> 3 calls)"));
+}
+
+#[test]
+fn test_diff_graph_colors_edges() {
+    let old = Snapshot::from_graph(&graph(&[("main", "legacy", 1), ("main", "run", 1)]));
+    let new = Snapshot::from_graph(&graph(&[("main", "run", 1), ("run", "worker", 1)]));
+
+    let diff_graph = diff(&old, &new).to_graph();
+    assert_eq!(diff_graph.edge_count(), 3);
+    let dot = diff_graph.graph_to_dot();
+    assert_eq!(dot.matches("color = \"green\"").count(), 1);
+    assert_eq!(dot.matches("style = \"dashed\"").count(), 1);
+}
@@ -0,0 +1,255 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::call_index::CallIndex;
+
+const PAGERANK_DAMPING: f64 = 0.85;
+const PAGERANK_ITERATIONS: usize = 100;
+const PAGERANK_TOLERANCE: f64 = 1e-10;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionStats {
+    pub name: String,
+    pub file: String,
+    pub fan_in: usize,
+    pub fan_out: usize,
+    pub transitive_callers: usize,
+    pub transitive_callees: usize,
+    pub betweenness: f64,
+    pub pagerank: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsColumn {
+    Name,
+    FanIn,
+    FanOut,
+    TransitiveCallers,
+    TransitiveCallees,
+    Betweenness,
+    PageRank,
+}
+
+impl FromStr for StatsColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.replace('-', "_").as_str() {
+            "name" => Ok(StatsColumn::Name),
+            "fan_in" => Ok(StatsColumn::FanIn),
+            "fan_out" => Ok(StatsColumn::FanOut),
+            "transitive_callers" => Ok(StatsColumn::TransitiveCallers),
+            "transitive_callees" => Ok(StatsColumn::TransitiveCallees),
+            "betweenness" => Ok(StatsColumn::Betweenness),
+            "pagerank" => Ok(StatsColumn::PageRank),
+            _ => Err(format!("unknown column '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for StatsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(StatsFormat::Csv),
+            "json" => Ok(StatsFormat::Json),
+            _ => Err(format!("unknown format '{}', expected csv or json", s)),
+        }
+    }
+}
+
+impl fmt::Display for StatsFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatsFormat::Csv => write!(f, "csv"),
+            StatsFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+// Metrics for every function defined in `index` over its calls. Self calls
+// are ignored.
+pub fn compute_stats(index: &dyn CallIndex) -> Vec<FunctionStats> {
+    let mut names: Vec<(&str, &str)> = index.definitions().map(|(file, function)| (function, file)).collect();
+    names.sort();
+    names.dedup_by(|a, b| a.0 == b.0);
+    let ids: HashMap<&str, usize> = names.iter().enumerate().map(|(id, (name, _))| (*name, id)).collect();
+
+    let mut callees: Vec<Vec<usize>> = vec![Vec::new(); names.len()];
+    let mut callers: Vec<Vec<usize>> = vec![Vec::new(); names.len()];
+    for (caller_name, _) in &names {
+        let caller = ids[caller_name];
+        for callee in index.callees(caller_name) {
+            if let Some(&callee) = ids.get(callee) {
+                if callee != caller && !callees[caller].contains(&callee) {
+                    callees[caller].push(callee);
+                    callers[callee].push(caller);
+                }
+            }
+        }
+    }
+
+    let (betweenness, transitive_callees, transitive_callers) = brandes(&callees);
+    let pagerank = pagerank(&callees);
+
+    names
+        .iter()
+        .enumerate()
+        .map(|(id, (name, file))| FunctionStats {
+            name: name.to_string(),
+            file: file.to_string(),
+            fan_in: callers[id].len(),
+            fan_out: callees[id].len(),
+            transitive_callers: transitive_callers[id],
+            transitive_callees: transitive_callees[id],
+            betweenness: betweenness[id],
+            pagerank: pagerank[id],
+        })
+        .collect()
+}
+
+// Brandes' algorithm for betweenness centrality on an unweighted directed
+// graph. The breadth first search from every function also gives the number
+// of functions it reaches and the number of functions it is reached from.
+fn brandes(callees: &[Vec<usize>]) -> (Vec<f64>, Vec<usize>, Vec<usize>) {
+    let n = callees.len();
+    let mut betweenness = vec![0.0; n];
+    let mut reaches = vec![0; n];
+    let mut reached_by = vec![0; n];
+
+    let mut stack: Vec<usize> = Vec::with_capacity(n);
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut paths = vec![0.0f64; n];
+    let mut distance = vec![-1i64; n];
+    let mut dependency = vec![0.0f64; n];
+    let mut queue = VecDeque::new();
+
+    for source in 0..n {
+        for v in stack.drain(..) {
+            predecessors[v].clear();
+            paths[v] = 0.0;
+            distance[v] = -1;
+            dependency[v] = 0.0;
+        }
+        paths[source] = 1.0;
+        distance[source] = 0;
+        queue.push_back(source);
+
+        while let Some(v) = queue.pop_front() {
+            stack.push(v);
+            for &w in &callees[v] {
+                if distance[w] < 0 {
+                    distance[w] = distance[v] + 1;
+                    queue.push_back(w);
+                }
+                if distance[w] == distance[v] + 1 {
+                    paths[w] += paths[v];
+                    predecessors[w].push(v);
+                }
+            }
+        }
+
+        reaches[source] = stack.len() - 1;
+        for &v in stack.iter().skip(1) {
+            reached_by[v] += 1;
+        }
+        for &w in stack.iter().rev() {
+            for &v in &predecessors[w] {
+                dependency[v] += paths[v] / paths[w] * (1.0 + dependency[w]);
+            }
+            if w != source {
+                betweenness[w] += dependency[w];
+            }
+        }
+    }
+    (betweenness, reaches, reached_by)
+}
+
+// Functions without callees spread their rank evenly over all functions.
+fn pagerank(callees: &[Vec<usize>]) -> Vec<f64> {
+    let n = callees.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let base = (1.0 - PAGERANK_DAMPING) / n as f64;
+    let mut rank = vec![1.0 / n as f64; n];
+
+    for _ in 0..PAGERANK_ITERATIONS {
+        let dangling: f64 = (0..n).filter(|&v| callees[v].is_empty()).map(|v| rank[v]).sum();
+        let mut next = vec![base + PAGERANK_DAMPING * dangling / n as f64; n];
+        for (v, targets) in callees.iter().enumerate() {
+            if targets.is_empty() {
+                continue;
+            }
+            let share = PAGERANK_DAMPING * rank[v] / targets.len() as f64;
+            for &w in targets {
+                next[w] += share;
+            }
+        }
+        let change: f64 = next.iter().zip(&rank).map(|(a, b)| (a - b).abs()).sum();
+        rank = next;
+        if change < PAGERANK_TOLERANCE {
+            break;
+        }
+    }
+    rank
+}
+
+// Sorts by the column, numbers from high to low and names alphabetically.
+pub fn sort_stats(stats: &mut [FunctionStats], column: StatsColumn) {
+    stats.sort_by(|a, b| {
+        let order = match column {
+            StatsColumn::Name => a.name.cmp(&b.name),
+            StatsColumn::FanIn => b.fan_in.cmp(&a.fan_in),
+            StatsColumn::FanOut => b.fan_out.cmp(&a.fan_out),
+            StatsColumn::TransitiveCallers => b.transitive_callers.cmp(&a.transitive_callers),
+            StatsColumn::TransitiveCallees => b.transitive_callees.cmp(&a.transitive_callees),
+            StatsColumn::Betweenness => b.betweenness.total_cmp(&a.betweenness),
+            StatsColumn::PageRank => b.pagerank.total_cmp(&a.pagerank),
+        };
+        order.then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+pub fn stats_to_csv(stats: &[FunctionStats]) -> String {
+    let mut csv = String::from("name,file,fan_in,fan_out,transitive_callers,transitive_callees,betweenness,pagerank\n");
+    for s in stats {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{:.3},{:.6}\n",
+            csv_field(&s.name),
+            csv_field(&s.file),
+            s.fan_in,
+            s.fan_out,
+            s.transitive_callers,
+            s.transitive_callees,
+            s.betweenness,
+            s.pagerank
+        ));
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn stats_to_json(stats: &[FunctionStats]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(stats)
+}
+
+#[cfg(test)]
+mod stats_test;
@@ -0,0 +1,91 @@
+use super::*;
+use crate::call_index::MapIndex;
+
+fn strings(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+// main -> parse -> {lex, alloc}, main -> run -> alloc, alloc -> alloc
+fn test_stats() -> Vec<FunctionStats> {
+    let index_map = HashMap::from([
+        ("main.c".to_string(), strings(&["main", "parse", "run"])),
+        ("util.c".to_string(), strings(&["lex", "alloc"])),
+    ]);
+    let function_calls = HashMap::from([
+        ("main".to_string(), strings(&["parse", "run"])),
+        ("parse".to_string(), strings(&["lex", "alloc", "alloc"])),
+        ("run".to_string(), strings(&["alloc", "printf"])),
+        ("alloc".to_string(), strings(&["alloc"])),
+    ]);
+    compute_stats(&MapIndex::new(index_map, function_calls))
+}
+
+fn find<'a>(stats: &'a [FunctionStats], name: &str) -> &'a FunctionStats {
+    stats.iter().find(|s| s.name == name).unwrap()
+}
+
+#[test]
+fn test_fan_in_and_fan_out() {
+    let stats = test_stats();
+    assert_eq!(stats.len(), 5);
+
+    let alloc = find(&stats, "alloc");
+    assert_eq!((alloc.fan_in, alloc.fan_out), (2, 0));
+    assert_eq!(alloc.file, "util.c");
+    let parse = find(&stats, "parse");
+    assert_eq!((parse.fan_in, parse.fan_out), (1, 2));
+    assert_eq!(find(&stats, "run").fan_out, 1);
+}
+
+#[test]
+fn test_transitive_counts() {
+    let stats = test_stats();
+    let main = find(&stats, "main");
+    assert_eq!((main.transitive_callers, main.transitive_callees), (0, 4));
+    let alloc = find(&stats, "alloc");
+    assert_eq!((alloc.transitive_callers, alloc.transitive_callees), (3, 0));
+}
+
+#[test]
+fn test_betweenness() {
+    let stats = test_stats();
+    // main -> lex and main -> alloc (one of two shortest paths) go through parse
+    assert!((find(&stats, "parse").betweenness - 1.5).abs() < 1e-9);
+    assert!((find(&stats, "run").betweenness - 0.5).abs() < 1e-9);
+    assert_eq!(find(&stats, "main").betweenness, 0.0);
+    assert_eq!(find(&stats, "alloc").betweenness, 0.0);
+}
+
+#[test]
+fn test_pagerank() {
+    let stats = test_stats();
+    let total: f64 = stats.iter().map(|s| s.pagerank).sum();
+    assert!((total - 1.0).abs() < 1e-6);
+    assert!(find(&stats, "alloc").pagerank > find(&stats, "lex").pagerank);
+    assert!(find(&stats, "lex").pagerank > find(&stats, "main").pagerank);
+}
+
+#[test]
+fn test_sort_and_export() {
+    let mut stats = test_stats();
+    sort_stats(&mut stats, "fan-in".parse().unwrap());
+    assert_eq!(stats[0].name, "alloc");
+    sort_stats(&mut stats, StatsColumn::Betweenness);
+    assert_eq!(stats[0].name, "parse");
+
+    let csv = stats_to_csv(&stats);
+    assert!(csv.starts_with("name,file,fan_in"));
+    assert!(csv.lines().nth(1).unwrap().starts_with("parse,main.c,1,2,1,2,1.500,"));
+
+    let json: serde_json::Value = serde_json::from_str(&stats_to_json(&stats).unwrap()).unwrap();
+    assert_eq!(json[0]["name"], "parse");
+    assert_eq!(json.as_array().unwrap().len(), 5);
+}
+
+#[test]
+fn test_stats_format_roundtrip() {
+    for format in [StatsFormat::Csv, StatsFormat::Json] {
+        assert_eq!(format.to_string().parse::<StatsFormat>().unwrap(), format);
+    }
+    assert!("jsn".parse::<StatsFormat>().is_err());
+}
@@ -0,0 +1,84 @@
+use std::collections::{HashMap, HashSet};
+
+use regex::Regex;
+
+use crate::analyzer::FilterName;
+use crate::call_index::{CallIndex, MapIndex};
+use crate::searcher::{FunctionNode, LSPServer, Links, ParentChildNode};
+
+// (file, function, first line, last line) of the project of `MockLSPServer`
+const FUNCTIONS: &[(&str, &str, u32, u32)] = &[
+    ("main.c", "main", 1, 10),
+    ("main.c", "print", 12, 20),
+    ("parse.c", "parse", 2, 4),
+    ("parse.c", "parse_args", 6, 8),
+];
+
+const CALLS: &[(&str, &[&str])] = &[("main", &["parse", "print"]), ("parse", &["print"])];
+
+// A language server over a small fixed project, for the tests of everything
+// that runs queries through an analyzer. Edges come from find_link, like
+// with clangd.
+pub struct MockLSPServer {
+    index: MapIndex,
+}
+
+impl MockLSPServer {
+    pub fn boxed() -> Box<dyn LSPServer> {
+        let mut index_map: HashMap<String, Vec<String>> = HashMap::new();
+        for (file, function, _, _) in FUNCTIONS {
+            index_map.entry(file.to_string()).or_default().push(function.to_string());
+        }
+        let function_calls = CALLS
+            .iter()
+            .map(|(caller, callees)| (caller.to_string(), callees.iter().map(|callee| callee.to_string()).collect()))
+            .collect();
+        Box::new(MockLSPServer { index: MapIndex::new(index_map, function_calls) })
+    }
+}
+
+impl LSPServer for MockLSPServer {
+    fn find_func_name(&mut self, filter: Vec<HashMap<FilterName, Regex>>) -> HashSet<FunctionNode> {
+        let mut names: HashSet<String> = HashSet::new();
+        for f in filter {
+            if let Some(regex) = f.get(&FilterName::FunctionNameFromIdent) {
+                names.insert(regex.as_str().to_string());
+            }
+        }
+        if names.is_empty() {
+            names = self.index.definitions().map(|(_, name)| name.to_string()).collect();
+        }
+        let node = ParentChildNode { function_name: names };
+        HashSet::from([FunctionNode { function_name: node.function_name.clone(), match_strategy: Box::new(node) }])
+    }
+
+    fn find_link(&mut self, parent_name: HashSet<String>, child_name: HashSet<String>) -> Links {
+        let mut result: Links = HashMap::new();
+        for caller in self.index.functions() {
+            for callee in self.index.callees(caller) {
+                if parent_name.contains(caller) && child_name.contains(callee) {
+                    result.insert((caller.to_string(), callee.to_string()), Vec::new());
+                }
+            }
+        }
+        result
+    }
+
+    fn call_index(&mut self) -> Box<dyn CallIndex + '_> {
+        Box::new(&self.index)
+    }
+
+    fn address_taken_functions(&mut self) -> HashSet<String> {
+        HashSet::new()
+    }
+
+    fn function_ranges(&mut self, file: &str) -> Vec<(String, u32, u32)> {
+        FUNCTIONS
+            .iter()
+            .filter(|function| function.0 == file)
+            .map(|(_, name, start, end)| (name.to_string(), *start, *end))
+            .collect()
+    }
+
+    fn close(&mut self) {}
+}
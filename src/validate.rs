@@ -0,0 +1,178 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use pest::error::{Error, ErrorVariant, LineColLocation};
+use pest::iterators::Pair;
+use pest::Span;
+use regex::Regex;
+
+use crate::ast_generator::{self, Rule};
+
+// verbs with a meaning of their own, any other verb names a function
+pub const KEYWORD_VERBS: &[&str] = &["filter", "forced", "path", "recursive", "cycles"];
+
+// The named parameters a verb understands.
+pub fn verb_parameters(verb: &str) -> &'static [&'static str] {
+    match verb {
+        "filter" => &["function", "file"],
+        "path" => &["from", "to", "k", "depth"],
+        "recursive" | "cycles" => &["file"],
+        _ => &[],
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// Positions are 0-based lines and character columns, `end` is exclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryDiagnostic {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for QueryDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.start.0 + 1, self.start.1 + 1, self.message)
+    }
+}
+
+impl QueryDiagnostic {
+    fn at(span: Span, severity: Severity, message: String) -> QueryDiagnostic {
+        let (start_line, start_column) = span.start_pos().line_col();
+        let (end_line, end_column) = span.end_pos().line_col();
+        QueryDiagnostic {
+            start: (start_line - 1, start_column - 1),
+            end: (end_line - 1, end_column - 1),
+            severity,
+            message,
+        }
+    }
+}
+
+pub fn syntax_error(err: &Error<Rule>) -> QueryDiagnostic {
+    let (start, end) = match err.line_col {
+        LineColLocation::Pos((line, column)) => ((line - 1, column - 1), (line - 1, column)),
+        LineColLocation::Span((start_line, start_column), (end_line, end_column)) => {
+            ((start_line - 1, start_column - 1), (end_line - 1, end_column - 1))
+        }
+    };
+    QueryDiagnostic { start, end, severity: Severity::Error, message: variant_message(&err.variant) }
+}
+
+fn variant_message(variant: &ErrorVariant<Rule>) -> String {
+    let rules = |rules: &[Rule]| rules.iter().map(|rule| format!("{:?}", rule)).collect::<Vec<_>>().join(", ");
+    match variant {
+        ErrorVariant::ParsingError { positives, negatives } => match (positives.is_empty(), negatives.is_empty()) {
+            (false, false) => format!("unexpected {}; expected {}", rules(negatives), rules(positives)),
+            (false, true) => format!("expected {}", rules(positives)),
+            (true, false) => format!("unexpected {}", rules(negatives)),
+            (true, true) => "unknown parsing error".to_string(),
+        },
+        ErrorVariant::CustomError { message } => message.clone(),
+    }
+}
+
+// Checks what the grammar cannot: parameter names and values, regexes and,
+// when the indexed functions are given, that function verbs name one of them.
+// `$parameters` are not checked.
+pub fn validate(source: &str, functions: Option<&HashSet<String>>) -> Vec<QueryDiagnostic> {
+    validate_with(source, functions, None)
+}
+
+// Like `validate`, with `parameters` every `$parameter` has to be bound and
+// its value is checked in place of the parameter.
+pub fn validate_with(source: &str, functions: Option<&HashSet<String>>, parameters: Option<&HashMap<String, String>>) -> Vec<QueryDiagnostic> {
+    let pairs = match ast_generator::parse_grammar(source) {
+        Ok(pairs) => pairs,
+        Err(err) => return vec![syntax_error(&err)],
+    };
+    let mut diagnostics = Vec::new();
+    for pair in pairs.flatten().filter(|pair| pair.as_rule() == Rule::verb) {
+        check_verb(pair, functions, parameters, &mut diagnostics);
+    }
+    diagnostics
+}
+
+// The value of `pair`, None for a parameter whose value is not known.
+fn resolve<'a>(pair: &Pair<'a, Rule>, parameters: Option<&'a HashMap<String, String>>, diagnostics: &mut Vec<QueryDiagnostic>) -> Option<&'a str> {
+    if pair.as_rule() != Rule::parameter {
+        return Some(pair.as_str());
+    }
+    let name = pair.as_str().trim_start_matches('$');
+    let value = parameters?.get(name).map(String::as_str);
+    if value.is_none() {
+        diagnostics.push(QueryDiagnostic::at(pair.as_span(), Severity::Error, format!("parameter ${} is not bound", name)));
+    }
+    value
+}
+
+fn check_verb(verb: Pair<Rule>, functions: Option<&HashSet<String>>, parameters: Option<&HashMap<String, String>>, diagnostics: &mut Vec<QueryDiagnostic>) {
+    let mut inner = verb.into_inner();
+    let ident = match inner.next() {
+        Some(ident) => ident,
+        None => return,
+    };
+    // a parameter always names a function, unknown while it is not bound
+    let resolved = resolve(&ident, parameters, diagnostics);
+    let name = resolved.unwrap_or_else(|| ident.as_str());
+    if let (Rule::parameter, Some(Err(err))) = (ident.as_rule(), resolved.map(Regex::new)) {
+        diagnostics.push(QueryDiagnostic::at(ident.as_span(), Severity::Error, format!("invalid function name: {}", err)));
+    }
+    let is_function = ident.as_rule() == Rule::parameter || !KEYWORD_VERBS.contains(&name);
+    let allowed = if is_function { &[] } else { verb_parameters(name) };
+    let mut given = HashSet::new();
+
+    for parameter in inner {
+        let mut parts = parameter.clone().into_inner();
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key, value),
+            _ => continue,
+        };
+        let key_name = key.as_str().to_lowercase();
+        if allowed.is_empty() {
+            diagnostics.push(QueryDiagnostic::at(parameter.as_span(), Severity::Error, format!("@{} takes no parameters", name)));
+            continue;
+        }
+        if !allowed.contains(&key_name.as_str()) {
+            let message = format!("unknown parameter '{}' for @{}, expected {}", key.as_str(), name, allowed.join(", "));
+            diagnostics.push(QueryDiagnostic::at(key.as_span(), Severity::Error, message));
+            continue;
+        }
+        given.insert(key_name.clone());
+        let value_str = match resolve(&value, parameters, diagnostics) {
+            Some(value_str) => value_str,
+            None => continue,
+        };
+        if key_name == "k" || key_name == "depth" {
+            if value_str.parse::<usize>().is_err() {
+                let message = format!("{} must be a number", key_name);
+                diagnostics.push(QueryDiagnostic::at(value.as_span(), Severity::Error, message));
+            }
+        } else if let Err(err) = Regex::new(value_str) {
+            diagnostics.push(QueryDiagnostic::at(value.as_span(), Severity::Error, format!("invalid regex: {}", err)));
+        }
+    }
+
+    if !is_function && name == "path" {
+        for required in ["from", "to"] {
+            if !given.contains(required) {
+                diagnostics.push(QueryDiagnostic::at(ident.as_span(), Severity::Error, format!("@path needs {}=\"...\"", required)));
+            }
+        }
+    } else if is_function {
+        if let (Some(functions), Some(name)) = (functions, resolved) {
+            if !functions.contains(name) {
+                diagnostics.push(QueryDiagnostic::at(ident.as_span(), Severity::Warning, format!("{} is not in the index", name)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod validate_test;
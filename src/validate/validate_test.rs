@@ -0,0 +1,73 @@
+use super::*;
+
+#[test]
+fn test_valid_query() {
+    let functions = HashSet::from(["main".to_string()]);
+    assert_eq!(validate("@filter(function=\"ma.*\")\n{@main}", Some(&functions)), Vec::new());
+    assert_eq!(validate("@path(from=\"main\",to=\"exit\",k=\"3\")", None), Vec::new());
+}
+
+#[test]
+fn test_syntax_error() {
+    let diagnostics = validate("{@main", None);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(diagnostics[0].start, (0, 6));
+}
+
+#[test]
+fn test_parameter_errors() {
+    let diagnostics = validate("@filter(name=\"x\")\n@main(file=\"x\")\n@path(from=\"a\",k=\"many\")", None);
+    let messages: Vec<(usize, &str)> = diagnostics.iter().map(|d| (d.start.0, d.message.as_str())).collect();
+    assert_eq!(
+        messages,
+        vec![
+            (0, "unknown parameter 'name' for @filter, expected function, file"),
+            (1, "@main takes no parameters"),
+            (2, "k must be a number"),
+            (2, "@path needs to=\"...\""),
+        ]
+    );
+    assert_eq!(diagnostics[0].start, (0, 8));
+    assert_eq!(diagnostics[0].end, (0, 12));
+}
+
+#[test]
+fn test_invalid_regex() {
+    let diagnostics = validate("@filter(file=\"(\")", None);
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].message.starts_with("invalid regex"));
+}
+
+#[test]
+fn test_unknown_function() {
+    let functions = HashSet::from(["main".to_string()]);
+    let diagnostics = validate("{@mian}", Some(&functions));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert_eq!((diagnostics[0].start, diagnostics[0].end), ((0, 2), (0, 6)));
+}
+
+#[test]
+fn test_parameters() {
+    let source = "{@$target}\n@path(from=\"$from\",to=\"b\",k=\"$k\")";
+    assert!(validate(source, None).is_empty());
+
+    let unbound = validate_with(source, None, Some(&HashMap::new()));
+    let messages: Vec<&str> = unbound.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(messages, vec!["parameter $target is not bound", "parameter $from is not bound", "parameter $k is not bound"]);
+    assert_eq!((unbound[0].start, unbound[0].end), ((0, 2), (0, 9)));
+
+    let parameters = HashMap::from([
+        ("target".to_string(), "mian".to_string()),
+        ("from".to_string(), "(".to_string()),
+        ("k".to_string(), "many".to_string()),
+    ]);
+    let functions = HashSet::from(["main".to_string()]);
+    let diagnostics = validate_with(source, Some(&functions), Some(&parameters));
+    let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(messages.len(), 3, "{:?}", messages);
+    assert_eq!(messages[0], "mian is not in the index");
+    assert!(messages[1].starts_with("invalid regex"));
+    assert_eq!(messages[2], "k must be a number");
+}
@@ -0,0 +1,310 @@
+// Single page viewer for the HTTP server mode. Everything is drawn with plain
+// SVG so the page works without network access.
+"use strict";
+
+const SVG = "http://www.w3.org/2000/svg";
+
+const state = {
+  nodes: new Map(),
+  edges: new Map(),
+  selected: null,
+  view: { x: -400, y: -300, width: 800, height: 600 },
+  running: false,
+};
+
+const svg = document.getElementById("graph");
+const status = document.getElementById("status");
+
+function setStatus(text, isError) {
+  status.textContent = text;
+  status.classList.toggle("error", Boolean(isError));
+}
+
+async function request(url, options) {
+  const response = await fetch(url, options);
+  const text = await response.text();
+  const body = text ? JSON.parse(text) : null;
+  if (!response.ok) {
+    throw new Error(body && body.error ? body.error : response.statusText);
+  }
+  return body;
+}
+
+function graphql(query) {
+  return request("/graphql", {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ query }),
+  }).then((response) => {
+    if (response.errors) {
+      throw new Error(response.errors.map((error) => error.message).join("\n"));
+    }
+    return response.data;
+  });
+}
+
+function addNode(name, near) {
+  if (!state.nodes.has(name)) {
+    const origin = near ? state.nodes.get(near) : null;
+    state.nodes.set(name, {
+      name,
+      x: (origin ? origin.x : 0) + (Math.random() - 0.5) * 100,
+      y: (origin ? origin.y : 0) + (Math.random() - 0.5) * 100,
+      vx: 0,
+      vy: 0,
+    });
+  }
+}
+
+function addEdge(caller, callee, calls, near) {
+  addNode(caller, near);
+  addNode(callee, near);
+  const key = caller + "\u0000" + callee;
+  if (!state.edges.has(key)) {
+    state.edges.set(key, { source: caller, target: callee, calls: calls || 0 });
+  }
+}
+
+// Repulsion between all nodes, springs along edges and a weak pull to the
+// center. Stops once the layout has settled.
+function step() {
+  const nodes = Array.from(state.nodes.values());
+  for (let i = 0; i < nodes.length; i++) {
+    for (let j = i + 1; j < nodes.length; j++) {
+      const a = nodes[i];
+      const b = nodes[j];
+      let dx = b.x - a.x;
+      let dy = b.y - a.y;
+      const distance = Math.max(Math.hypot(dx, dy), 1);
+      const force = 4000 / (distance * distance);
+      dx /= distance;
+      dy /= distance;
+      a.vx -= dx * force;
+      a.vy -= dy * force;
+      b.vx += dx * force;
+      b.vy += dy * force;
+    }
+  }
+  for (const edge of state.edges.values()) {
+    const a = state.nodes.get(edge.source);
+    const b = state.nodes.get(edge.target);
+    const dx = b.x - a.x;
+    const dy = b.y - a.y;
+    const distance = Math.max(Math.hypot(dx, dy), 1);
+    const force = (distance - 120) * 0.02;
+    a.vx += (dx / distance) * force;
+    a.vy += (dy / distance) * force;
+    b.vx -= (dx / distance) * force;
+    b.vy -= (dy / distance) * force;
+  }
+  let energy = 0;
+  for (const node of nodes) {
+    node.vx = (node.vx - node.x * 0.002) * 0.8;
+    node.vy = (node.vy - node.y * 0.002) * 0.8;
+    if (!node.dragged) {
+      node.x += node.vx;
+      node.y += node.vy;
+    }
+    energy += node.vx * node.vx + node.vy * node.vy;
+  }
+  return energy / Math.max(nodes.length, 1);
+}
+
+function simulate() {
+  if (state.running) {
+    return;
+  }
+  state.running = true;
+  let ticks = 0;
+  const tick = () => {
+    const energy = step();
+    render();
+    if (energy > 0.01 && ticks++ < 1000) {
+      requestAnimationFrame(tick);
+    } else {
+      state.running = false;
+    }
+  };
+  requestAnimationFrame(tick);
+}
+
+function element(name, attributes, parent) {
+  const node = document.createElementNS(SVG, name);
+  for (const [key, value] of Object.entries(attributes)) {
+    node.setAttribute(key, value);
+  }
+  if (parent) {
+    parent.appendChild(node);
+  }
+  return node;
+}
+
+function render() {
+  const view = state.view;
+  svg.setAttribute("viewBox", `${view.x} ${view.y} ${view.width} ${view.height}`);
+  svg.replaceChildren();
+  const marker = element("marker", {
+    id: "arrow", viewBox: "0 0 10 10", refX: 22, refY: 5,
+    markerWidth: 6, markerHeight: 6, orient: "auto",
+  }, element("defs", {}, svg));
+  element("path", { d: "M 0 0 L 10 5 L 0 10 z", fill: "#999" }, marker);
+
+  for (const edge of state.edges.values()) {
+    const a = state.nodes.get(edge.source);
+    const b = state.nodes.get(edge.target);
+    const line = element("line", { class: "edge", x1: a.x, y1: a.y, x2: b.x, y2: b.y }, svg);
+    if (edge.calls) {
+      element("title", {}, line).textContent = `called ${edge.calls}×`;
+    }
+  }
+  for (const node of state.nodes.values()) {
+    const group = element("g", { class: "node", transform: `translate(${node.x},${node.y})` }, svg);
+    if (node.name === state.selected) {
+      group.classList.add("selected");
+    }
+    const circle = element("circle", { r: 8 }, group);
+    element("text", { x: 12, y: 4 }, group).textContent = node.name;
+    circle.addEventListener("mousedown", (event) => startDrag(event, node));
+    circle.addEventListener("click", () => select(node.name));
+  }
+}
+
+function toGraph(event) {
+  const box = svg.getBoundingClientRect();
+  return {
+    x: state.view.x + ((event.clientX - box.left) / box.width) * state.view.width,
+    y: state.view.y + ((event.clientY - box.top) / box.height) * state.view.height,
+  };
+}
+
+function startDrag(event, node) {
+  event.stopPropagation();
+  node.dragged = true;
+  const move = (moveEvent) => {
+    const point = toGraph(moveEvent);
+    node.x = point.x;
+    node.y = point.y;
+    render();
+  };
+  const stop = () => {
+    node.dragged = false;
+    window.removeEventListener("mousemove", move);
+    window.removeEventListener("mouseup", stop);
+    simulate();
+  };
+  window.addEventListener("mousemove", move);
+  window.addEventListener("mouseup", stop);
+}
+
+// Dragging the background pans, the mouse wheel zooms.
+svg.addEventListener("mousedown", (event) => {
+  const start = toGraph(event);
+  const move = (moveEvent) => {
+    const point = toGraph(moveEvent);
+    state.view.x -= point.x - start.x;
+    state.view.y -= point.y - start.y;
+    render();
+  };
+  const stop = () => {
+    window.removeEventListener("mousemove", move);
+    window.removeEventListener("mouseup", stop);
+  };
+  window.addEventListener("mousemove", move);
+  window.addEventListener("mouseup", stop);
+});
+
+svg.addEventListener("wheel", (event) => {
+  event.preventDefault();
+  const point = toGraph(event);
+  const scale = event.deltaY > 0 ? 1.1 : 1 / 1.1;
+  const view = state.view;
+  view.x = point.x - (point.x - view.x) * scale;
+  view.y = point.y - (point.y - view.y) * scale;
+  view.width *= scale;
+  view.height *= scale;
+  render();
+}, { passive: false });
+
+async function runQuery() {
+  setStatus("running…");
+  try {
+    const result = await request("/query?format=json", {
+      method: "POST",
+      body: document.getElementById("query").value,
+    });
+    state.nodes.clear();
+    state.edges.clear();
+    state.selected = null;
+    document.getElementById("details").hidden = true;
+    for (const name of result.nodes) {
+      addNode(name);
+    }
+    for (const edge of result.edges) {
+      addEdge(edge.caller, edge.callee, edge.call_sites.length);
+    }
+    setStatus(`${result.nodes.length} functions, ${result.edges.length} calls`);
+    render();
+    simulate();
+  } catch (error) {
+    setStatus(error.message, true);
+  }
+}
+
+async function select(name) {
+  state.selected = name;
+  render();
+  document.getElementById("details").hidden = false;
+  document.getElementById("function-name").textContent = name;
+  document.getElementById("function-location").textContent = "";
+  const source = document.getElementById("source");
+  source.textContent = "";
+  try {
+    const result = await request("/source?function=" + encodeURIComponent(name));
+    document.getElementById("function-location").textContent =
+      `${result.file}:${result.start_line}-${result.end_line}`;
+    source.textContent = result.lines
+      .map((line, i) => String(result.start_line + i).padStart(5) + "  " + line)
+      .join("\n");
+  } catch (error) {
+    source.textContent = error.message;
+  }
+}
+
+async function expand(direction) {
+  const name = state.selected;
+  if (!name) {
+    return;
+  }
+  try {
+    const data = await graphql(
+      `{ function(name: ${JSON.stringify(name)}) { ${direction}(first: 100) { totalCount functions { name } } } }`
+    );
+    if (!data.function) {
+      throw new Error(`${name} is not in the index`);
+    }
+    const page = data.function[direction];
+    for (const other of page.functions) {
+      if (direction === "callers") {
+        addEdge(other.name, name, 0, name);
+      } else {
+        addEdge(name, other.name, 0, name);
+      }
+    }
+    setStatus(`${page.totalCount} ${direction} of ${name}` +
+      (page.totalCount > page.functions.length ? `, showing ${page.functions.length}` : ""));
+    render();
+    simulate();
+  } catch (error) {
+    setStatus(error.message, true);
+  }
+}
+
+document.getElementById("run").addEventListener("click", runQuery);
+document.getElementById("query").addEventListener("keydown", (event) => {
+  if (event.key === "Enter" && (event.ctrlKey || event.metaKey)) {
+    runQuery();
+  }
+});
+document.getElementById("expand-callers").addEventListener("click", () => expand("callers"));
+document.getElementById("expand-callees").addEventListener("click", () => expand("callees"));
+render();